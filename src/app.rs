@@ -1,3 +1,9 @@
+use std::{
+  collections::{HashMap, VecDeque},
+  path::Path,
+  time::Duration,
+};
+
 use anyhow::bail;
 use crossterm::event::{
   Event, KeyEvent, KeyEventKind, MouseButton, MouseEventKind,
@@ -8,9 +14,12 @@ use termwiz::escape::csi::CursorStyle;
 use tokio::{
   io::AsyncReadExt,
   sync::mpsc::{UnboundedReceiver, UnboundedSender},
+  task::JoinHandle,
+  time::MissedTickBehavior,
 };
 use tui::{
   layout::{Constraint, Direction, Layout, Margin, Rect},
+  style::{Color, Style},
   widgets::Widget,
   Terminal,
 };
@@ -18,35 +27,62 @@ use vt100::Size;
 
 use crate::{
   config::{CmdConfig, Config, ProcConfig, ServerConfig},
+  deps::{procs_to_start, unknown_deps_warning},
   error::ResultLogger,
-  event::AppEvent,
+  event::{AppEvent, ProcSort},
   host::{
     receiver::MsgReceiver, sender::MsgSender, socket::bind_server_socket,
   },
+  just::split_args,
   kernel::kernel_message::{KernelMessage, KernelSender},
   key::Key,
   keymap::Keymap,
   modal::{
-    add_proc::AddProcModal, commands_menu::CommandsMenuModal, modal::Modal,
+    add_proc::AddProcModal, commands_menu::CommandsMenuModal,
+    confirm::ConfirmModal, copy_search::CopySearchModal,
+    edit_proc_cmd::EditProcCmdModal, modal::Modal, proc_args::ProcArgsModal,
     quit::QuitModal, remove_proc::RemoveProcModal,
     rename_proc::RenameProcModal,
   },
   mouse::MouseEvent,
+  placeholder::ProcVars,
   proc::{
     create_proc,
     msg::{ProcCmd, ProcEvent},
-    StopSignal,
+    paste_clipboard,
   },
   protocol::{CltToSrv, ProxyBackend, SrvToClt},
-  state::{Scope, State},
+  run_cmd::run_command,
+  session::Session,
+  settings::{OnAllFinished, Settings},
+  state::{ProcDrag, Scope, State},
+  theme::Theme,
   ui_keymap::render_keymap,
-  ui_procs::{procs_check_hit, procs_get_clicked_index, render_procs},
-  ui_term::{render_term, term_check_hit},
+  ui_procs::{
+    procs_border_hit, procs_check_hit, procs_get_clicked_index, render_procs,
+  },
+  ui_term::{render_term, scrollbar_hit, term_check_hit},
   ui_zoom_tip::render_zoom_tip,
+  watch::WatchAction,
 };
 
 type Term = Terminal<ProxyBackend>;
 
+/// Number of columns moved by `scroll-left` and `scroll-right`.
+const H_SCROLL_STEP: usize = 8;
+
+/// Columns added or removed by `grow-sidebar` and `shrink-sidebar`.
+const SIDEBAR_STEP: usize = 2;
+
+/// Min width of the process list when it is resized at runtime.
+const SIDEBAR_MIN_WIDTH: usize = 8;
+
+/// Min width left for the terminal when the process list is widened.
+const TERM_MIN_WIDTH: usize = 10;
+
+/// Interval of the idle tick that updates time-dependent UI.
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
 #[derive(Debug, Default, PartialEq)]
 pub enum LoopAction {
   Render,
@@ -71,6 +107,8 @@ impl LoopAction {
 
 pub struct App {
   config: Config,
+  /// Options for procs added at runtime.
+  settings: Settings,
   keymap: Keymap,
   state: State,
   modal: Option<Box<dyn Modal>>,
@@ -83,10 +121,14 @@ pub struct App {
 
   screen_size: Size,
   clients: Vec<ClientHandle>,
+
+  /// Proc output changed and the screen should be redrawn on the next frame.
+  render_dirty: bool,
 }
 
 impl App {
-  pub async fn run(self) -> anyhow::Result<()> {
+  /// Runs until quit. Returns the exit code for mprocs.
+  pub async fn run(self) -> anyhow::Result<i32> {
     let (exit_trigger, exit_listener) = triggered::trigger();
 
     let server_thread = if let Some(ref server_addr) = self.config.server {
@@ -142,7 +184,7 @@ impl App {
     result
   }
 
-  async fn main_loop(mut self) -> anyhow::Result<()> {
+  async fn main_loop(mut self) -> anyhow::Result<i32> {
     self.start_procs(Rect::new(
       0,
       0,
@@ -150,23 +192,57 @@ impl App {
       self.screen_size.height,
     ))?;
 
+    let mut render_interval = tokio::time::interval(Duration::from_secs_f64(
+      1.0 / self.config.max_fps as f64,
+    ));
+    render_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let mut tick_interval = tokio::time::interval(TICK_INTERVAL);
+    tick_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
     let mut render_needed = true;
     loop {
       if render_needed {
-        let layout = self.get_layout();
-
-        if let Some((first, rest)) = self.clients.split_first_mut() {
-          first.render(
-            &mut self.state,
-            &layout,
-            &self.config,
-            &self.keymap,
-            &mut self.modal,
-            rest,
-          )?;
+        self.render_dirty = false;
+        self.state.update_visible_procs();
+
+        if self.config.sync_focus {
+          let layout = self.get_layout();
+          if let Some((first, rest)) = self.clients.split_first_mut() {
+            first.render(
+              &mut self.state,
+              &layout,
+              &self.config,
+              &self.keymap,
+              &mut self.modal,
+              rest,
+            )?;
+          }
+        } else {
+          self.render_clients()?;
         }
       }
 
+      // Output from procs is drawn at most `max_fps` times per second.
+      let render_dirty = self.render_dirty;
+      let render_tick = async {
+        if render_dirty {
+          render_interval.tick().await;
+        } else {
+          futures::future::pending::<()>().await;
+        }
+      };
+
+      // Without time-dependent UI the loop only wakes up on events.
+      let tick_enabled = self.tick_enabled();
+      let tick = async {
+        if tick_enabled {
+          tick_interval.tick().await;
+        } else {
+          futures::future::pending::<()>().await;
+        }
+      };
+
       let mut loop_action = LoopAction::default();
       let () = select! {
         event = self.kernel_receiver.recv().fuse() => {
@@ -184,6 +260,12 @@ impl App {
             self.handle_event(&mut loop_action, &event)
           }
         }
+        () = render_tick.fuse() => {
+          loop_action.render();
+        }
+        () = tick.fuse() => {
+          self.handle_tick(&mut loop_action);
+        }
       };
 
       if self.state.quitting && self.state.all_procs_down() {
@@ -201,30 +283,170 @@ impl App {
       };
     }
 
+    let exit_code = self.exit_code();
     for client in self.clients.into_iter() {
       let mut sender = client.sender.clone();
       drop(client);
       sender.send(SrvToClt::Quit).log_ignore();
     }
 
-    Ok(())
+    Ok(exit_code)
   }
 
-  fn start_procs(&mut self, size: Rect) -> anyhow::Result<()> {
-    let mut procs = self
-      .config
+  /// Exit code of mprocs. With `exit_with_child_code` it's the highest exit
+  /// code of processes that failed on their own, 0 otherwise.
+  fn exit_code(&self) -> i32 {
+    if !self.config.exit_with_child_code {
+      return 0;
+    }
+    let code = self
+      .state
       .procs
       .iter()
-      .map(|proc_cfg| {
-        create_proc(proc_cfg.name.clone(), proc_cfg, self.proc_tx.clone(), size)
-      })
-      .collect::<Vec<_>>();
+      .filter_map(|p| p.failure_code())
+      .max();
+    code.map_or(0, |code| code.min(255) as i32)
+  }
+
+  /// Whether anything on screen changes over time without any events.
+  fn tick_enabled(&self) -> bool {
+    self.config.show_foreground_cmd
+      || self.config.show_resource_usage
+      || self.config.dim_idle_procs.is_some()
+      || !self.config.clock_format.is_empty()
+      || self.shows_uptime()
+      || self.state.flash.is_some()
+  }
+
+  fn shows_uptime(&self) -> bool {
+    self
+      .config
+      .proc_list_format
+      .as_ref()
+      .map_or(false, |format| format.has_uptime())
+  }
+
+  /// Refreshes time-dependent UI. Renders only if something changed.
+  fn handle_tick(&mut self, loop_action: &mut LoopAction) {
+    let mut changed =
+      !self.config.clock_format.is_empty() || self.shows_uptime();
+    changed |= self.state.clear_expired_flash();
+    if self.config.show_foreground_cmd {
+      changed |= self.state.update_foreground_cmds();
+    }
+    if self.config.show_resource_usage {
+      changed |= self.state.update_resource_usage();
+    }
+    if let Some(after) = self.config.dim_idle_procs {
+      changed |= self.state.update_idle_procs(after);
+    }
+    if changed {
+      loop_action.render();
+    }
+  }
 
+  fn start_procs(&mut self, size: Rect) -> anyhow::Result<()> {
+    let stagger = Duration::from_millis(self.config.start_stagger_ms);
+    let mut autostart_index = 0;
+    let mut procs = Vec::with_capacity(self.config.procs.len());
+    for proc_cfg in &self.config.procs {
+      if proc_cfg.autostart && !proc_cfg.deps.is_empty() {
+        // Started by `start_ready_procs` once the deps are ready.
+        let cfg = ProcConfig {
+          autostart: false,
+          ..proc_cfg.clone()
+        };
+        let mut proc = create_proc(
+          cfg.name.clone(),
+          &cfg,
+          self.config.read_options,
+          self.proc_tx.clone(),
+          size,
+        );
+        proc.schedule_start();
+        proc.wait_for(cfg.deps.clone());
+        procs.push(proc);
+        continue;
+      }
+      let delay = if proc_cfg.autostart {
+        autostart_index += 1;
+        stagger * (autostart_index - 1) + proc_cfg.start_delay
+      } else {
+        Duration::ZERO
+      };
+      if delay.is_zero() {
+        let proc = create_proc(
+          proc_cfg.name.clone(),
+          proc_cfg,
+          self.config.read_options,
+          self.proc_tx.clone(),
+          size,
+        );
+        procs.push(proc);
+      } else {
+        // Started by a `ProcEvent::ScheduledStart` later.
+        let cfg = ProcConfig {
+          autostart: false,
+          ..proc_cfg.clone()
+        };
+        let mut proc = create_proc(
+          cfg.name.clone(),
+          &cfg,
+          self.config.read_options,
+          self.proc_tx.clone(),
+          size,
+        );
+        proc.schedule_start();
+        let (id, tx) = (proc.id(), self.proc_tx.clone());
+        tokio::spawn(async move {
+          tokio::time::sleep(delay).await;
+          let _r = tx.send((id, ProcEvent::ScheduledStart));
+        });
+        procs.push(proc);
+      }
+    }
+
+    for proc in &procs {
+      proc.set_line_output(self.state.interleave);
+    }
     self.state.procs.append(&mut procs);
 
+    for warning in &self.config.warnings {
+      log::warn!("{}", warning);
+      self.state.flash(warning.as_str());
+    }
+    // Procs waiting for them would never start.
+    if let Some(warning) = unknown_deps_warning(&self.config.procs) {
+      log::warn!("{}", warning);
+      self.state.flash(warning);
+    }
+
     Ok(())
   }
 
+  /// Starts procs waiting for deps that are ready now, after their
+  /// `start_delay`.
+  fn start_ready_procs(&mut self, loop_action: &mut LoopAction) {
+    let ids = procs_to_start(&mut self.state.procs);
+    for id in ids {
+      let proc = match self.state.get_proc_mut(id) {
+        Some(proc) => proc,
+        None => continue,
+      };
+      let delay = proc.cfg().start_delay;
+      if delay.is_zero() {
+        proc.handle_event(ProcEvent::ScheduledStart, false);
+      } else {
+        let tx = self.proc_tx.clone();
+        tokio::spawn(async move {
+          tokio::time::sleep(delay).await;
+          let _r = tx.send((id, ProcEvent::ScheduledStart));
+        });
+      }
+      loop_action.render();
+    }
+  }
+
   fn handle_kernel_message(
     &mut self,
     loop_action: &mut LoopAction,
@@ -234,13 +456,24 @@ impl App {
       KernelMessage::ClientMessage { client_id, msg } => {
         self.handle_client_msg(loop_action, client_id, msg)?;
       }
-      KernelMessage::ClientConnected { handle } => {
+      KernelMessage::ClientConnected { mut handle } => {
+        // New clients start with the focus of the last active client.
+        if !self.config.sync_focus {
+          self.state.store_focus(handle.id);
+        }
+        if !self.state.mouse_capture {
+          handle
+            .sender
+            .send(SrvToClt::MouseCapture(false))
+            .log_ignore();
+        }
         self.clients.push(handle);
         self.update_screen_size();
         loop_action.render();
       }
       KernelMessage::ClientDisconnected { client_id } => {
         self.clients.retain(|c| c.id != client_id);
+        self.state.client_focus.remove(&client_id);
         self.update_screen_size();
         loop_action.render();
       }
@@ -270,6 +503,25 @@ impl App {
     }
   }
 
+  /// Changes the width of the process list, keeping both panes usable.
+  fn set_proc_list_width(&mut self, width: usize) {
+    let max = (self.screen_size.width as usize)
+      .saturating_sub(TERM_MIN_WIDTH)
+      .max(SIDEBAR_MIN_WIDTH);
+    let width = width.clamp(SIDEBAR_MIN_WIDTH, max);
+    let old = self.config.proc_list_width;
+    self.config.proc_list_width.set_fixed(width);
+    if self.config.proc_list_width != old {
+      self.sync_proc_handle_size();
+    }
+  }
+
+  /// Current width of the process list in columns.
+  fn proc_list_width(&self) -> usize {
+    let screen_width = self.screen_size.width;
+    self.config.proc_list_width.resolve(screen_width) as usize
+  }
+
   fn handle_client_msg(
     &mut self,
     loop_action: &mut LoopAction,
@@ -277,13 +529,27 @@ impl App {
     msg: CltToSrv,
   ) -> anyhow::Result<()> {
     self.state.current_client_id = Some(client_id);
+    if !self.config.sync_focus {
+      self.state.load_focus(client_id);
+    }
     let ret = match msg {
       CltToSrv::Init { .. } => bail!("Init message is unexpected."),
       CltToSrv::Key(event) => {
-        self.handle_input(loop_action, client_id, event);
+        let readonly = self
+          .clients
+          .iter()
+          .find(|c| c.id == client_id)
+          .map_or(false, |c| c.readonly);
+        // View-only clients may only report their own size.
+        if !readonly || matches!(event, Event::Resize(..)) {
+          self.handle_input(loop_action, client_id, event);
+        }
         Ok(())
       }
     };
+    if !self.config.sync_focus {
+      self.state.store_focus(client_id);
+    }
     self.state.current_client_id = None;
     ret
   }
@@ -317,8 +583,23 @@ impl App {
         state: _,
       }) => {
         let key = Key::new(code, modifiers);
+        if self.state.literal_next {
+          self.state.literal_next = false;
+          self.handle_event(loop_action, &AppEvent::SendKey { key });
+          loop_action.render();
+          return;
+        }
+        let signal = match self.state.scope {
+          Scope::Procs => None,
+          Scope::Term | Scope::TermZoom => self
+            .state
+            .get_current_proc()
+            .and_then(|proc| proc.cfg().signal_keys.get(&key).copied()),
+        };
         let group = self.state.get_keymap_group();
-        if let Some(bound) = self.keymap.resolve(group, &key) {
+        if let Some(signal) = signal {
+          self.handle_event(loop_action, &AppEvent::SendSignal { signal })
+        } else if let Some(bound) = self.keymap.resolve(group, &key) {
           let bound = bound.clone();
           self.handle_event(loop_action, &bound)
         } else {
@@ -335,14 +616,68 @@ impl App {
         ..
       }) => (),
       Event::Mouse(mev) => {
-        if mev.kind == MouseEventKind::Moved {
+        if mev.kind == MouseEventKind::Moved || !self.state.mouse_capture {
           return;
         }
 
         let mouse_event = MouseEvent::from_crossterm(mev);
 
         let layout = self.get_layout();
-        if term_check_hit(layout.term_area(), mev.column, mev.row) {
+        let scrollbar = match mev.kind {
+          MouseEventKind::Down(MouseButton::Left)
+          | MouseEventKind::Drag(MouseButton::Left) => {
+            self.state.get_current_proc().and_then(|proc| {
+              let mode = self.config.scrollbar;
+              scrollbar_hit(layout.term, mev.column, mev.row, proc, mode)
+            })
+          }
+          _ => None,
+        };
+        if self.state.sidebar_drag {
+          match mev.kind {
+            MouseEventKind::Drag(MouseButton::Left) => {
+              let width = (mev.column + 1).saturating_sub(layout.procs.x);
+              self.set_proc_list_width(width as usize);
+            }
+            MouseEventKind::Up(_) => self.state.sidebar_drag = false,
+            _ => (),
+          }
+        } else if mev.kind == MouseEventKind::Down(MouseButton::Left)
+          && procs_border_hit(layout.procs, mev.column, mev.row)
+        {
+          self.state.sidebar_drag = true;
+        } else if let Some(drag) = self.state.drag {
+          match mev.kind {
+            MouseEventKind::Drag(MouseButton::Left) => {
+              let to = procs_get_clicked_index(
+                layout.procs,
+                mev.column,
+                mev.row,
+                &self.state,
+              );
+              if let Some(to) = to {
+                self.state.drag = Some(ProcDrag { to, ..drag });
+              }
+            }
+            MouseEventKind::Up(_) => {
+              self.state.drag = None;
+              self.state.move_proc(drag.from, drag.to);
+            }
+            _ => (),
+          }
+        } else if let Some((current, target)) = scrollbar {
+          if let Some(proc) = self.state.get_current_proc_mut() {
+            if target > current {
+              proc.send(ProcCmd::ScrollUpLines {
+                n: target - current,
+              });
+            } else {
+              proc.send(ProcCmd::ScrollDownLines {
+                n: current - target,
+              });
+            }
+          }
+        } else if term_check_hit(layout.term_area(), mev.column, mev.row) {
           match (self.state.scope, mev.kind) {
             (Scope::Procs, MouseEventKind::Down(_)) => {
               self.state.scope = Scope::Term
@@ -371,6 +706,10 @@ impl App {
                   &self.state,
                 ) {
                   self.state.select_proc(index);
+                  self.state.drag = Some(ProcDrag {
+                    from: index,
+                    to: index,
+                  });
                 }
               }
               MouseButton::Right | MouseButton::Middle => (),
@@ -414,9 +753,14 @@ impl App {
       Event::FocusLost => {
         log::warn!("Ignore input event: {:?}", event);
       }
-      Event::Paste(_) => {
-        log::warn!("Ignore input event: {:?}", event);
-      }
+      Event::Paste(text) => match self.state.scope {
+        Scope::Procs => (),
+        Scope::Term | Scope::TermZoom => {
+          if let Some(proc) = self.state.get_current_proc_mut() {
+            proc.send(ProcCmd::Paste(text));
+          }
+        }
+      },
     }
   }
 
@@ -477,7 +821,9 @@ impl App {
       }
 
       AppEvent::ShowCommandsMenu => {
-        self.modal = Some(CommandsMenuModal::new(self.ev_tx.clone()).boxed());
+        self.modal = Some(
+          CommandsMenuModal::new(&self.keymap, self.ev_tx.clone()).boxed(),
+        );
         loop_action.render();
       }
       AppEvent::NextProc => {
@@ -497,6 +843,32 @@ impl App {
         self.state.select_proc(next);
         loop_action.render();
       }
+      AppEvent::NextRunningProc => {
+        if let Some(next) = self.state.next_running_proc(true) {
+          self.state.select_proc(next);
+          loop_action.render();
+        }
+      }
+      AppEvent::PrevRunningProc => {
+        if let Some(next) = self.state.next_running_proc(false) {
+          self.state.select_proc(next);
+          loop_action.render();
+        }
+      }
+      AppEvent::SortProcs { by, desc } => {
+        self.state.sort_procs(*by, *desc);
+        loop_action.render();
+      }
+      AppEvent::CycleProcSort => {
+        let by = self.state.sort.map_or(ProcSort::Name, |(by, _)| by.next());
+        self.state.sort_procs(by, false);
+        loop_action.render();
+      }
+      AppEvent::ReverseProcSort => {
+        let (by, desc) = self.state.sort.unwrap_or((ProcSort::Name, false));
+        self.state.sort_procs(by, !desc);
+        loop_action.render();
+      }
       AppEvent::SelectProc { index } => {
         self.state.select_proc(*index);
         loop_action.render();
@@ -504,20 +876,60 @@ impl App {
 
       AppEvent::StartProc => {
         if let Some(proc) = self.state.get_current_proc_mut() {
+          if !proc.is_up() && !proc.cfg().params.is_empty() {
+            let params = proc.cfg().params.join(" ");
+            self.modal =
+              Some(ProcArgsModal::new(params, self.ev_tx.clone()).boxed());
+            loop_action.render();
+          } else {
+            proc.send(ProcCmd::Start);
+          }
+        }
+      }
+      AppEvent::StartProcWithArgs { args } => {
+        if let Some(proc) = self.state.get_current_proc_mut() {
+          proc.set_args(&split_args(args));
           proc.send(ProcCmd::Start);
+          loop_action.render();
         }
       }
+      AppEvent::RetryFailedProcs => {
+        for proc in &mut self.state.procs {
+          if proc.spawn_error().is_some() {
+            proc.send(ProcCmd::Start);
+          }
+        }
+        loop_action.render();
+      }
       AppEvent::TermProc => {
+        let event = AppEvent::TermProcNoConfirm;
+        if !self.confirm_kill("Stop", &event, false, loop_action) {
+          self.handle_event(loop_action, &event);
+        }
+      }
+      AppEvent::TermProcNoConfirm => {
         if let Some(proc) = self.state.get_current_proc_mut() {
           proc.send(ProcCmd::Stop);
         }
       }
       AppEvent::KillProc => {
+        let event = AppEvent::KillProcNoConfirm;
+        if !self.confirm_kill("Kill", &event, true, loop_action) {
+          self.handle_event(loop_action, &event);
+        }
+      }
+      AppEvent::KillProcNoConfirm => {
         if let Some(proc) = self.state.get_current_proc_mut() {
           proc.send(ProcCmd::Kill);
         }
       }
       AppEvent::RestartProc => {
+        let event = AppEvent::RestartProcNoConfirm;
+        if !self.confirm_kill("Restart", &event, false, loop_action) {
+          self.handle_event(loop_action, &event);
+        }
+      }
+      AppEvent::RestartProcNoConfirm => {
         if let Some(proc) = self.state.get_current_proc_mut() {
           if proc.is_up() {
             proc.to_restart = true;
@@ -528,6 +940,12 @@ impl App {
         }
       }
       AppEvent::ForceRestartProc => {
+        let event = AppEvent::ForceRestartProcNoConfirm;
+        if !self.confirm_kill("Force restart", &event, true, loop_action) {
+          self.handle_event(loop_action, &event);
+        }
+      }
+      AppEvent::ForceRestartProcNoConfirm => {
         if let Some(proc) = self.state.get_current_proc_mut() {
           if proc.is_up() {
             proc.to_restart = true;
@@ -550,6 +968,52 @@ impl App {
           loop_action.render();
         }
       }
+      AppEvent::ScrollToPrompt { dir } => {
+        if let Some(proc) = self.state.get_current_proc_mut() {
+          proc.send(ProcCmd::ScrollToPrompt { dir: *dir });
+          loop_action.render();
+        }
+      }
+      AppEvent::AddMarker { label } => {
+        if let Some(proc) = self.state.get_current_proc_mut() {
+          proc.send(ProcCmd::AddMarker {
+            label: label.clone(),
+          });
+          loop_action.render();
+        }
+      }
+      AppEvent::ScrollToMarker { dir } => {
+        if let Some(proc) = self.state.get_current_proc_mut() {
+          proc.send(ProcCmd::ScrollToMarker { dir: *dir });
+          loop_action.render();
+        }
+      }
+      AppEvent::ClearMarkers => {
+        if let Some(proc) = self.state.get_current_proc_mut() {
+          proc.send(ProcCmd::ClearMarkers);
+          loop_action.render();
+        }
+      }
+      AppEvent::CopyLastCommandOutput => {
+        if let Some(proc) = self.state.get_current_proc_mut() {
+          proc.send(ProcCmd::CopyLastCommandOutput);
+        }
+      }
+      AppEvent::CopyProcInfo => {
+        if let Some(proc) = self.state.get_current_proc() {
+          match proc.info() {
+            Some(info) => {
+              crate::clipboard::copy(&info);
+              self.state.flash("Copied process info");
+            }
+            None => {
+              let msg = format!("Process \"{}\" is not running.", proc.name());
+              self.state.flash(msg);
+            }
+          }
+          loop_action.render();
+        }
+      }
       AppEvent::ScrollUp => {
         if let Some(proc) = self.state.get_current_proc_mut() {
           proc.send(ProcCmd::ScrollUp);
@@ -562,6 +1026,60 @@ impl App {
           loop_action.render();
         }
       }
+      AppEvent::SetScrollback { n } => {
+        if let Some(proc) = self.state.get_current_proc_mut() {
+          proc.send(ProcCmd::SetScrollback { n: *n });
+          loop_action.render();
+        }
+      }
+      AppEvent::ResetDecodeErrors => {
+        if let Some(proc) = self.state.get_current_proc_mut() {
+          proc.send(ProcCmd::ResetDecodeErrors);
+          loop_action.render();
+        }
+      }
+      AppEvent::Paste => {
+        if let Some(proc) = self.state.get_current_proc() {
+          paste_clipboard(
+            self.proc_tx.clone(),
+            proc.id(),
+            crate::clipboard::paste,
+          );
+        }
+      }
+      AppEvent::SendLiteralNext => {
+        self.state.literal_next = true;
+        loop_action.render();
+      }
+      AppEvent::SendSignal { signal } => {
+        if let Some(proc) = self.state.get_current_proc_mut() {
+          proc.send(ProcCmd::SendSignal(*signal));
+        }
+      }
+      AppEvent::ToggleWrap => {
+        if let Some(proc) = self.state.get_current_proc_mut() {
+          proc.toggle_wrap();
+          loop_action.render();
+        }
+      }
+      AppEvent::ToggleDedupLines => {
+        if let Some(proc) = self.state.get_current_proc_mut() {
+          proc.toggle_dedup_lines();
+          loop_action.render();
+        }
+      }
+      AppEvent::ScrollLeft => {
+        if let Some(proc) = self.state.get_current_proc_mut() {
+          proc.scroll_left(H_SCROLL_STEP);
+          loop_action.render();
+        }
+      }
+      AppEvent::ScrollRight => {
+        if let Some(proc) = self.state.get_current_proc_mut() {
+          proc.scroll_right(H_SCROLL_STEP);
+          loop_action.render();
+        }
+      }
       AppEvent::ShowAddProc => {
         self.modal = Some(AddProcModal::new(self.ev_tx.clone()).boxed());
         loop_action.render();
@@ -569,22 +1087,18 @@ impl App {
       AppEvent::AddProc { cmd } => {
         let proc_handle = create_proc(
           cmd.to_string(),
-          &ProcConfig {
-            name: cmd.to_string(),
-            cmd: CmdConfig::Shell {
+          &ProcConfig::from_settings(
+            cmd.to_string(),
+            CmdConfig::Shell {
               shell: cmd.to_string(),
             },
-            cwd: None,
-            env: None,
-            autostart: true,
-            autorestart: false,
-            stop: StopSignal::default(),
-            mouse_scroll_speed: self.config.mouse_scroll_speed,
-            scrollback_len: self.config.scrollback_len,
-          },
+            &self.settings,
+          ),
+          self.config.read_options,
           self.proc_tx.clone(),
           self.get_layout().term_area(),
         );
+        proc_handle.set_line_output(self.state.interleave);
         self.state.procs.push(proc_handle);
         loop_action.render();
       }
@@ -595,6 +1109,32 @@ impl App {
         }
         loop_action.render();
       }
+      AppEvent::ToggleAutostart => {
+        if let Some(proc) = self.state.get_current_proc_mut() {
+          proc.toggle_autostart();
+          let state = if proc.autostart() {
+            "enabled"
+          } else {
+            "disabled"
+          };
+          let msg = format!("Autostart {} for \"{}\"", state, proc.name());
+          self.state.flash(msg);
+          loop_action.render();
+        }
+      }
+      AppEvent::ToggleAutorestart => {
+        if let Some(proc) = self.state.get_current_proc_mut() {
+          proc.toggle_autorestart();
+          let state = if proc.autorestart {
+            "enabled"
+          } else {
+            "disabled"
+          };
+          let msg = format!("Autorestart {} for \"{}\"", state, proc.name());
+          self.state.flash(msg);
+          loop_action.render();
+        }
+      }
       AppEvent::ShowRemoveProc => {
         let id = self
           .state
@@ -630,6 +1170,26 @@ impl App {
           loop_action.render();
         }
       }
+      AppEvent::ShowEditProcCmd => {
+        if let Some(proc) = self.state.get_current_proc() {
+          let cmd = proc.cfg().cmd.command_line();
+          self.modal =
+            Some(EditProcCmdModal::new(cmd, self.ev_tx.clone()).boxed());
+          loop_action.render();
+        }
+      }
+      AppEvent::EditProcCmd { cmd } => {
+        if let Some(proc) = self.state.get_current_proc_mut() {
+          proc.set_cmd(CmdConfig::Shell { shell: cmd.clone() });
+          if proc.is_up() {
+            proc.to_restart = true;
+            proc.send(ProcCmd::Stop);
+          } else {
+            proc.send(ProcCmd::Start);
+          }
+          loop_action.render();
+        }
+      }
 
       AppEvent::CopyModeEnter => {
         match self.state.get_current_proc_mut() {
@@ -641,6 +1201,13 @@ impl App {
           None => (),
         };
       }
+      AppEvent::ToggleAltView => {
+        if let Some(proc) = self.state.get_current_proc_mut() {
+          proc.send(ProcCmd::ToggleAltView);
+          self.state.scope = Scope::Term;
+          loop_action.render();
+        }
+      }
       AppEvent::CopyModeLeave => {
         if let Some(proc) = self.state.get_current_proc_mut() {
           proc.send(ProcCmd::CopyModeLeave);
@@ -665,6 +1232,62 @@ impl App {
         }
         loop_action.render();
       }
+      AppEvent::ShowCopyModeSearch { backward } => {
+        self.modal =
+          Some(CopySearchModal::new(*backward, self.ev_tx.clone()).boxed());
+        loop_action.render();
+      }
+      AppEvent::CopyModeSearch { query, backward } => {
+        if let Some(proc) = self.state.get_current_proc_mut() {
+          proc.send(ProcCmd::CopyModeSearch {
+            query: query.clone(),
+            backward: *backward,
+          });
+        }
+        loop_action.render();
+      }
+      AppEvent::CopyModeSearchNext => {
+        if let Some(proc) = self.state.get_current_proc_mut() {
+          proc.send(ProcCmd::CopyModeSearchAgain { reverse: false });
+        }
+        loop_action.render();
+      }
+      AppEvent::CopyModeSearchPrev => {
+        if let Some(proc) = self.state.get_current_proc_mut() {
+          proc.send(ProcCmd::CopyModeSearchAgain { reverse: true });
+        }
+        loop_action.render();
+      }
+
+      AppEvent::ToggleInterleave => {
+        self.state.interleave = !self.state.interleave;
+        for proc in &self.state.procs {
+          proc.set_line_output(self.state.interleave);
+        }
+        loop_action.render();
+      }
+      AppEvent::GrowSidebar => {
+        let width = self.proc_list_width() + SIDEBAR_STEP;
+        self.set_proc_list_width(width);
+        loop_action.render();
+      }
+      AppEvent::ShrinkSidebar => {
+        let width = self.proc_list_width().saturating_sub(SIDEBAR_STEP);
+        self.set_proc_list_width(width);
+        loop_action.render();
+      }
+      AppEvent::ToggleMouseCapture => {
+        self.state.mouse_capture = !self.state.mouse_capture;
+        self.state.drag = None;
+        self.state.sidebar_drag = false;
+        for client in &mut self.clients {
+          client
+            .sender
+            .send(SrvToClt::MouseCapture(self.state.mouse_capture))
+            .log_ignore();
+        }
+        loop_action.render();
+      }
 
       AppEvent::ToggleKeymapWindow => {
         self.state.toggle_keymap_window();
@@ -672,6 +1295,36 @@ impl App {
         loop_action.render();
       }
 
+      AppEvent::SaveSession { path } => {
+        match Session::from_state(&self.state, &self.config)
+          .save(Path::new(path))
+        {
+          Ok(()) => self.state.flash(format!("Session saved to {}", path)),
+          Err(err) => {
+            self.state.flash(format!("Failed to save session: {}", err))
+          }
+        }
+        loop_action.render();
+      }
+
+      AppEvent::RunCommand { cmd } => {
+        let vars = self
+          .state
+          .get_current_proc()
+          .map(ProcVars::from_proc)
+          .unwrap_or_default();
+        let started = vars
+          .expand(cmd, self.config.strict_placeholders)
+          .and_then(|cmd| run_command(&cmd, &vars).map(|()| cmd));
+        match started {
+          Ok(cmd) => self.state.flash(format!("Started \"{}\"", cmd)),
+          Err(err) => self
+            .state
+            .flash(format!("Failed to run \"{}\": {}", cmd, err)),
+        }
+        loop_action.render();
+      }
+
       AppEvent::SendKey { key } => {
         if let Some(proc) = self.state.get_current_proc_mut() {
           proc.send(ProcCmd::SendKey(key.clone()));
@@ -680,6 +1333,69 @@ impl App {
     }
   }
 
+  /// Shows a confirmation dialog for stopping the current proc if it is
+  /// `critical` or, when `kill` is set, if `confirm_kill` is enabled. Returns
+  /// false if no confirmation is needed.
+  fn confirm_kill(
+    &mut self,
+    action: &str,
+    event: &AppEvent,
+    kill: bool,
+    loop_action: &mut LoopAction,
+  ) -> bool {
+    let confirm_kill = kill && self.config.confirm_kill;
+    let name = match self.state.get_current_proc() {
+      Some(proc) if proc.is_up() && (confirm_kill || proc.critical()) => {
+        proc.name().to_string()
+      }
+      _ => return false,
+    };
+    let message = format!("{} \"{}\"?", action, name);
+    self.modal = Some(
+      ConfirmModal::new(message, event.clone(), self.ev_tx.clone()).boxed(),
+    );
+    loop_action.render();
+    true
+  }
+
+  fn handle_watch_match(
+    &mut self,
+    loop_action: &mut LoopAction,
+    id: usize,
+    name: &str,
+    action: WatchAction,
+    line: String,
+  ) {
+    match action {
+      WatchAction::Notify => {
+        let text = format!("{}: {}", name, line);
+        for client in &mut self.clients {
+          client
+            .sender
+            .send(SrvToClt::Notify(text.clone()))
+            .log_ignore();
+        }
+      }
+      WatchAction::Bell => {
+        for client in &mut self.clients {
+          client.sender.send(SrvToClt::Bell).log_ignore();
+        }
+      }
+      WatchAction::Focus => {
+        if let Some(index) = self.state.procs.iter().position(|p| p.id() == id)
+        {
+          self.state.select_proc(index);
+          loop_action.render();
+        }
+      }
+      WatchAction::Ready => {
+        if let Some(proc) = self.state.get_proc_mut(id) {
+          proc.mark_ready();
+        }
+      }
+    }
+  }
+
   fn handle_proc_event(
     &mut self,
     loop_action: &mut LoopAction,
@@ -689,12 +1405,95 @@ impl App {
       .state
       .get_current_proc()
       .map_or(false, |p| p.id() == event.0);
+    let stopped =
+      matches!(event.1, ProcEvent::Stopped(_) | ProcEvent::SpawnFailed(_));
+    let became_ready = matches!(
+      event.1,
+      ProcEvent::Started | ProcEvent::Watch(WatchAction::Ready, _)
+    );
     if let Some(proc) = self.state.get_proc_mut(event.0) {
-      proc.handle_event(event.1, selected);
-      loop_action.render();
+      let was_changed = proc.changed();
+      match event.1 {
+        ProcEvent::Render => {
+          proc.handle_event(event.1, selected);
+          // Output of hidden procs only matters when it changes the marker in
+          // the process list.
+          if selected || proc.changed() != was_changed {
+            self.render_dirty = true;
+          }
+        }
+        ProcEvent::Watch(action, line) => {
+          let name = proc.name().to_string();
+          self.handle_watch_match(loop_action, event.0, &name, action, line);
+        }
+        ProcEvent::Reply(_) => proc.handle_event(event.1, selected),
+        ProcEvent::Flash(msg) => {
+          self.state.flash(msg);
+          loop_action.render();
+        }
+        ProcEvent::Output(line) => {
+          self.state.push_interleaved(event.0, line);
+          if self.state.interleave {
+            self.render_dirty = true;
+          }
+        }
+        _ => {
+          proc.handle_event(event.1, selected);
+          loop_action.render();
+        }
+      }
+    }
+
+    if became_ready {
+      self.start_ready_procs(loop_action);
+    }
+
+    if stopped && !self.state.quitting && self.state.all_procs_finished() {
+      self.handle_all_finished(loop_action);
     }
   }
 
+  fn handle_all_finished(&mut self, loop_action: &mut LoopAction) {
+    match self.config.on_all_finished.clone() {
+      OnAllFinished::Keep => (),
+      OnAllFinished::Quit => self.handle_event(loop_action, &AppEvent::Quit),
+      OnAllFinished::RestartFailed => {
+        for proc in &mut self.state.procs {
+          if proc.is_failed() {
+            proc.send(ProcCmd::Start);
+          }
+        }
+      }
+      OnAllFinished::Event(event) => self.handle_event(loop_action, &event),
+    }
+  }
+
+  /// Renders every client with its own selected proc and scope.
+  fn render_clients(&mut self) -> anyhow::Result<()> {
+    let (selected, scope) = (self.state.selected, self.state.scope);
+    let size = self.screen_size;
+    for client in &mut self.clients {
+      self.state.load_focus(client.id);
+      let layout = AppLayout::new(
+        Rect::new(0, 0, size.width, size.height),
+        self.state.scope.is_zoomed(),
+        self.state.hide_keymap_window,
+        &self.config,
+      );
+      client.render(
+        &mut self.state,
+        &layout,
+        &self.config,
+        &self.keymap,
+        &mut self.modal,
+        &mut [],
+      )?;
+    }
+    self.state.selected = selected;
+    self.state.scope = scope;
+    Ok(())
+  }
+
   fn get_layout(&mut self) -> AppLayout {
     let size = self.screen_size;
     AppLayout::new(
@@ -724,7 +1523,7 @@ impl AppLayout {
     let procs_w = if zoom {
       0
     } else {
-      config.proc_list_width as u16
+      config.proc_list_width.resolve(area.width)
     };
     let zoom_banner_h = if zoom { 1 } else { 0 };
     let top_bot = Layout::default()
@@ -770,12 +1569,17 @@ impl ClientConnector {
     tokio::spawn(async move {
       let init_msg = receiver.recv().await;
       match init_msg {
-        Some(Ok(CltToSrv::Init { width, height })) => {
+        Some(Ok(CltToSrv::Init {
+          width,
+          height,
+          readonly,
+        })) => {
           let client_handle = ClientHandle::create(
             id,
             (receiver, sender),
             kernel_sender.clone(),
             Size { width, height },
+            readonly,
           );
           match client_handle {
             Ok(handle) => {
@@ -800,6 +1604,7 @@ pub struct ClientHandle {
   id: ClientId,
   sender: MsgSender<SrvToClt>,
   terminal: Term,
+  readonly: bool,
 
   cursor_style: CursorStyle,
 }
@@ -810,6 +1615,7 @@ impl ClientHandle {
     (mut read, write): (MsgReceiver<CltToSrv>, MsgSender<SrvToClt>),
     kernel_sender: KernelSender,
     size: Size,
+    readonly: bool,
   ) -> anyhow::Result<Self> {
     {
       let kernel_sender = kernel_sender.clone();
@@ -854,6 +1660,7 @@ impl ClientHandle {
       id,
       sender: write,
       terminal,
+      readonly,
 
       cursor_style: CursorStyle::Default,
     })
@@ -882,17 +1689,36 @@ impl ClientHandle {
     &mut self,
     state: &mut State,
     layout: &AppLayout,
-    _config: &Config,
+    config: &Config,
     keymap: &Keymap,
     modal: &mut Option<Box<dyn Modal>>,
     rest: &mut [ClientHandle],
   ) -> anyhow::Result<()> {
+    let mut cursor_style = self.cursor_style;
+    let theme = Theme {
+      border_style: config.border_style,
+      focus_border_color: config.focus_border_color,
+      ..Theme::default()
+    };
     self.terminal.draw(|f| {
-      let mut cursor_style = self.cursor_style;
-
-      render_procs(layout.procs, f, state);
-      render_term(layout.term, f, state, &mut cursor_style);
-      render_keymap(layout.keymap, f, state, keymap);
+      render_procs(
+        layout.procs,
+        f,
+        state,
+        &theme,
+        &config.title_format,
+        config.proc_list_format.as_ref(),
+        &config.status_glyphs,
+      );
+      render_term(layout.term, f, state, &theme, config, &mut cursor_style);
+      render_keymap(
+        layout.keymap,
+        f,
+        state,
+        keymap,
+        config.keymap_hints.as_deref(),
+        &config.clock_format,
+      );
       render_zoom_tip(layout.zoom_banner, f, keymap);
 
       if let Some(modal) = modal {
@@ -900,26 +1726,44 @@ impl ClientHandle {
         modal.render(f);
       }
 
-      for client_handle in rest {
+      for client_handle in rest.iter_mut() {
         f.render_widget(RenderOtherClient(client_handle), f.size());
       }
 
-      if self.cursor_style != cursor_style {
-        self
-          .sender
-          .send(SrvToClt::CursorShape(cursor_style.into()))
-          .log_ignore();
-        self.cursor_style = cursor_style;
+      // Drawn after other clients copied the screen.
+      if self.readonly {
+        f.render_widget(ReadonlyMarker, f.size());
       }
     })?;
 
+    // Other clients show the same screen, so they get the same cursor.
+    self.update_cursor_style(cursor_style);
+    for client_handle in rest {
+      client_handle.update_cursor_style(cursor_style);
+    }
+
     Ok(())
   }
 
+  /// Sends the cursor style only if it differs from the one this client
+  /// already has.
+  fn update_cursor_style(&mut self, cursor_style: CursorStyle) {
+    if self.cursor_style != cursor_style {
+      self
+        .sender
+        .send(SrvToClt::CursorShape(cursor_style.into()))
+        .log_ignore();
+      self.cursor_style = cursor_style;
+    }
+  }
+
   fn render_from(&mut self, buf: &tui::buffer::Buffer) -> anyhow::Result<()> {
     self.terminal.draw(|f| {
       let area = buf.area().intersection(f.size());
       f.render_widget(CopyBuffer(buf), area);
+      if self.readonly {
+        f.render_widget(ReadonlyMarker, f.size());
+      }
     })?;
     Ok(())
   }
@@ -946,10 +1790,30 @@ impl Widget for CopyBuffer<'_> {
   }
 }
 
+/// Shown in the top right corner of view-only clients.
+struct ReadonlyMarker;
+
+impl Widget for ReadonlyMarker {
+  fn render(self, area: Rect, buf: &mut tui::prelude::Buffer) {
+    let text = "(view-only)";
+    let width = text.len() as u16;
+    if area.width < width || area.height == 0 {
+      return;
+    }
+    buf.set_string(
+      area.x + area.width - width,
+      area.y,
+      text,
+      Style::default().fg(Color::Black).bg(Color::Yellow),
+    );
+  }
+}
+
 pub async fn start_kernel_process(
   config: Config,
+  settings: Settings,
   keymap: Keymap,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<i32> {
   let (kernel_sender, kernel_receiver) = tokio::sync::mpsc::unbounded_channel();
 
   let mut server_socket = bind_server_socket().await?;
@@ -975,48 +1839,62 @@ pub async fn start_kernel_process(
     })
   };
 
-  kernel_main(config, keymap, kernel_receiver).await
+  kernel_main(config, settings, keymap, kernel_receiver).await
 }
 
 pub async fn start_kernel_thread(
   config: Config,
+  settings: Settings,
   keymap: Keymap,
   socket: (MsgSender<SrvToClt>, MsgReceiver<CltToSrv>),
-) -> anyhow::Result<()> {
+) -> anyhow::Result<JoinHandle<anyhow::Result<i32>>> {
   let (kernel_sender, kernel_receiver) = tokio::sync::mpsc::unbounded_channel();
 
   let id = ClientId(1);
   ClientConnector::connect(id, socket, kernel_sender.clone());
 
-  tokio::spawn(async {
-    kernel_main(config, keymap, kernel_receiver).await;
+  let kernel = tokio::spawn(async {
+    kernel_main(config, settings, keymap, kernel_receiver).await
   });
 
-  Ok(())
+  Ok(kernel)
 }
 
 pub async fn kernel_main(
   config: Config,
+  settings: Settings,
   keymap: Keymap,
   kernel_receiver: UnboundedReceiver<KernelMessage>,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<i32> {
   let (upd_tx, upd_rx) =
     tokio::sync::mpsc::unbounded_channel::<(usize, ProcEvent)>();
   let (ev_tx, ev_rx) = tokio::sync::mpsc::unbounded_channel::<AppEvent>();
 
   let state = State {
     current_client_id: None,
+    client_focus: HashMap::new(),
 
     scope: Scope::Procs,
     procs: Vec::new(),
-    selected: 0,
-    hide_keymap_window: config.hide_keymap_window,
-
+    selected: config.selected,
+    hide_keymap_window: config.hide_keymap_window
+      || config.keymap_hints.as_ref().map_or(false, |h| h.is_empty()),
+
+    literal_next: false,
+    drag: None,
+    sidebar_drag: false,
+    mouse_capture: true,
+    scroll_to_bottom_on_focus: config.scroll_to_bottom_on_focus,
+    flash: None,
+    sort: None,
+    interleave: config.interleave,
+    interleaved: VecDeque::new(),
     quitting: false,
   };
 
   let app = App {
     config,
+    settings,
     keymap,
     state,
     modal: None,
@@ -1033,8 +1911,8 @@ pub async fn kernel_main(
       height: 50,
     },
     clients: Vec::new(),
-  };
-  app.run().await?;
 
-  Ok(())
+    render_dirty: false,
+  };
+  app.run().await
 }
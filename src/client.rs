@@ -2,6 +2,7 @@ use crossterm::{
   cursor::SetCursorStyle,
   event::{DisableMouseCapture, EnableMouseCapture, Event, EventStream},
   execute,
+  style::Print,
   terminal::{
     disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen,
     LeaveAlternateScreen,
@@ -21,6 +22,7 @@ use crate::{
 pub async fn client_main(
   sender: MsgSender<CltToSrv>,
   receiver: MsgReceiver<SrvToClt>,
+  readonly: bool,
 ) -> anyhow::Result<()> {
   enable_raw_mode()?;
 
@@ -54,12 +56,13 @@ pub async fn client_main(
   )
   .log_ignore());
 
-  client_main_loop(sender, receiver).await
+  client_main_loop(sender, receiver, readonly).await
 }
 
 async fn client_main_loop(
   mut sender: MsgSender<CltToSrv>,
   mut receiver: MsgReceiver<SrvToClt>,
+  readonly: bool,
 ) -> anyhow::Result<()> {
   let mut backend = CrosstermBackend::new(std::io::stdout());
 
@@ -67,6 +70,7 @@ async fn client_main_loop(
   sender.send(CltToSrv::Init {
     width: init_size.width,
     height: init_size.height,
+    readonly,
   })?;
 
   let mut term_events = EventStream::new();
@@ -109,6 +113,21 @@ async fn client_main_loop(
           SrvToClt::HideCursor => backend.hide_cursor()?,
           SrvToClt::Clear => backend.clear()?,
           SrvToClt::Flush => backend.flush()?,
+          SrvToClt::Bell => {
+            execute!(std::io::stdout(), Print("\x07"))?;
+          }
+          SrvToClt::Notify(text) => {
+            // Control characters would end the sequence early.
+            let text = text.replace(|c: char| c.is_control(), " ");
+            let osc = format!("\x1b]9;{}\x07", text);
+            execute!(std::io::stdout(), Print(osc))?;
+          }
+          SrvToClt::MouseCapture(true) => {
+            execute!(std::io::stdout(), EnableMouseCapture)?;
+          }
+          SrvToClt::MouseCapture(false) => {
+            execute!(std::io::stdout(), DisableMouseCapture)?;
+          }
           SrvToClt::Quit => break,
         },
         _ => break,
@@ -59,6 +59,83 @@ fn detect_copy_provider() -> Provider {
   Provider::OSC52
 }
 
+#[cfg(windows)]
+fn detect_paste_provider() -> Provider {
+  Provider::Win
+}
+
+#[cfg(target_os = "macos")]
+fn detect_paste_provider() -> Provider {
+  if let Some(provider) = check_prog("pbpaste", &[]) {
+    return provider;
+  }
+  Provider::NoOp
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn detect_paste_provider() -> Provider {
+  // Wayland
+  if std::env::var("WAYLAND_DISPLAY").is_ok() {
+    if let Some(provider) = check_prog("wl-paste", &["--no-newline"]) {
+      return provider;
+    }
+  }
+  // X11
+  if std::env::var("DISPLAY").is_ok() {
+    if let Some(provider) =
+      check_prog("xclip", &["-o", "-selection", "clipboard"])
+    {
+      return provider;
+    }
+    if let Some(provider) = check_prog("xsel", &["-o", "-b"]) {
+      return provider;
+    }
+  }
+  // Termux
+  if let Some(provider) = check_prog("termux-clipboard-get", &[]) {
+    return provider;
+  }
+  // Tmux
+  if std::env::var("TMUX").is_ok() {
+    if let Some(provider) = check_prog("tmux", &["save-buffer", "-"]) {
+      return provider;
+    }
+  }
+
+  // Reading clipboard with OSC 52 is rarely allowed by terminals.
+  Provider::NoOp
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn detect_primary_provider() -> Provider {
+  Provider::NoOp
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn detect_primary_provider() -> Provider {
+  // Wayland
+  if std::env::var("WAYLAND_DISPLAY").is_ok() {
+    if let Some(provider) =
+      check_prog("wl-paste", &["--primary", "--no-newline"])
+    {
+      return provider;
+    }
+  }
+  // X11
+  if std::env::var("DISPLAY").is_ok() {
+    if let Some(provider) =
+      check_prog("xclip", &["-o", "-selection", "primary"])
+    {
+      return provider;
+    }
+    if let Some(provider) = check_prog("xsel", &["-o", "-p"]) {
+      return provider;
+    }
+  }
+
+  Provider::NoOp
+}
+
 #[allow(dead_code)]
 fn check_prog(cmd: &'static str, args: &[&'static str]) -> Option<Provider> {
   if which(cmd).is_ok() {
@@ -105,8 +182,34 @@ fn copy_impl(s: &str, provider: &Provider) -> Result<()> {
   Ok(())
 }
 
+fn paste_impl(provider: &Provider) -> Result<String> {
+  match provider {
+    Provider::Exec(prog, args) => {
+      let output = std::process::Command::new(prog)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()?;
+      if !output.status.success() {
+        anyhow::bail!("\"{}\" exited with {}", prog, output.status);
+      }
+      Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    #[cfg(windows)]
+    Provider::Win => clipboard_win::get_clipboard_string()
+      .map_err(|e| anyhow::Error::msg(e.to_string())),
+
+    Provider::OSC52 | Provider::NoOp => {
+      anyhow::bail!("No clipboard tool found")
+    }
+  }
+}
+
 lazy_static::lazy_static! {
   static ref PROVIDER: Provider = detect_copy_provider();
+  static ref PASTE_PROVIDER: Provider = detect_paste_provider();
+  static ref PRIMARY_PROVIDER: Provider = detect_primary_provider();
 }
 
 pub fn copy(s: &str) {
@@ -115,3 +218,27 @@ pub fn copy(s: &str) {
     Err(err) => log::warn!("Copying error: {}", err.to_string()),
   }
 }
+
+pub fn paste() -> Option<String> {
+  match paste_impl(&PASTE_PROVIDER) {
+    Ok(s) => Some(s),
+    Err(err) => {
+      log::warn!("Pasting error: {}", err.to_string());
+      None
+    }
+  }
+}
+
+/// Reads the primary selection (X11 and Wayland only).
+pub fn paste_primary() -> Option<String> {
+  if let Provider::NoOp = *PRIMARY_PROVIDER {
+    return None;
+  }
+  match paste_impl(&PRIMARY_PROVIDER) {
+    Ok(s) => Some(s),
+    Err(err) => {
+      log::warn!("Pasting primary selection error: {}", err.to_string());
+      None
+    }
+  }
+}
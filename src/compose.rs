@@ -0,0 +1,105 @@
+use std::{fs::File, io::BufReader, path::Path};
+
+use anyhow::{bail, Result};
+use indexmap::IndexMap;
+use serde::Deserialize;
+use serde_yaml::Value;
+
+use crate::{
+  config::{CmdConfig, ProcConfig},
+  proc::StopSignal,
+  settings::Settings,
+};
+
+/// Compose file names in the order docker compose looks for them.
+const COMPOSE_FILES: [&str; 4] = [
+  "compose.yaml",
+  "compose.yml",
+  "docker-compose.yaml",
+  "docker-compose.yml",
+];
+
+#[derive(Deserialize)]
+struct ComposeFile {
+  #[serde(default)]
+  services: IndexMap<String, Value>,
+}
+
+pub fn load_compose_procs(settings: &Settings) -> Result<Vec<ProcConfig>> {
+  let path = match COMPOSE_FILES.iter().find(|name| Path::new(name).is_file()) {
+    Some(path) => path,
+    None => bail!("Compose file not found."),
+  };
+  let file = File::open(path)?;
+  let compose: ComposeFile = serde_yaml::from_reader(BufReader::new(file))?;
+  let compose_cmd = compose_cmd();
+
+  let procs = compose.services.into_keys().map(|name| {
+    let cmd = CmdConfig::Shell {
+      shell: service_script(compose_cmd, &name),
+    };
+    ProcConfig {
+      autostart: false,
+      stop: StopSignal::SIGTERM,
+      ..ProcConfig::from_settings(name, cmd, settings)
+    }
+  });
+  Ok(procs.collect())
+}
+
+/// `docker compose` if the compose plugin is installed, otherwise the legacy
+/// `docker-compose`.
+fn compose_cmd() -> &'static str {
+  let plugin = std::process::Command::new("docker")
+    .args(["compose", "version"])
+    .stdout(std::process::Stdio::null())
+    .stderr(std::process::Stdio::null())
+    .status()
+    .map_or(false, |status| status.success());
+  if !plugin && which::which("docker-compose").is_ok() {
+    "docker-compose"
+  } else {
+    "docker compose"
+  }
+}
+
+/// Starts the service and follows its logs. Stopping the proc (SIGTERM)
+/// stops the service. Service names can't contain shell special characters.
+fn service_script(compose_cmd: &str, service: &str) -> String {
+  format!(
+    "trap '{cmd} stop {svc}; exit' INT TERM; \
+     {cmd} up -d {svc} && {cmd} logs -f {svc} & wait",
+    cmd = compose_cmd,
+    svc = service,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn services() {
+    let yaml = "
+services:
+  web:
+    image: nginx
+  db:
+    image: postgres
+volumes:
+  data:
+";
+    let compose: ComposeFile = serde_yaml::from_str(yaml).unwrap();
+    let names = compose.services.into_keys().collect::<Vec<_>>();
+    assert_eq!(names, vec!["web", "db"]);
+  }
+
+  #[test]
+  fn script() {
+    assert_eq!(
+      service_script("docker compose", "web"),
+      "trap 'docker compose stop web; exit' INT TERM; \
+       docker compose up -d web && docker compose logs -f web & wait"
+    );
+  }
+}
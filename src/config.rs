@@ -1,19 +1,108 @@
-use std::{ffi::OsString, path::PathBuf, str::FromStr};
+use std::{
+  ffi::OsString,
+  path::{Path, PathBuf},
+  str::FromStr,
+  time::Duration,
+};
 
 use anyhow::{bail, Result};
 use indexmap::IndexMap;
 use portable_pty::CommandBuilder;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_yaml::Value;
+use tui::style::Color;
 
 use crate::{
-  proc::StopSignal,
-  settings::Settings,
-  yaml_val::{value_to_string, Val},
+  deps::{check_deps, unknown_deps_warning},
+  encode_term::{KeyEncoding, NewlineMode},
+  event::AppEvent,
+  highlight::{parse_color, Highlight},
+  key::Key,
+  proc::{reader::ReadOptions, signal::Signal, RestartOn, StopSignal},
+  proc_format::{ProcFormat, StatusGlyphs},
+  settings::{OnAllFinished, ProcListWidth, Settings},
+  theme::BorderStyle,
+  title_format::TitleFormat,
+  ui_term::ScrollbarMode,
+  watch::{patterns_for, WatchAction, WatchPattern},
+  yaml_val::{find_position, value_to_string, Val, ValError},
 };
 
 pub struct ConfigContext {
   pub path: PathBuf,
+  /// Files included by the config, in the order they were merged. The config
+  /// file itself is merged last.
+  pub includes: Vec<PathBuf>,
+}
+
+impl ConfigContext {
+  /// Directory where the config file is located.
+  pub fn dir(&self) -> Result<PathBuf> {
+    let path = dunce::canonicalize(&self.path)?;
+    Ok(path.parent().map(Path::to_path_buf).unwrap_or_default())
+  }
+
+  /// Place in the config files of the value that caused the error. Values
+  /// merged from several files come from the last one that has them. Only
+  /// yaml and json configs are searched.
+  pub fn error_pos(&self, err: &anyhow::Error) -> Option<ErrorPos> {
+    let err = err.chain().find_map(|err| err.downcast_ref::<ValError>())?;
+    match err.file() {
+      Some(file) => find_error_pos(file, err.path()),
+      None => std::iter::once(&self.path)
+        .chain(self.includes.iter().rev())
+        .find_map(|file| find_error_pos(file, err.path())),
+    }
+  }
+
+  /// Adds the position in the config file and the line with the value to the
+  /// error message.
+  pub fn locate_error(&self, err: anyhow::Error) -> anyhow::Error {
+    let pos = match self.error_pos(&err) {
+      Some(pos) => pos,
+      None => return err,
+    };
+    let num = pos.line.to_string();
+    let pad = " ".repeat(num.len());
+    anyhow::format_err!(
+      "{}\n{}--> {}:{}:{}\n{} |\n{} | {}\n{} | {}^",
+      err,
+      pad,
+      pos.path.to_string_lossy(),
+      pos.line,
+      pos.col,
+      pad,
+      num,
+      pos.text,
+      pad,
+      " ".repeat(pos.col - 1),
+    )
+  }
+}
+
+fn find_error_pos(file: &Path, path: &[String]) -> Option<ErrorPos> {
+  let ext = file.extension()?.to_str()?;
+  if !matches!(ext, "yaml" | "yml" | "json") {
+    return None;
+  }
+  let source = std::fs::read_to_string(file).ok()?;
+  let (line, col) = find_position(&source, path)?;
+  let text = source.lines().nth(line - 1)?.to_string();
+  Some(ErrorPos {
+    path: file.to_path_buf(),
+    line,
+    col,
+    text,
+  })
+}
+
+/// File, line and column (1-based) in it and the text of the line.
+pub struct ErrorPos {
+  pub path: PathBuf,
+  pub line: usize,
+  pub col: usize,
+  pub text: String,
 }
 
 pub struct Config {
@@ -22,7 +111,46 @@ pub struct Config {
   pub hide_keymap_window: bool,
   pub mouse_scroll_speed: usize,
   pub scrollback_len: usize,
-  pub proc_list_width: usize,
+  pub scrollback_bytes: usize,
+  pub proc_list_width: ProcListWidth,
+  pub max_fps: usize,
+  pub lazy_parse: bool,
+  pub line_numbers: bool,
+  pub keep_history_on_restart: bool,
+  pub highlights: Vec<Highlight>,
+  pub title_format: TitleFormat,
+  pub show_foreground_cmd: bool,
+  pub show_resource_usage: bool,
+  pub dim_idle_procs: Option<Duration>,
+  pub clock_format: String,
+  pub confirm_kill: bool,
+  pub show_term_title: bool,
+  pub scrollbar: ScrollbarMode,
+  pub border_style: BorderStyle,
+  pub focus_border_color: Color,
+  pub proc_list_format: Option<ProcFormat>,
+  pub status_glyphs: StatusGlyphs,
+  pub watch_patterns: Vec<WatchPattern>,
+  pub cell_pixel_size: Option<(u16, u16)>,
+  pub tab_width: u16,
+  pub read_options: ReadOptions,
+  pub middle_click_paste: bool,
+  pub scroll_acceleration: bool,
+  pub scroll_acceleration_max: usize,
+  pub copy_scrolloff: usize,
+  pub keymap_hints: Option<Vec<AppEvent>>,
+  pub sync_focus: bool,
+  pub scroll_to_bottom_on_focus: bool,
+  pub start_stagger_ms: u64,
+  pub on_all_finished: OnAllFinished,
+  /// Show output of all procs merged in one pane (`--interleave`).
+  pub interleave: bool,
+  pub exit_with_child_code: bool,
+  pub strict_placeholders: bool,
+  pub default_stop: StopSignal,
+  pub selected: usize,
+  /// Problems with the command line arguments to show at startup.
+  pub warnings: Vec<String>,
 }
 
 impl Config {
@@ -34,27 +162,11 @@ impl Config {
     let config = Val::new(value)?;
     let config = config.as_object()?;
 
-    let procs = if let Some(procs) = config.get(&Value::from("procs")) {
-      let procs = procs
-        .as_object()?
-        .into_iter()
-        .map(|(name, proc)| {
-          Ok(ProcConfig::from_val(
-            value_to_string(&name)?,
-            settings.mouse_scroll_speed,
-            settings.scrollback_len,
-            proc,
-            ctx,
-          )?)
-        })
-        .collect::<Result<Vec<_>>>()?
-        .into_iter()
-        .filter_map(|x| x)
-        .collect::<Vec<_>>();
-      procs
-    } else {
-      Vec::new()
-    };
+    let (procs, errors) = parse_procs(&config, ctx, settings);
+    if let Some(err) = errors.into_iter().next() {
+      return Err(err);
+    }
+    check_deps(&procs)?;
 
     let server = if let Some(addr) = config.get(&Value::from("server")) {
       Some(ServerConfig::from_str(addr.as_str()?)?)
@@ -68,7 +180,44 @@ impl Config {
       hide_keymap_window: settings.hide_keymap_window,
       mouse_scroll_speed: settings.mouse_scroll_speed,
       scrollback_len: settings.scrollback_len,
+      scrollback_bytes: settings.scrollback_bytes,
       proc_list_width: settings.proc_list_width,
+      max_fps: settings.max_fps,
+      lazy_parse: settings.lazy_parse,
+      line_numbers: settings.line_numbers,
+      keep_history_on_restart: settings.keep_history_on_restart,
+      highlights: settings.highlights.clone(),
+      title_format: settings.title_format.clone(),
+      show_foreground_cmd: settings.show_foreground_cmd,
+      show_resource_usage: settings.show_resource_usage,
+      dim_idle_procs: settings.dim_idle_procs,
+      clock_format: settings.clock_format.clone(),
+      confirm_kill: settings.confirm_kill,
+      show_term_title: settings.show_term_title,
+      scrollbar: settings.scrollbar,
+      border_style: settings.border_style,
+      focus_border_color: settings.focus_border_color,
+      proc_list_format: settings.proc_list_format.clone(),
+      status_glyphs: settings.status_glyphs.clone(),
+      watch_patterns: settings.watch_patterns.clone(),
+      cell_pixel_size: settings.cell_pixel_size,
+      tab_width: settings.tab_width,
+      read_options: settings.read_options(),
+      middle_click_paste: settings.middle_click_paste,
+      scroll_acceleration: settings.scroll_acceleration,
+      scroll_acceleration_max: settings.scroll_acceleration_max,
+      copy_scrolloff: settings.copy_scrolloff,
+      keymap_hints: settings.keymap_hints.clone(),
+      sync_focus: settings.sync_focus,
+      scroll_to_bottom_on_focus: settings.scroll_to_bottom_on_focus,
+      start_stagger_ms: settings.start_stagger_ms,
+      on_all_finished: settings.on_all_finished.clone(),
+      interleave: false,
+      exit_with_child_code: settings.exit_with_child_code,
+      strict_placeholders: settings.strict_placeholders,
+      default_stop: settings.default_stop.clone(),
+      selected: 0,
+      warnings: Vec::new(),
     };
 
     Ok(config)
@@ -81,11 +230,104 @@ impl Config {
       hide_keymap_window: settings.hide_keymap_window,
       mouse_scroll_speed: settings.mouse_scroll_speed,
       scrollback_len: settings.scrollback_len,
+      scrollback_bytes: settings.scrollback_bytes,
       proc_list_width: settings.proc_list_width,
+      max_fps: settings.max_fps,
+      lazy_parse: settings.lazy_parse,
+      line_numbers: settings.line_numbers,
+      keep_history_on_restart: settings.keep_history_on_restart,
+      highlights: settings.highlights.clone(),
+      title_format: settings.title_format.clone(),
+      show_foreground_cmd: settings.show_foreground_cmd,
+      show_resource_usage: settings.show_resource_usage,
+      dim_idle_procs: settings.dim_idle_procs,
+      clock_format: settings.clock_format.clone(),
+      confirm_kill: settings.confirm_kill,
+      show_term_title: settings.show_term_title,
+      scrollbar: settings.scrollbar,
+      border_style: settings.border_style,
+      focus_border_color: settings.focus_border_color,
+      proc_list_format: settings.proc_list_format.clone(),
+      status_glyphs: settings.status_glyphs.clone(),
+      watch_patterns: settings.watch_patterns.clone(),
+      cell_pixel_size: settings.cell_pixel_size,
+      tab_width: settings.tab_width,
+      read_options: settings.read_options(),
+      middle_click_paste: settings.middle_click_paste,
+      scroll_acceleration: settings.scroll_acceleration,
+      scroll_acceleration_max: settings.scroll_acceleration_max,
+      copy_scrolloff: settings.copy_scrolloff,
+      keymap_hints: settings.keymap_hints.clone(),
+      sync_focus: settings.sync_focus,
+      scroll_to_bottom_on_focus: settings.scroll_to_bottom_on_focus,
+      start_stagger_ms: settings.start_stagger_ms,
+      on_all_finished: settings.on_all_finished.clone(),
+      interleave: false,
+      exit_with_child_code: settings.exit_with_child_code,
+      strict_placeholders: settings.strict_placeholders,
+      default_stop: settings.default_stop.clone(),
+      selected: 0,
+      warnings: Vec::new(),
     }
   }
 }
 
+/// Parses procs of the config. Errors are collected for every proc instead of
+/// stopping at the first one.
+fn parse_procs(
+  config: &IndexMap<Value, Val>,
+  ctx: &ConfigContext,
+  settings: &Settings,
+) -> (Vec<ProcConfig>, Vec<anyhow::Error>) {
+  let mut procs = Vec::new();
+  let mut errors = Vec::new();
+  let map = match config.get(&Value::from("procs")).map(|v| v.as_object()) {
+    Some(Ok(map)) => map,
+    Some(Err(err)) => return (procs, vec![err]),
+    None => return (procs, errors),
+  };
+  for (name, proc) in map {
+    let proc = value_to_string(&name)
+      .and_then(|name| ProcConfig::from_val(name, settings, proc, ctx));
+    match proc {
+      Ok(Some(proc)) => procs.push(proc),
+      Ok(None) => (),
+      Err(err) => errors.push(err),
+    }
+  }
+  (procs, errors)
+}
+
+/// Validates the config like `Config::from_value`, but collects all problems
+/// instead of stopping at the first one (`--check`).
+pub fn check_config(
+  value: &Value,
+  ctx: &ConfigContext,
+  settings: &Settings,
+) -> Vec<anyhow::Error> {
+  let config = match Val::new(value).and_then(|config| config.as_object()) {
+    Ok(config) => config,
+    Err(err) => return vec![err],
+  };
+
+  let (procs, mut problems) = parse_procs(&config, ctx, settings);
+  if let Some(warning) = unknown_deps_warning(&procs) {
+    problems.push(anyhow::Error::msg(warning));
+  }
+  if let Err(err) = check_deps(&procs) {
+    problems.push(err);
+  }
+
+  if let Some(addr) = config.get(&Value::from("server")) {
+    if let Err(err) = addr.as_str().and_then(ServerConfig::from_str) {
+      problems.push(err);
+    }
+  }
+
+  problems
+}
+
+#[derive(Clone)]
 pub struct ProcConfig {
   pub name: String,
   pub cmd: CmdConfig,
@@ -93,57 +335,135 @@ pub struct ProcConfig {
   pub env: Option<IndexMap<String, Option<String>>>,
   pub autostart: bool,
   pub autorestart: bool,
+  pub autorestart_on: RestartOn,
 
   pub stop: StopSignal,
 
   pub mouse_scroll_speed: usize,
   pub scrollback_len: usize,
+  pub scrollback_bytes: usize,
+  pub lazy_parse: bool,
+  pub raw_passthrough: bool,
+  pub line_numbers: bool,
+  pub keep_history_on_restart: bool,
+  pub watch: Vec<WatchPattern>,
+  pub enter_sends: NewlineMode,
+  pub key_encoding: KeyEncoding,
+  pub kitty_keyboard: bool,
+  pub follow_output: bool,
+  /// Keep output of full-screen programs on the normal screen, so it stays in
+  /// the scrollback.
+  pub disable_alt_screen: bool,
+  /// Erase leftovers of longer lines redrawn after a carriage return (e.g. by
+  /// progress bars).
+  pub collapse_progress: bool,
+  /// Display runs of identical lines as a single line with a repeat count.
+  pub dedup_lines: bool,
+  /// Collapse runs of identical lines in text copied in copy mode.
+  pub dedup_copy: bool,
+  /// Names of procs that have to be running (and ready, if they have
+  /// `ready_when`) before the proc is autostarted.
+  pub deps: Vec<String>,
+  /// Ask for confirmation before killing or restarting the proc.
+  pub critical: bool,
+  /// Forward mouse events to the proc when it asks for them.
+  pub mouse: bool,
+  /// Signals sent to the proc by keys pressed while its terminal is focused.
+  pub signal_keys: IndexMap<Key, Signal>,
+  /// Color of the proc name in prefixed output. Picked from a palette if not
+  /// set.
+  pub color: Option<Color>,
+  pub start_delay: Duration,
+  pub cell_pixel_size: Option<(u16, u16)>,
+  pub tab_width: u16,
+  pub middle_click_paste: bool,
+  pub scroll_acceleration: bool,
+  pub scroll_acceleration_max: usize,
+  pub copy_scrolloff: usize,
+  /// Parameters to ask values for before starting (like of a just recipe).
+  /// The values are appended to the command.
+  pub params: Vec<String>,
 }
 
 impl ProcConfig {
+  /// Proc with default options, taking the global ones from `settings`.
+  pub fn from_settings(
+    name: String,
+    cmd: CmdConfig,
+    settings: &Settings,
+  ) -> ProcConfig {
+    ProcConfig {
+      watch: patterns_for(&settings.watch_patterns, &name),
+      name,
+      cmd,
+      cwd: None,
+      env: None,
+      autostart: true,
+      autorestart: false,
+      autorestart_on: RestartOn::default(),
+      stop: settings.default_stop.clone(),
+      mouse_scroll_speed: settings.mouse_scroll_speed,
+      scrollback_len: settings.scrollback_len,
+      scrollback_bytes: settings.scrollback_bytes,
+      lazy_parse: settings.lazy_parse,
+      raw_passthrough: false,
+      line_numbers: settings.line_numbers,
+      keep_history_on_restart: settings.keep_history_on_restart,
+      enter_sends: NewlineMode::default(),
+      key_encoding: KeyEncoding::default(),
+      params: Vec::new(),
+      kitty_keyboard: false,
+      follow_output: true,
+      disable_alt_screen: false,
+      collapse_progress: false,
+      dedup_lines: false,
+      dedup_copy: false,
+      deps: Vec::new(),
+      critical: false,
+      mouse: true,
+      signal_keys: IndexMap::new(),
+      color: None,
+      start_delay: Duration::ZERO,
+      cell_pixel_size: settings.cell_pixel_size,
+      tab_width: settings.tab_width,
+      middle_click_paste: settings.middle_click_paste,
+      scroll_acceleration: settings.scroll_acceleration,
+      scroll_acceleration_max: settings.scroll_acceleration_max,
+      copy_scrolloff: settings.copy_scrolloff,
+    }
+  }
+
   fn from_val(
     name: String,
-    mouse_scroll_speed: usize,
-    scrollback_len: usize,
+    settings: &Settings,
     val: Val,
     ctx: &ConfigContext,
   ) -> Result<Option<ProcConfig>> {
     match val.raw() {
       Value::Null => Ok(None),
-      Value::Bool(_) => todo!(),
-      Value::Number(_) => todo!(),
-      Value::String(shell) => Ok(Some(ProcConfig {
+      Value::Bool(_) | Value::Number(_) => {
+        Err(val.error_at("Expected process config"))
+      }
+      Value::String(shell) => Ok(Some(ProcConfig::from_settings(
         name,
-        cmd: CmdConfig::Shell {
+        CmdConfig::Shell {
           shell: shell.to_owned(),
         },
-        cwd: None,
-        env: None,
-        autostart: true,
-        autorestart: false,
-        stop: StopSignal::default(),
-
-        mouse_scroll_speed,
-        scrollback_len,
-      })),
+        settings,
+      ))),
       Value::Sequence(_) => {
         let cmd = val.as_array()?;
         let cmd = cmd
           .into_iter()
           .map(|item| item.as_str().map(|s| s.to_owned()))
           .collect::<Result<Vec<_>>>()?;
+        let cmd = resolve_cmd_path(cmd, settings, ctx)?;
 
-        Ok(Some(ProcConfig {
+        Ok(Some(ProcConfig::from_settings(
           name,
-          cmd: CmdConfig::Cmd { cmd },
-          cwd: None,
-          env: None,
-          autostart: true,
-          autorestart: false,
-          stop: StopSignal::default(),
-          mouse_scroll_speed,
-          scrollback_len,
-        }))
+          CmdConfig::Cmd { cmd },
+          settings,
+        )))
       }
       Value::Mapping(_) => {
         let map = val.as_object()?;
@@ -163,8 +483,14 @@ impl ProcConfig {
             (Some(shell), None) => CmdConfig::Shell {
               shell: shell.as_str()?.to_owned(),
             },
-            (None, None) => todo!(),
-            (Some(_), Some(_)) => todo!(),
+            (None, None) => {
+              return Err(val.error_at("Expected \"cmd\" or \"shell\""));
+            }
+            (Some(_), Some(_)) => {
+              return Err(
+                val.error_at("Expected only one of \"cmd\" and \"shell\""),
+              );
+            }
           }
         };
 
@@ -173,10 +499,12 @@ impl ProcConfig {
             let cwd = cwd.as_str()?;
             let mut buf = OsString::new();
             if let Some(rest) = cwd.strip_prefix("<CONFIG_DIR>") {
-              if let Some(parent) = dunce::canonicalize(&ctx.path)?.parent() {
-                buf.push(parent);
-              }
+              buf.push(ctx.dir()?);
               buf.push(rest);
+            } else if settings.paths_relative_to_config
+              && Path::new(cwd).is_relative()
+            {
+              buf.push(ctx.dir()?.join(cwd));
             } else {
               buf.push(cwd);
             }
@@ -184,6 +512,13 @@ impl ProcConfig {
           }
           None => None,
         };
+        // With `cwd` set, the command is looked up relative to it instead.
+        let cmd = match (cmd, &cwd) {
+          (CmdConfig::Cmd { cmd }, None) => CmdConfig::Cmd {
+            cmd: resolve_cmd_path(cmd, settings, ctx)?,
+          },
+          (cmd, _) => cmd,
+        };
 
         let env = match map.get(&Value::from("env")) {
           Some(env) => {
@@ -249,26 +584,164 @@ impl ProcConfig {
           .get(&Value::from("autostart"))
           .map_or(Ok(true), |v| v.as_bool())?;
 
-        let autorestart = map
-          .get(&Value::from("autorestart"))
-          .map_or(Ok(false), |v| v.as_bool())?;
+        let autorestart_on = match (
+          map.get(&Value::from("autorestart_on")),
+          map.get(&Value::from("autorestart_unless")),
+        ) {
+          (Some(_), Some(unless)) => bail!(
+            unless.error_at("Can't be used together with \"autorestart_on\"")
+          ),
+          (Some(codes), None) => RestartOn::Codes(exit_codes(codes)?),
+          (None, Some(codes)) => RestartOn::AllExcept(exit_codes(codes)?),
+          (None, None) => RestartOn::Failure,
+        };
+        // Exit codes enable autorestart, unless it is disabled explicitly,
+        // which is likely a mistake.
+        let autorestart = match map.get(&Value::from("autorestart")) {
+          Some(v) if !v.as_bool()? && autorestart_on != RestartOn::Failure => {
+            bail!(v.error_at(
+              "Can't be false together with \"autorestart_on\" or \
+               \"autorestart_unless\""
+            ))
+          }
+          Some(v) => v.as_bool()?,
+          None => autorestart_on != RestartOn::Failure,
+        };
 
         let stop_signal = if let Some(val) = map.get(&Value::from("stop")) {
           StopSignal::from_val(val)?
         } else {
-          StopSignal::default()
+          settings.default_stop.clone()
         };
 
+        let scrollback_bytes = map
+          .get(&Value::from("scrollback_bytes"))
+          .map_or(Ok(settings.scrollback_bytes), |v| v.as_usize())?;
+
+        let raw_passthrough = map
+          .get(&Value::from("raw_passthrough"))
+          .map_or(Ok(false), |v| v.as_bool())?;
+
+        let line_numbers = map
+          .get(&Value::from("line_numbers"))
+          .map_or(Ok(settings.line_numbers), |v| v.as_bool())?;
+
+        let keep_history_on_restart = map
+          .get(&Value::from("keep_history_on_restart"))
+          .map_or(Ok(settings.keep_history_on_restart), |v| v.as_bool())?;
+
+        let enter_sends = map
+          .get(&Value::from("enter_sends"))
+          .map_or(Ok(NewlineMode::default()), NewlineMode::from_val)?;
+
+        let key_encoding = map
+          .get(&Value::from("key_encoding"))
+          .map_or(Ok(KeyEncoding::default()), KeyEncoding::from_val)?;
+
+        let kitty_keyboard = map
+          .get(&Value::from("kitty_keyboard"))
+          .map_or(Ok(false), |v| v.as_bool())?;
+
+        let follow_output = map
+          .get(&Value::from("follow_output"))
+          .map_or(Ok(true), |v| v.as_bool())?;
+
+        let disable_alt_screen = map
+          .get(&Value::from("disable_alt_screen"))
+          .map_or(Ok(false), |v| v.as_bool())?;
+
+        let collapse_progress = map
+          .get(&Value::from("collapse_progress"))
+          .map_or(Ok(false), |v| v.as_bool())?;
+
+        let dedup_lines = map
+          .get(&Value::from("dedup_lines"))
+          .map_or(Ok(false), |v| v.as_bool())?;
+
+        let dedup_copy = map
+          .get(&Value::from("dedup_copy"))
+          .map_or(Ok(false), |v| v.as_bool())?;
+
+        let critical = map
+          .get(&Value::from("critical"))
+          .map_or(Ok(false), |v| v.as_bool())?;
+
+        let mouse = map
+          .get(&Value::from("mouse"))
+          .map_or(Ok(true), |v| v.as_bool())?;
+
+        let signal_keys = match map.get(&Value::from("signal_keys")) {
+          Some(keys) => keys
+            .as_object()?
+            .into_iter()
+            .map(|(key, val)| {
+              let key = Key::parse(value_to_string(&key)?.as_str())
+                .map_err(|err| val.error_at(err.to_string()))?;
+              let signal = Signal::parse(&value_to_string(val.raw())?)
+                .map_err(|err| val.error_at(err.to_string()))?;
+              Ok((key, signal))
+            })
+            .collect::<Result<IndexMap<_, _>>>()?,
+          None => IndexMap::new(),
+        };
+
+        let color = map
+          .get(&Value::from("color"))
+          .map(parse_color)
+          .transpose()?;
+
+        let mut watch = patterns_for(&settings.watch_patterns, &name);
+        if let Some(ready_when) = map.get(&Value::from("ready_when")) {
+          let regex = Regex::new(ready_when.as_str()?)
+            .map_err(|err| ready_when.error_at(err.to_string()))?;
+          watch.push(WatchPattern {
+            proc: Some(name.clone()),
+            regex,
+            action: WatchAction::Ready,
+          });
+        }
+
+        let deps = match map.get(&Value::from("deps")) {
+          Some(deps) => deps
+            .as_array()?
+            .iter()
+            .map(|dep| Ok(dep.as_str()?.to_string()))
+            .collect::<Result<Vec<_>>>()?,
+          None => Vec::new(),
+        };
+
+        let start_delay = map
+          .get(&Value::from("start_delay"))
+          .map_or(Ok(0), |v| v.as_usize())?;
+        let start_delay = Duration::from_millis(start_delay as u64);
+
         Ok(Some(ProcConfig {
-          name,
-          cmd,
           cwd,
           env,
           autostart,
           autorestart,
+          autorestart_on,
           stop: stop_signal,
-          mouse_scroll_speed,
-          scrollback_len,
+          scrollback_bytes,
+          raw_passthrough,
+          line_numbers,
+          keep_history_on_restart,
+          watch,
+          enter_sends,
+          key_encoding,
+          kitty_keyboard,
+          follow_output,
+          disable_alt_screen,
+          collapse_progress,
+          dedup_lines,
+          dedup_copy,
+          deps,
+          critical,
+          mouse,
+          signal_keys,
+          color,
+          start_delay,
+          ..ProcConfig::from_settings(name, cmd, settings)
         }))
       }
       Value::Tagged(_) => anyhow::bail!("Yaml tags are not supported"),
@@ -276,6 +749,169 @@ impl ProcConfig {
   }
 }
 
+/// Makes a relative command path (like `./run.sh`, but not `npm`) relative
+/// to the config directory, if `paths_relative_to_config` is enabled.
+fn resolve_cmd_path(
+  mut cmd: Vec<String>,
+  settings: &Settings,
+  ctx: &ConfigContext,
+) -> Result<Vec<String>> {
+  if let Some(head) = cmd.first_mut() {
+    let path = Path::new(head.as_str());
+    if settings.paths_relative_to_config
+      && path.is_relative()
+      && path.components().count() > 1
+    {
+      *head = ctx.dir()?.join(path).to_string_lossy().into_owned();
+    }
+  }
+  Ok(cmd)
+}
+
+fn exit_codes(val: &Val) -> Result<Vec<u32>> {
+  let codes = val.as_array()?;
+  if codes.is_empty() {
+    bail!(val.error_at("Expected at least one exit code"));
+  }
+  codes
+    .iter()
+    .map(|code| {
+      u32::try_from(code.as_usize()?)
+        .map_err(|_| code.error_at("Exit code is too big"))
+    })
+    .collect()
+}
+
+/// Merges config files listed in `include` (relative to the including file)
+/// into the config and adds them to `included` in the order they were merged.
+pub fn resolve_includes(
+  value: Value,
+  path: &Path,
+  included: &mut Vec<PathBuf>,
+) -> Result<Value> {
+  resolve_includes_rec(value, path, &mut Vec::new(), included)
+}
+
+fn resolve_includes_rec(
+  mut value: Value,
+  path: &Path,
+  stack: &mut Vec<PathBuf>,
+  included: &mut Vec<PathBuf>,
+) -> Result<Value> {
+  let path = dunce::canonicalize(path)?;
+  if stack.contains(&path) {
+    let chain = stack
+      .iter()
+      .chain(std::iter::once(&path))
+      .map(|p| p.to_string_lossy())
+      .collect::<Vec<_>>();
+    bail!("Include cycle: {}", chain.join(" -> "));
+  }
+
+  let includes = match value.get("include") {
+    Some(_) => include_paths(&value).map_err(|err| {
+      // Errors in the config file itself are located by the caller.
+      if stack.is_empty() {
+        err
+      } else {
+        ValError::in_file(err, &path)
+      }
+    })?,
+    None => return Ok(value),
+  };
+  if let Value::Mapping(map) = &mut value {
+    map.remove("include");
+  }
+
+  let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+  stack.push(path);
+  let mut merged = Value::Mapping(Default::default());
+  for include in includes {
+    let include = dir.join(include);
+    let value = read_included(&include)?;
+    let value = resolve_includes_rec(value, &include, stack, included)?;
+    included.push(dunce::canonicalize(&include)?);
+    merge_config_values(&mut merged, value);
+  }
+  merge_config_values(&mut merged, value);
+  stack.pop();
+
+  Ok(merged)
+}
+
+fn include_paths(value: &Value) -> Result<Vec<String>> {
+  let value = Val::new(value)?;
+  let config = value.as_object()?;
+  match config.get(&Value::from("include")) {
+    Some(includes) => includes
+      .as_array()?
+      .into_iter()
+      .map(|item| item.as_str().map(|s| s.to_owned()))
+      .collect(),
+    None => Ok(Vec::new()),
+  }
+}
+
+fn read_included(path: &Path) -> Result<Value> {
+  let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+  if !matches!(ext, "yaml" | "yml" | "json") {
+    bail!(
+      "Included config '{}' must be yaml or json.",
+      path.to_string_lossy()
+    );
+  }
+  let file = std::fs::File::open(path).map_err(|e| {
+    anyhow::Error::msg(format!(
+      "Included config '{}': {}",
+      path.to_string_lossy(),
+      e
+    ))
+  })?;
+  let mut value: Value =
+    serde_yaml::from_reader(std::io::BufReader::new(file))?;
+  value.apply_merge()?;
+  Ok(value)
+}
+
+/// Procs are replaced by name, other mappings are merged recursively.
+fn merge_config_values(base: &mut Value, value: Value) {
+  match (base, value) {
+    (Value::Mapping(base), Value::Mapping(value)) => {
+      for (key, value) in value {
+        let is_procs = key.as_str() == Some("procs");
+        match (base.get_mut(&key), value) {
+          (Some(Value::Mapping(procs)), Value::Mapping(value)) if is_procs => {
+            for (name, proc) in value {
+              procs.insert(name, proc);
+            }
+          }
+          (Some(base), value) => merge_values(base, value),
+          (None, value) => {
+            base.insert(key, value);
+          }
+        }
+      }
+    }
+    (base, value) => *base = value,
+  }
+}
+
+fn merge_values(base: &mut Value, value: Value) {
+  match (base, value) {
+    (Value::Mapping(base), Value::Mapping(value)) => {
+      for (key, value) in value {
+        match base.get_mut(&key) {
+          Some(base) => merge_values(base, value),
+          None => {
+            base.insert(key, value);
+          }
+        }
+      }
+    }
+    (base, value) => *base = value,
+  }
+}
+
 pub enum ServerConfig {
   Tcp(String),
 }
@@ -286,13 +922,35 @@ impl ServerConfig {
   }
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum CmdConfig {
   Cmd { cmd: Vec<String> },
   Shell { shell: String },
 }
 
+impl CmdConfig {
+  /// Command as it would be typed in a shell.
+  pub fn command_line(&self) -> String {
+    match self {
+      CmdConfig::Cmd { cmd } => cmd
+        .iter()
+        .map(|arg| {
+          let quote = arg.is_empty()
+            || arg.contains(|c: char| c.is_whitespace() || "'\"".contains(c));
+          if quote {
+            format!("'{}'", arg.replace('\'', "'\\''"))
+          } else {
+            arg.clone()
+          }
+        })
+        .collect::<Vec<_>>()
+        .join(" "),
+      CmdConfig::Shell { shell } => shell.clone(),
+    }
+  }
+}
+
 impl From<&ProcConfig> for CommandBuilder {
   fn from(cfg: &ProcConfig) -> Self {
     let mut cmd = match &cfg.cmd {
@@ -324,3 +982,123 @@ impl From<&ProcConfig> for CommandBuilder {
     cmd
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::temp_path;
+
+  #[test]
+  fn error_location() {
+    let path = temp_path("error-location.yaml");
+    let source = "procs:\n  web:\n    shell: npm start\n    stop: SIGFOO\n";
+    std::fs::write(&path, source).unwrap();
+    let ctx = ConfigContext {
+      path: path.clone(),
+      includes: Vec::new(),
+    };
+    let value: Value = serde_yaml::from_str(source).unwrap();
+    let err = Config::from_value(&value, &ctx, &Settings::default())
+      .err()
+      .unwrap();
+    let msg = ctx.locate_error(err).to_string();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(msg.contains("at <config>.procs.web.stop"));
+    assert!(msg.contains(&format!("{}:4:5", path.to_string_lossy())));
+    assert!(msg.contains("4 |     stop: SIGFOO"));
+  }
+
+  #[test]
+  fn error_location_in_include() {
+    let dir = temp_path("error-include");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("mprocs.yaml");
+    let source = "include: [procs.yaml]\nprocs:\n  api:\n    shell: x\n";
+    std::fs::write(&path, source).unwrap();
+    let inc = "procs:\n  web:\n    shell: x\n    stop: SIGFOO\n";
+    std::fs::write(dir.join("procs.yaml"), inc).unwrap();
+
+    let mut ctx = ConfigContext {
+      path: path.clone(),
+      includes: Vec::new(),
+    };
+    let value: Value = serde_yaml::from_str(source).unwrap();
+    let value = resolve_includes(value, &path, &mut ctx.includes).unwrap();
+    let err = Config::from_value(&value, &ctx, &Settings::default())
+      .err()
+      .unwrap();
+    let pos = ctx.error_pos(&err).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(pos.path.ends_with("procs.yaml"));
+    assert_eq!((pos.line, pos.col), (4, 5));
+  }
+
+  #[test]
+  fn autorestart_codes() {
+    let ctx = ConfigContext {
+      path: PathBuf::from("mprocs.yaml"),
+      includes: Vec::new(),
+    };
+    let parse = |source: &str| {
+      let value: Value = serde_yaml::from_str(source).unwrap();
+      Config::from_value(&value, &ctx, &Settings::default())
+    };
+
+    let cfg =
+      parse("procs:\n  a:\n    shell: x\n    autorestart_on: [1]\n").unwrap();
+    assert!(cfg.procs[0].autorestart);
+    assert_eq!(cfg.procs[0].autorestart_on, RestartOn::Codes(vec![1]));
+
+    let err = parse(
+      "procs:\n  a:\n    shell: x\n    autorestart: false\n    autorestart_on: [1]\n",
+    )
+    .err()
+    .unwrap();
+    assert!(err.to_string().contains("at <config>.procs.a.autorestart"));
+
+    let err = parse("procs:\n  a:\n    shell: x\n    autorestart_unless: []\n")
+      .err()
+      .unwrap();
+    assert!(err
+      .to_string()
+      .contains("at <config>.procs.a.autorestart_unless"));
+  }
+
+  #[test]
+  fn signal_keys() {
+    let ctx = ConfigContext {
+      path: PathBuf::from("mprocs.yaml"),
+      includes: Vec::new(),
+    };
+    let parse = |source: &str| {
+      let value: Value = serde_yaml::from_str(source).unwrap();
+      Config::from_value(&value, &ctx, &Settings::default())
+    };
+
+    let cfg =
+      parse("procs:\n  a:\n    shell: x\n    signal_keys: { <C-g>: quit }\n")
+        .unwrap();
+    assert_eq!(
+      cfg.procs[0].signal_keys.get(&Key::parse("<C-g>").unwrap()),
+      Some(&Signal::parse("SIGQUIT").unwrap())
+    );
+
+    let err =
+      parse("procs:\n  a:\n    shell: x\n    signal_keys: { <C-g>: foo }\n")
+        .err()
+        .unwrap();
+    assert!(err
+      .to_string()
+      .contains("at <config>.procs.a.signal_keys.<C-g>"));
+
+    let err =
+      parse("procs:\n  a:\n    shell: x\n    signal_keys: { <F99>: quit }\n")
+        .err()
+        .unwrap();
+    assert!(err
+      .to_string()
+      .contains("at <config>.procs.a.signal_keys.<F99>"));
+  }
+}
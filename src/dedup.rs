@@ -0,0 +1,59 @@
+/// Groups runs of identical consecutive lines. Returns the index of the first
+/// line of each run and the length of the run. Empty lines are never grouped.
+pub fn count_runs<S: AsRef<str>>(lines: &[S]) -> Vec<(usize, usize)> {
+  let mut runs: Vec<(usize, usize)> = Vec::new();
+  for (i, line) in lines.iter().enumerate() {
+    let line = line.as_ref();
+    match runs.last_mut() {
+      Some((first, count))
+        if !line.is_empty() && lines[*first].as_ref() == line =>
+      {
+        *count += 1
+      }
+      _ => runs.push((i, 1)),
+    }
+  }
+  runs
+}
+
+/// Shown after a line collapsed from `count` identical lines.
+pub fn repeat_suffix(count: usize) -> String {
+  format!(" (x{})", count)
+}
+
+/// Collapses runs of identical lines of the text into a single line followed
+/// by the number of repeats, like `uniq -c`.
+pub fn dedup_text(text: &str) -> String {
+  let lines = text.split('\n').collect::<Vec<_>>();
+  count_runs(&lines)
+    .into_iter()
+    .map(|(i, count)| {
+      if count > 1 {
+        format!("{}{}", lines[i], repeat_suffix(count))
+      } else {
+        lines[i].to_string()
+      }
+    })
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn runs() {
+    let lines = ["a", "a", "b", "", "", "a"];
+    assert_eq!(
+      count_runs(&lines),
+      vec![(0, 2), (2, 1), (3, 1), (4, 1), (5, 1)]
+    );
+  }
+
+  #[test]
+  fn text() {
+    assert_eq!(dedup_text("ping\nping\nping\ndone"), "ping (x3)\ndone");
+    assert_eq!(dedup_text("a\n\n\nb"), "a\n\n\nb");
+  }
+}
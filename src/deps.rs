@@ -0,0 +1,144 @@
+use anyhow::{bail, Result};
+
+use crate::{config::ProcConfig, proc::handle::ProcHandle};
+
+/// Fails if procs depend on each other in a cycle, because none of them
+/// would ever start.
+pub fn check_deps(procs: &[ProcConfig]) -> Result<()> {
+  let graph = procs
+    .iter()
+    .map(|p| (p.name.as_str(), p.deps.as_slice()))
+    .collect::<Vec<_>>();
+  if let Some(cycle) = find_cycle(&graph) {
+    bail!(
+      "Circular dependency between processes: {}",
+      cycle.join(" -> ")
+    );
+  }
+  Ok(())
+}
+
+/// Message about deps that don't name any proc, if there are such deps.
+pub fn unknown_deps_warning(procs: &[ProcConfig]) -> Option<String> {
+  let unknown = procs
+    .iter()
+    .flat_map(|p| p.deps.iter().map(move |dep| (&p.name, dep)))
+    .filter(|(_, dep)| !procs.iter().any(|p| &p.name == *dep))
+    .map(|(name, dep)| format!("\"{}\" (in {})", dep, name))
+    .collect::<Vec<_>>();
+  if unknown.is_empty() {
+    None
+  } else {
+    Some(format!("Unknown deps: {}", unknown.join(", ")))
+  }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mark {
+  New,
+  Visiting,
+  Done,
+}
+
+/// Depth-first search over procs (names with deps) for a dependency cycle.
+/// Returns names along the cycle, starting and ending with the same proc.
+/// Deps on unknown procs are skipped.
+fn find_cycle<'a>(graph: &[(&'a str, &[String])]) -> Option<Vec<&'a str>> {
+  let mut marks = vec![Mark::New; graph.len()];
+  let mut path = Vec::new();
+  (0..graph.len()).find_map(|i| visit(graph, i, &mut marks, &mut path))
+}
+
+fn visit<'a>(
+  graph: &[(&'a str, &[String])],
+  i: usize,
+  marks: &mut [Mark],
+  path: &mut Vec<usize>,
+) -> Option<Vec<&'a str>> {
+  match marks[i] {
+    Mark::Done => return None,
+    Mark::Visiting => {
+      let start = path.iter().position(|&j| j == i).unwrap_or(0);
+      let mut cycle = path[start..]
+        .iter()
+        .map(|&j| graph[j].0)
+        .collect::<Vec<_>>();
+      cycle.push(graph[i].0);
+      return Some(cycle);
+    }
+    Mark::New => (),
+  }
+  marks[i] = Mark::Visiting;
+  path.push(i);
+  for dep in graph[i].1 {
+    if let Some(j) = graph.iter().position(|(name, _)| *name == dep.as_str()) {
+      if let Some(cycle) = visit(graph, j, marks, path) {
+        return Some(cycle);
+      }
+    }
+  }
+  path.pop();
+  marks[i] = Mark::Done;
+  None
+}
+
+/// Removes dependencies that became ready from the lists of waiting procs.
+/// Returns ids of procs that were waiting and have all dependencies ready
+/// now, so they should be started.
+pub fn procs_to_start(procs: &mut [ProcHandle]) -> Vec<usize> {
+  let ready = procs
+    .iter()
+    .filter(|p| p.is_ready())
+    .map(|p| p.cfg().name.clone())
+    .collect::<Vec<_>>();
+  let ready = ready.iter().map(String::as_str).collect::<Vec<_>>();
+  procs
+    .iter_mut()
+    .filter(|p| !p.waiting_for().is_empty())
+    .filter_map(|p| {
+      let done = p.update_waiting(&ready) && p.waiting_for().is_empty();
+      done.then(|| p.id())
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn deps(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+  }
+
+  #[test]
+  fn no_cycle() {
+    let (a, b) = (deps(&["b", "c"]), deps(&["c"]));
+    let graph = [("a", a.as_slice()), ("b", b.as_slice()), ("c", &[][..])];
+    assert_eq!(find_cycle(&graph), None);
+  }
+
+  #[test]
+  fn two_node_cycle() {
+    let (a, b) = (deps(&["b"]), deps(&["a"]));
+    let graph = [("a", a.as_slice()), ("b", b.as_slice())];
+    assert_eq!(find_cycle(&graph), Some(vec!["a", "b", "a"]));
+  }
+
+  #[test]
+  fn three_node_cycle() {
+    let (a, b, c) = (deps(&["b"]), deps(&["c", "x"]), deps(&["a"]));
+    let graph = [
+      ("web", &[][..]),
+      ("a", a.as_slice()),
+      ("b", b.as_slice()),
+      ("c", c.as_slice()),
+    ];
+    assert_eq!(find_cycle(&graph), Some(vec!["a", "b", "c", "a"]));
+  }
+
+  #[test]
+  fn self_dependency() {
+    let a = deps(&["a"]);
+    assert_eq!(find_cycle(&[("a", a.as_slice())]), Some(vec!["a", "a"]));
+  }
+}
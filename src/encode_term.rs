@@ -3,7 +3,7 @@ use std::fmt::Write;
 use anyhow::Result;
 use crossterm::event::{KeyCode, KeyModifiers, MouseButton, MouseEventKind};
 
-use crate::{key::Key, mouse::MouseEvent};
+use crate::{key::Key, mouse::MouseEvent, yaml_val::Val};
 
 pub const CSI: &str = "\x1b[";
 pub const SS3: &str = "\x1bO";
@@ -14,7 +14,84 @@ pub const SS3: &str = "\x1bO";
 pub struct KeyCodeEncodeModes {
   pub enable_csi_u_key_encoding: bool,
   pub application_cursor_keys: bool,
-  pub newline_mode: bool,
+  pub newline_mode: NewlineMode,
+  pub key_encoding: KeyEncoding,
+}
+
+/// What the Enter key sends.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum NewlineMode {
+  #[default]
+  Cr,
+  Lf,
+  CrLf,
+}
+
+impl NewlineMode {
+  pub fn from_val(val: &Val) -> Result<Self> {
+    match val.as_str()? {
+      "cr" => Ok(NewlineMode::Cr),
+      "lf" => Ok(NewlineMode::Lf),
+      "crlf" => Ok(NewlineMode::CrLf),
+      _ => Err(val.error_at("Expected \"cr\", \"lf\" or \"crlf\"")),
+    }
+  }
+}
+
+/// Which terminal to mimic when encoding unmodified function and editing
+/// keys. Modified keys always use the xterm encoding.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum KeyEncoding {
+  #[default]
+  Xterm,
+  Vt100,
+  Linux,
+}
+
+impl KeyEncoding {
+  pub fn from_val(val: &Val) -> Result<Self> {
+    match val.as_str()? {
+      "xterm" => Ok(KeyEncoding::Xterm),
+      "vt100" => Ok(KeyEncoding::Vt100),
+      "linux" => Ok(KeyEncoding::Linux),
+      _ => Err(val.error_at("Expected \"xterm\", \"vt100\" or \"linux\"")),
+    }
+  }
+
+  /// Sequence for an unmodified F-key, if it differs from xterm.
+  fn function_key(self, n: u8) -> Option<&'static str> {
+    match self {
+      KeyEncoding::Xterm => None,
+      // As in the vt100 terminfo entry: PF1-PF4 followed by keypad keys.
+      KeyEncoding::Vt100 => match n {
+        5 => Some("\x1bOt"),
+        6 => Some("\x1bOu"),
+        7 => Some("\x1bOv"),
+        8 => Some("\x1bOl"),
+        9 => Some("\x1bOw"),
+        10 => Some("\x1bOx"),
+        _ => None,
+      },
+      KeyEncoding::Linux => match n {
+        1 => Some("\x1b[[A"),
+        2 => Some("\x1b[[B"),
+        3 => Some("\x1b[[C"),
+        4 => Some("\x1b[[D"),
+        5 => Some("\x1b[[E"),
+        _ => None,
+      },
+    }
+  }
+
+  /// Sequence for unmodified Home/End, if it differs from xterm.
+  fn home_end_key(self, code: KeyCode) -> Option<&'static str> {
+    match (self, code) {
+      (KeyEncoding::Xterm, _) => None,
+      (_, KeyCode::Home) => Some("\x1b[1~"),
+      (_, KeyCode::End) => Some("\x1b[4~"),
+      _ => None,
+    }
+  }
 }
 
 impl Default for KeyCodeEncodeModes {
@@ -22,7 +99,8 @@ impl Default for KeyCodeEncodeModes {
     KeyCodeEncodeModes {
       enable_csi_u_key_encoding: false,
       application_cursor_keys: false,
-      newline_mode: false,
+      newline_mode: NewlineMode::Cr,
+      key_encoding: KeyEncoding::Xterm,
     }
   }
 }
@@ -118,9 +196,10 @@ pub fn encode_key(key: &Key, modes: KeyCodeEncodeModes) -> Result<String> {
         if mods.contains(KeyModifiers::ALT) {
           buf.push(0x1b as char);
         }
-        buf.push(c);
-        if modes.newline_mode && code == Enter {
-          buf.push(0x0a as char);
+        match (code, modes.newline_mode) {
+          (Enter, NewlineMode::Lf) => buf.push('\n'),
+          (Enter, NewlineMode::CrLf) => buf.push_str("\r\n"),
+          _ => buf.push(c),
         }
       }
     }
@@ -184,6 +263,8 @@ pub fn encode_key(key: &Key, modes: KeyCodeEncodeModes) -> Result<String> {
         || mods.contains(KeyModifiers::CONTROL)
       {
         write!(buf, "{}1;{}{}", CSI, 1 + encode_modifiers(mods), c)?;
+      } else if let Some(s) = modes.key_encoding.home_end_key(code) {
+        buf.push_str(s);
       } else {
         write!(buf, "{}{}", csi_or_ss3, c)?;
       }
@@ -209,7 +290,14 @@ pub fn encode_key(key: &Key, modes: KeyCodeEncodeModes) -> Result<String> {
     }
 
     F(n) => {
-      if mods.is_empty() && n < 5 {
+      let alt_encoding = if mods.is_empty() {
+        modes.key_encoding.function_key(n)
+      } else {
+        None
+      };
+      if let Some(s) = alt_encoding {
+        buf.push_str(s);
+      } else if mods.is_empty() && n < 5 {
         // F1-F4 are encoded using SS3 if there are no modifiers
         let s = match n {
           1 => "\x1bOP",
@@ -615,3 +703,71 @@ pub fn encode_mouse_event(mev: MouseEvent) -> String {
 
   buf
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn encode_enter(mods: KeyModifiers, newline_mode: NewlineMode) -> String {
+    let modes = KeyCodeEncodeModes {
+      newline_mode,
+      ..Default::default()
+    };
+    encode_key(&Key::new(KeyCode::Enter, mods), modes).unwrap()
+  }
+
+  #[test]
+  fn enter_newline_mode() {
+    let none = KeyModifiers::NONE;
+    assert_eq!(encode_enter(none, NewlineMode::Cr), "\r");
+    assert_eq!(encode_enter(none, NewlineMode::Lf), "\n");
+    assert_eq!(encode_enter(none, NewlineMode::CrLf), "\r\n");
+
+    let alt = KeyModifiers::ALT;
+    assert_eq!(encode_enter(alt, NewlineMode::Cr), "\x1b\r");
+    assert_eq!(encode_enter(alt, NewlineMode::CrLf), "\x1b\r\n");
+  }
+
+  fn encode_with(code: KeyCode, key_encoding: KeyEncoding) -> String {
+    let modes = KeyCodeEncodeModes {
+      key_encoding,
+      ..Default::default()
+    };
+    encode_key(&Key::new(code, KeyModifiers::NONE), modes).unwrap()
+  }
+
+  #[test]
+  fn key_encoding_f5() {
+    let f5 = KeyCode::F(5);
+    assert_eq!(encode_with(f5, KeyEncoding::Xterm), "\x1b[15~");
+    assert_eq!(encode_with(f5, KeyEncoding::Vt100), "\x1bOt");
+    assert_eq!(encode_with(f5, KeyEncoding::Linux), "\x1b[[E");
+  }
+
+  #[test]
+  fn key_encoding_home() {
+    let home = KeyCode::Home;
+    assert_eq!(encode_with(home, KeyEncoding::Xterm), "\x1b[H");
+    assert_eq!(encode_with(home, KeyEncoding::Vt100), "\x1b[1~");
+    assert_eq!(encode_with(home, KeyEncoding::Linux), "\x1b[1~");
+
+    // Modified keys keep the xterm encoding.
+    let key = Key::new(home, KeyModifiers::SHIFT);
+    let modes = KeyCodeEncodeModes {
+      key_encoding: KeyEncoding::Linux,
+      ..Default::default()
+    };
+    assert_eq!(encode_key(&key, modes).unwrap(), "\x1b[1;2H");
+  }
+
+  #[test]
+  fn ctrl_bracket_csi_u() {
+    let key = Key::new(KeyCode::Char('['), KeyModifiers::CONTROL);
+    let csi_u = KeyCodeEncodeModes {
+      enable_csi_u_key_encoding: true,
+      ..Default::default()
+    };
+    assert_eq!(encode_key(&key, csi_u).unwrap(), "\x1b[91;5u");
+    assert_eq!(encode_key(&key, Default::default()).unwrap(), "\x1b");
+  }
+}
@@ -2,17 +2,21 @@ use std::fmt::Display;
 
 use serde::{Deserialize, Serialize};
 
-use crate::{app::ClientId, key::Key};
+use crate::{app::ClientId, key::Key, proc::signal::Signal};
 
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 #[serde(tag = "c", rename_all = "kebab-case")]
 pub enum AppEvent {
-  Batch { cmds: Vec<AppEvent> },
+  Batch {
+    cmds: Vec<AppEvent>,
+  },
 
   QuitOrAsk,
   Quit,
   ForceQuit,
-  Detach { client_id: ClientId },
+  Detach {
+    client_id: ClientId,
+  },
 
   ToggleFocus,
   FocusProcs,
@@ -22,35 +26,122 @@ pub enum AppEvent {
   ShowCommandsMenu,
   NextProc,
   PrevProc,
-  SelectProc { index: usize },
+  NextRunningProc,
+  PrevRunningProc,
+  SortProcs {
+    by: ProcSort,
+    #[serde(default)]
+    desc: bool,
+  },
+  CycleProcSort,
+  ReverseProcSort,
+  SelectProc {
+    index: usize,
+  },
   StartProc,
+  StartProcWithArgs {
+    args: String,
+  },
+  RetryFailedProcs,
   TermProc,
+  TermProcNoConfirm,
   KillProc,
+  KillProcNoConfirm,
   RestartProc,
-  RenameProc { name: String },
+  RestartProcNoConfirm,
+  RenameProc {
+    name: String,
+  },
+  EditProcCmd {
+    cmd: String,
+  },
   ForceRestartProc,
+  ForceRestartProcNoConfirm,
   ShowAddProc,
   ShowRenameProc,
-  AddProc { cmd: String },
+  ShowEditProcCmd,
+  AddProc {
+    cmd: String,
+  },
   DuplicateProc,
+  ToggleAutostart,
+  ToggleAutorestart,
   ShowRemoveProc,
-  RemoveProc { id: usize },
+  RemoveProc {
+    id: usize,
+  },
 
   CloseCurrentModal,
 
-  ScrollDownLines { n: usize },
-  ScrollUpLines { n: usize },
+  ScrollDownLines {
+    n: usize,
+  },
+  ScrollUpLines {
+    n: usize,
+  },
   ScrollDown,
   ScrollUp,
+  SetScrollback {
+    n: usize,
+  },
+  ResetDecodeErrors,
+  ToggleWrap,
+  ToggleDedupLines,
+  ScrollLeft,
+  ScrollRight,
+  ScrollToPrompt {
+    dir: PromptDir,
+  },
+  AddMarker {
+    label: String,
+  },
+  ScrollToMarker {
+    dir: PromptDir,
+  },
+  ClearMarkers,
 
   CopyModeEnter,
+  ToggleAltView,
   CopyModeLeave,
-  CopyModeMove { dir: CopyMove },
+  CopyModeMove {
+    dir: CopyMove,
+  },
   CopyModeEnd,
   CopyModeCopy,
+  ShowCopyModeSearch {
+    #[serde(default)]
+    backward: bool,
+  },
+  CopyModeSearch {
+    query: String,
+    #[serde(default)]
+    backward: bool,
+  },
+  CopyModeSearchNext,
+  CopyModeSearchPrev,
+  CopyLastCommandOutput,
+  CopyProcInfo,
   ToggleKeymapWindow,
+  ToggleMouseCapture,
+  ToggleInterleave,
+  GrowSidebar,
+  ShrinkSidebar,
 
-  SendKey { key: Key },
+  SaveSession {
+    path: String,
+  },
+
+  SendKey {
+    key: Key,
+  },
+  SendLiteralNext,
+  SendSignal {
+    signal: Signal,
+  },
+  RunCommand {
+    cmd: String,
+  },
+  Paste,
 }
 
 impl AppEvent {
@@ -70,17 +161,43 @@ impl AppEvent {
       AppEvent::ShowCommandsMenu => "Show commands menu".to_string(),
       AppEvent::NextProc => "Next".to_string(),
       AppEvent::PrevProc => "Prev".to_string(),
+      AppEvent::NextRunningProc => "Next running".to_string(),
+      AppEvent::PrevRunningProc => "Prev running".to_string(),
+      AppEvent::SortProcs { by, desc } => match desc {
+        false => format!("Sort by {}", by),
+        true => format!("Sort by {} (descending)", by),
+      },
+      AppEvent::CycleProcSort => "Cycle sorting".to_string(),
+      AppEvent::ReverseProcSort => "Reverse sorting".to_string(),
       AppEvent::SelectProc { index } => format!("Select process #{}", index),
       AppEvent::StartProc => "Start".to_string(),
+      AppEvent::StartProcWithArgs { args } => {
+        format!("Start with arguments `{}`", args)
+      }
+      AppEvent::RetryFailedProcs => {
+        "Start processes that failed to start".to_string()
+      }
       AppEvent::TermProc => "Stop".to_string(),
+      AppEvent::TermProcNoConfirm => "Stop without confirmation".to_string(),
       AppEvent::KillProc => "Kill".to_string(),
+      AppEvent::KillProcNoConfirm => "Kill without confirmation".to_string(),
       AppEvent::RestartProc => "Restart".to_string(),
+      AppEvent::RestartProcNoConfirm => {
+        "Restart without confirmation".to_string()
+      }
       AppEvent::RenameProc { name } => format!("Rename to \"{}\"", name),
+      AppEvent::EditProcCmd { cmd } => format!("Change command to `{}`", cmd),
       AppEvent::ForceRestartProc => "Force restart".to_string(),
+      AppEvent::ForceRestartProcNoConfirm => {
+        "Force restart without confirmation".to_string()
+      }
       AppEvent::ShowAddProc => "New process dialog".to_string(),
       AppEvent::ShowRenameProc => "Rename process dialog".to_string(),
+      AppEvent::ShowEditProcCmd => "Edit command dialog".to_string(),
       AppEvent::AddProc { cmd } => format!("New process `{}`", cmd),
       AppEvent::DuplicateProc => "Duplicate current process".to_string(),
+      AppEvent::ToggleAutostart => "Toggle autostart".to_string(),
+      AppEvent::ToggleAutorestart => "Toggle autorestart".to_string(),
       AppEvent::ShowRemoveProc => "Remove process dialog".to_string(),
       AppEvent::RemoveProc { id } => format!("Remove process by id {}", id),
       AppEvent::CloseCurrentModal => "Close current modal".to_string(),
@@ -92,19 +209,209 @@ impl AppEvent {
       }
       AppEvent::ScrollDown => "Scroll down".to_string(),
       AppEvent::ScrollUp => "Scroll up".to_string(),
+      AppEvent::SetScrollback { n } => {
+        format!("Set scrollback to {} {}", n, lines_str(*n))
+      }
+      AppEvent::ResetDecodeErrors => "Reset decode errors marker".to_string(),
+      AppEvent::ToggleWrap => "Toggle line wrapping".to_string(),
+      AppEvent::ToggleDedupLines => {
+        "Toggle collapsing repeated lines".to_string()
+      }
+      AppEvent::ScrollLeft => "Scroll left".to_string(),
+      AppEvent::ScrollRight => "Scroll right".to_string(),
+      AppEvent::ScrollToPrompt { dir } => match dir {
+        PromptDir::Prev => "Scroll to previous prompt".to_string(),
+        PromptDir::Next => "Scroll to next prompt".to_string(),
+      },
+      AppEvent::AddMarker { label } => format!("Add marker \"{}\"", label),
+      AppEvent::ScrollToMarker { dir } => match dir {
+        PromptDir::Prev => "Scroll to previous marker".to_string(),
+        PromptDir::Next => "Scroll to next marker".to_string(),
+      },
+      AppEvent::ClearMarkers => "Clear markers".to_string(),
       AppEvent::CopyModeEnter => "Enter copy mode".to_string(),
+      AppEvent::ToggleAltView => "Toggle view of the normal screen".to_string(),
       AppEvent::CopyModeLeave => "Leave copy mode".to_string(),
       AppEvent::CopyModeMove { dir } => {
         format!("Move selection cursor {}", dir)
       }
       AppEvent::CopyModeEnd => "Select end position".to_string(),
       AppEvent::CopyModeCopy => "Copy selected text".to_string(),
+      AppEvent::ShowCopyModeSearch { backward } => match backward {
+        false => "Search".to_string(),
+        true => "Search backward".to_string(),
+      },
+      AppEvent::CopyModeSearch { query, backward } => match backward {
+        false => format!("Search \"{}\"", query),
+        true => format!("Search \"{}\" backward", query),
+      },
+      AppEvent::CopyModeSearchNext => "Next match".to_string(),
+      AppEvent::CopyModeSearchPrev => "Previous match".to_string(),
+      AppEvent::CopyLastCommandOutput => {
+        "Copy output of the last command".to_string()
+      }
+      AppEvent::CopyProcInfo => "Copy process pid and command".to_string(),
       AppEvent::ToggleKeymapWindow => "Toggle help".to_string(),
+      AppEvent::ToggleMouseCapture => "Toggle mouse capture".to_string(),
+      AppEvent::ToggleInterleave => "Toggle interleaved output".to_string(),
+      AppEvent::GrowSidebar => "Widen process list".to_string(),
+      AppEvent::ShrinkSidebar => "Narrow process list".to_string(),
+      AppEvent::SaveSession { path } => {
+        format!("Save session to \"{}\"", path)
+      }
       AppEvent::SendKey { key } => format!("Send {} key", key.to_string()),
+      AppEvent::SendLiteralNext => "Send next key to process".to_string(),
+      AppEvent::SendSignal { signal } => format!("Send {}", signal),
+      AppEvent::RunCommand { cmd } => format!("Run \"{}\"", cmd),
+      AppEvent::Paste => "Paste from clipboard".to_string(),
     }
   }
 }
 
+/// Commands listed in the commands menu together with their names.
+/// Commands with arguments are listed once per useful argument value.
+pub fn menu_commands() -> Vec<(&'static str, AppEvent)> {
+  vec![
+    ("quit-or-ask", AppEvent::QuitOrAsk),
+    ("quit", AppEvent::Quit),
+    ("force-quit", AppEvent::ForceQuit),
+    ("toggle-focus", AppEvent::ToggleFocus),
+    ("focus-procs", AppEvent::FocusProcs),
+    ("focus-term", AppEvent::FocusTerm),
+    ("zoom", AppEvent::Zoom),
+    ("show-commands-menu", AppEvent::ShowCommandsMenu),
+    ("next-proc", AppEvent::NextProc),
+    ("prev-proc", AppEvent::PrevProc),
+    ("next-running-proc", AppEvent::NextRunningProc),
+    ("prev-running-proc", AppEvent::PrevRunningProc),
+    (
+      "sort-procs",
+      AppEvent::SortProcs {
+        by: ProcSort::Name,
+        desc: false,
+      },
+    ),
+    (
+      "sort-procs",
+      AppEvent::SortProcs {
+        by: ProcSort::Status,
+        desc: false,
+      },
+    ),
+    (
+      "sort-procs",
+      AppEvent::SortProcs {
+        by: ProcSort::Uptime,
+        desc: true,
+      },
+    ),
+    (
+      "sort-procs",
+      AppEvent::SortProcs {
+        by: ProcSort::Restarts,
+        desc: true,
+      },
+    ),
+    ("cycle-proc-sort", AppEvent::CycleProcSort),
+    ("reverse-proc-sort", AppEvent::ReverseProcSort),
+    ("start-proc", AppEvent::StartProc),
+    ("retry-failed-procs", AppEvent::RetryFailedProcs),
+    ("term-proc", AppEvent::TermProc),
+    ("kill-proc", AppEvent::KillProc),
+    ("restart-proc", AppEvent::RestartProc),
+    ("restart-proc-no-confirm", AppEvent::RestartProcNoConfirm),
+    ("duplicate-proc", AppEvent::DuplicateProc),
+    ("toggle-autostart", AppEvent::ToggleAutostart),
+    ("toggle-autorestart", AppEvent::ToggleAutorestart),
+    ("force-restart-proc", AppEvent::ForceRestartProc),
+    ("kill-proc-no-confirm", AppEvent::KillProcNoConfirm),
+    ("term-proc-no-confirm", AppEvent::TermProcNoConfirm),
+    (
+      "force-restart-proc-no-confirm",
+      AppEvent::ForceRestartProcNoConfirm,
+    ),
+    ("show-add-proc", AppEvent::ShowAddProc),
+    ("show-rename-proc", AppEvent::ShowRenameProc),
+    ("show-edit-proc-cmd", AppEvent::ShowEditProcCmd),
+    ("show-remove-proc", AppEvent::ShowRemoveProc),
+    ("close-current-modal", AppEvent::CloseCurrentModal),
+    ("scroll-down", AppEvent::ScrollDown),
+    ("scroll-up", AppEvent::ScrollUp),
+    ("toggle-wrap", AppEvent::ToggleWrap),
+    ("toggle-dedup-lines", AppEvent::ToggleDedupLines),
+    ("scroll-left", AppEvent::ScrollLeft),
+    ("scroll-right", AppEvent::ScrollRight),
+    (
+      "scroll-to-prompt",
+      AppEvent::ScrollToPrompt {
+        dir: PromptDir::Prev,
+      },
+    ),
+    (
+      "scroll-to-prompt",
+      AppEvent::ScrollToPrompt {
+        dir: PromptDir::Next,
+      },
+    ),
+    (
+      "scroll-to-marker",
+      AppEvent::ScrollToMarker {
+        dir: PromptDir::Prev,
+      },
+    ),
+    (
+      "scroll-to-marker",
+      AppEvent::ScrollToMarker {
+        dir: PromptDir::Next,
+      },
+    ),
+    ("clear-markers", AppEvent::ClearMarkers),
+    ("copy-mode-enter", AppEvent::CopyModeEnter),
+    ("toggle-alt-view", AppEvent::ToggleAltView),
+    ("copy-mode-leave", AppEvent::CopyModeLeave),
+    (
+      "copy-mode-move",
+      AppEvent::CopyModeMove { dir: CopyMove::Up },
+    ),
+    (
+      "copy-mode-move",
+      AppEvent::CopyModeMove {
+        dir: CopyMove::Down,
+      },
+    ),
+    (
+      "copy-mode-move",
+      AppEvent::CopyModeMove {
+        dir: CopyMove::Left,
+      },
+    ),
+    (
+      "copy-mode-move",
+      AppEvent::CopyModeMove {
+        dir: CopyMove::Right,
+      },
+    ),
+    ("copy-mode-end", AppEvent::CopyModeEnd),
+    ("copy-mode-copy", AppEvent::CopyModeCopy),
+    (
+      "show-copy-mode-search",
+      AppEvent::ShowCopyModeSearch { backward: false },
+    ),
+    ("copy-mode-search-next", AppEvent::CopyModeSearchNext),
+    ("copy-mode-search-prev", AppEvent::CopyModeSearchPrev),
+    ("copy-last-command-output", AppEvent::CopyLastCommandOutput),
+    ("copy-proc-info", AppEvent::CopyProcInfo),
+    ("toggle-keymap-window", AppEvent::ToggleKeymapWindow),
+    ("toggle-mouse-capture", AppEvent::ToggleMouseCapture),
+    ("toggle-interleave", AppEvent::ToggleInterleave),
+    ("grow-sidebar", AppEvent::GrowSidebar),
+    ("shrink-sidebar", AppEvent::ShrinkSidebar),
+    ("reset-decode-errors", AppEvent::ResetDecodeErrors),
+    ("send-literal-next", AppEvent::SendLiteralNext),
+    ("paste", AppEvent::Paste),
+  ]
+}
+
 fn lines_str(n: usize) -> &'static str {
   if n == 1 {
     "line"
@@ -133,6 +440,47 @@ impl Display for CopyMove {
   }
 }
 
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProcSort {
+  Name,
+  /// Failed first, then running, then stopped.
+  Status,
+  Uptime,
+  Restarts,
+}
+
+impl ProcSort {
+  /// Sorting used by `cycle-proc-sort` after this one.
+  pub fn next(self) -> Self {
+    match self {
+      ProcSort::Name => ProcSort::Status,
+      ProcSort::Status => ProcSort::Uptime,
+      ProcSort::Uptime => ProcSort::Restarts,
+      ProcSort::Restarts => ProcSort::Name,
+    }
+  }
+}
+
+impl Display for ProcSort {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let str = match self {
+      ProcSort::Name => "name",
+      ProcSort::Status => "status",
+      ProcSort::Uptime => "uptime",
+      ProcSort::Restarts => "restarts",
+    };
+    f.write_str(str)
+  }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PromptDir {
+  Prev,
+  Next,
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -152,4 +500,15 @@ mod tests {
       "c: send-key\nkey: <C-a>\n"
     );
   }
+
+  #[test]
+  fn sort_procs_default_order() {
+    assert_eq!(
+      serde_yaml::from_str::<AppEvent>("{c: sort-procs, by: status}").unwrap(),
+      AppEvent::SortProcs {
+        by: ProcSort::Status,
+        desc: false,
+      }
+    );
+  }
 }
@@ -0,0 +1,231 @@
+use std::io::{IsTerminal, Write};
+
+use anyhow::{bail, Result};
+use crossterm::style::{ResetColor, SetForegroundColor};
+use futures::{select, FutureExt};
+use tui::{layout::Rect, style::Color};
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+  config::{Config, ProcConfig},
+  deps::{procs_to_start, unknown_deps_warning},
+  proc::{
+    create_proc,
+    handle::ProcHandle,
+    msg::{ProcCmd, ProcEvent},
+    StopSignal,
+  },
+  theme::Theme,
+  watch::WatchAction,
+};
+
+/// Size of the pty of processes. Programs usually don't wrap lines, so it
+/// only matters for full-screen programs.
+const TERM_WIDTH: u16 = 160;
+const TERM_HEIGHT: u16 = 50;
+
+/// Exit code when mprocs was interrupted with Ctrl-C.
+const INTERRUPTED_CODE: i32 = 130;
+
+/// Runs processes with autostart without the TUI and prints their output with
+/// the process name in front of each line. Returns once all processes have
+/// exited. The exit code is the highest exit code of failed processes.
+///
+/// Processes with `ready_when` print `READY <name>` once the pattern matches.
+/// Processes named in `wait_ready` are started first, the rest only after all
+/// of them are ready. Processes with `deps` are started once their deps are
+/// ready.
+pub async fn run_headless(
+  config: Config,
+  wait_ready: Vec<String>,
+) -> Result<i32> {
+  for name in &wait_ready {
+    let found = config
+      .procs
+      .iter()
+      .any(|cfg| cfg.autostart && &cfg.name == name && has_ready_pattern(cfg));
+    if !found {
+      bail!(
+        "--wait-ready: no process \"{}\" with autostart and ready_when.",
+        name
+      );
+    }
+  }
+
+  for warning in &config.warnings {
+    eprintln!("{}.", warning);
+  }
+  if let Some(warning) = unknown_deps_warning(&config.procs) {
+    eprintln!("{}.", warning);
+  }
+
+  let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+  let size = Rect::new(0, 0, TERM_WIDTH, TERM_HEIGHT);
+
+  let mut procs = config
+    .procs
+    .iter()
+    .filter(|cfg| cfg.autostart)
+    .map(|cfg| {
+      let mut cfg = ProcConfig {
+        autostart: false,
+        lazy_parse: false,
+        raw_passthrough: false,
+        ..cfg.clone()
+      };
+      // Ctrl-C is forwarded as is.
+      if cfg!(unix) {
+        cfg.stop = StopSignal::SIGINT;
+      }
+      let mut proc = create_proc(
+        cfg.name.clone(),
+        &cfg,
+        config.read_options,
+        tx.clone(),
+        size,
+      );
+      proc.set_line_output(true);
+      if !cfg.deps.is_empty() {
+        proc.wait_for(cfg.deps.clone());
+      } else if wait_ready.is_empty() || wait_ready.contains(&cfg.name) {
+        proc.send(ProcCmd::Start);
+      }
+      proc
+    })
+    .collect::<Vec<_>>();
+  drop(tx);
+
+  // Procs from `wait_ready` that are not ready yet, and the procs started
+  // once all of them are ready.
+  let mut not_ready = Vec::new();
+  let mut pending = Vec::new();
+  if !wait_ready.is_empty() {
+    for proc in &procs {
+      if wait_ready.iter().any(|name| name == proc.name()) {
+        not_ready.push(proc.id());
+      } else {
+        pending.push(proc.id());
+      }
+    }
+  }
+
+  let name_width = procs.iter().map(|p| p.name().width()).max().unwrap_or(0);
+  // Don't write escape sequences into files and pipes.
+  let colors = std::io::stdout().is_terminal();
+  let theme = Theme::default();
+  let mut interrupted = false;
+  let ctrl_c = tokio::signal::ctrl_c().fuse();
+  tokio::pin!(ctrl_c);
+
+  while !procs.iter().all(|p| p.is_finished()) {
+    select! {
+      event = rx.recv().fuse() => {
+        let (id, event) = match event {
+          Some(event) => event,
+          None => break,
+        };
+        if let Some(proc) = procs.iter_mut().find(|p| p.id() == id) {
+          let color = colors.then(|| {
+            proc.color().unwrap_or_else(|| theme.proc_color(proc.id()))
+          });
+          let ready = matches!(event, ProcEvent::Watch(WatchAction::Ready, _));
+          let started = matches!(event, ProcEvent::Started);
+          handle_event(proc, event, name_width, color);
+          if ready && !interrupted {
+            not_ready.retain(|ready_id| *ready_id != id);
+            if not_ready.is_empty() {
+              for id in pending.drain(..) {
+                if let Some(proc) = procs.iter_mut().find(|p| p.id() == id) {
+                  if proc.waiting_for().is_empty() {
+                    proc.send(ProcCmd::Start);
+                  }
+                }
+              }
+            }
+          }
+          if (ready || started) && !interrupted {
+            // Pending procs are started once `wait_ready` procs are ready.
+            for id in procs_to_start(&mut procs) {
+              if pending.contains(&id) {
+                continue;
+              }
+              if let Some(proc) = procs.iter_mut().find(|p| p.id() == id) {
+                proc.send(ProcCmd::Start);
+              }
+            }
+          }
+        }
+      }
+      _ = &mut ctrl_c => {
+        // Ask nicely first, kill on the second Ctrl-C.
+        for proc in &mut procs {
+          proc.send(if interrupted { ProcCmd::Kill } else { ProcCmd::Stop });
+        }
+        interrupted = true;
+        ctrl_c.set(tokio::signal::ctrl_c().fuse());
+      }
+    }
+  }
+
+  if interrupted {
+    return Ok(INTERRUPTED_CODE);
+  }
+  if !pending.is_empty() {
+    bail!("Processes from --wait-ready exited before they were ready.");
+  }
+  let waiting = procs
+    .iter()
+    .filter(|p| !p.waiting_for().is_empty())
+    .map(|p| format!("{} ({})", p.name(), p.waiting_for().join(", ")))
+    .collect::<Vec<_>>();
+  if !waiting.is_empty() {
+    bail!(
+      "Processes were not started because their deps were not ready: {}.",
+      waiting.join(", ")
+    );
+  }
+  let code = procs.iter().filter_map(|p| p.failure_code()).max();
+  Ok(code.map_or(0, |code| code.min(255) as i32))
+}
+
+fn handle_event(
+  proc: &mut ProcHandle,
+  event: ProcEvent,
+  name_width: usize,
+  color: Option<Color>,
+) {
+  let print = |line: &str| print_line(proc.name(), line, name_width, color);
+  match &event {
+    ProcEvent::Output(line) => print(line),
+    ProcEvent::Stopped(status) => {
+      print(&format!("exited with code {}", status.code));
+    }
+    ProcEvent::SpawnFailed(err) => print(err),
+    ProcEvent::Watch(WatchAction::Ready, _) => {
+      let _r = writeln!(std::io::stdout().lock(), "READY {}", proc.name());
+    }
+    _ => (),
+  }
+  proc.handle_event(event, false);
+}
+
+fn has_ready_pattern(cfg: &ProcConfig) -> bool {
+  cfg.watch.iter().any(|w| w.action == WatchAction::Ready)
+}
+
+fn print_line(name: &str, line: &str, name_width: usize, color: Option<Color>) {
+  let pad = name_width.saturating_sub(name.width());
+  let prefix = format!("[{}]{}", name, " ".repeat(pad));
+  let mut stdout = std::io::stdout().lock();
+  let _r = match color {
+    Some(color) => writeln!(
+      stdout,
+      "{}{}{} {}",
+      SetForegroundColor(color.into()),
+      prefix,
+      ResetColor,
+      line.trim_end()
+    ),
+    None => writeln!(stdout, "{} {}", prefix, line.trim_end()),
+  };
+}
@@ -0,0 +1,57 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use regex::Regex;
+use serde_yaml::Value;
+use tui::style::{Color, Modifier, Style};
+
+use crate::yaml_val::Val;
+
+/// Style applied on top of terminal rows matching a pattern. Only changes how
+/// the output is displayed, the parsed screen is not modified.
+#[derive(Clone, Debug)]
+pub struct Highlight {
+  pub regex: Regex,
+  pub style: Style,
+}
+
+impl Highlight {
+  pub fn from_val(val: &Val) -> Result<Self> {
+    let map = val.as_object()?;
+
+    let regex = match map.get(&Value::from("pattern")) {
+      Some(pattern) => Regex::new(pattern.as_str()?)
+        .map_err(|err| pattern.error_at(err.to_string()))?,
+      None => bail!(val.error_at("Expected \"pattern\" field")),
+    };
+
+    let mut style = Style::default();
+    if let Some(fg) = map.get(&Value::from("fg")) {
+      style = style.fg(parse_color(fg)?);
+    }
+    if let Some(bg) = map.get(&Value::from("bg")) {
+      style = style.bg(parse_color(bg)?);
+    }
+    if let Some(bold) = map.get(&Value::from("bold")) {
+      if bold.as_bool()? {
+        style = style.add_modifier(Modifier::BOLD);
+      }
+    }
+
+    Ok(Highlight { regex, style })
+  }
+}
+
+pub fn parse_color(val: &Val) -> Result<Color> {
+  Color::from_str(val.as_str()?).map_err(|_| val.error_at("Unknown color"))
+}
+
+/// Combined style of all rules matching the text. Later rules win.
+pub fn highlight_style(highlights: &[Highlight], text: &str) -> Option<Style> {
+  highlights
+    .iter()
+    .filter(|h| h.regex.is_match(text))
+    .fold(None, |acc: Option<Style>, h| {
+      Some(acc.unwrap_or_default().patch(h.style))
+    })
+}
@@ -0,0 +1,163 @@
+use anyhow::{bail, Result};
+use indexmap::IndexMap;
+use serde::Deserialize;
+use serde_yaml::Value;
+
+use crate::{
+  config::{CmdConfig, ProcConfig},
+  settings::Settings,
+};
+
+#[derive(Deserialize)]
+struct Dump {
+  recipes: IndexMap<String, Recipe>,
+}
+
+#[derive(Deserialize)]
+struct Recipe {
+  #[serde(default)]
+  private: bool,
+  #[serde(default)]
+  parameters: Vec<Parameter>,
+}
+
+#[derive(Deserialize)]
+struct Parameter {
+  name: String,
+  #[serde(default)]
+  default: Option<Value>,
+  #[serde(default)]
+  kind: String,
+}
+
+impl Parameter {
+  /// Parameter as it is written in a justfile.
+  fn signature(&self) -> String {
+    let prefix = match self.kind.as_str() {
+      "plus" => "+",
+      "star" => "*",
+      _ => "",
+    };
+    match &self.default {
+      None => format!("{}{}", prefix, self.name),
+      Some(Value::String(default)) => {
+        format!("{}{}='{}'", prefix, self.name, default)
+      }
+      // Default is an expression.
+      Some(_) => format!("{}{}=…", prefix, self.name),
+    }
+  }
+}
+
+pub fn load_just_procs(settings: &Settings) -> Result<Vec<ProcConfig>> {
+  let output = std::process::Command::new("just")
+    .args(["--dump", "--dump-format", "json"])
+    .output()
+    .map_err(|e| anyhow::Error::msg(format!("Failed to run just: {}", e)))?;
+  if !output.status.success() {
+    bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+  }
+  let dump: Dump = serde_yaml::from_slice(&output.stdout)?;
+
+  let procs = dump
+    .recipes
+    .into_iter()
+    .filter(|(name, recipe)| !recipe.private && !name.starts_with('_'))
+    .map(|(name, recipe)| {
+      let cmd = CmdConfig::Cmd {
+        cmd: vec!["just".to_string(), name.clone()],
+      };
+      ProcConfig {
+        autostart: false,
+        params: recipe.parameters.iter().map(Parameter::signature).collect(),
+        ..ProcConfig::from_settings(name, cmd, settings)
+      }
+    });
+  Ok(procs.collect())
+}
+
+/// Splits arguments like a shell does: by whitespace, except inside single
+/// or double quotes. A backslash escapes the next character outside of
+/// single quotes.
+pub fn split_args(text: &str) -> Vec<String> {
+  let mut args = Vec::new();
+  let mut arg = String::new();
+  let mut in_arg = false;
+  let mut quote = None;
+  let mut chars = text.chars();
+
+  while let Some(c) = chars.next() {
+    match (quote, c) {
+      (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+      (Some('\''), c) => arg.push(c),
+      (_, '\\') => {
+        in_arg = true;
+        if let Some(c) = chars.next() {
+          arg.push(c);
+        }
+      }
+      (Some(_), c) => arg.push(c),
+      (None, '\'' | '"') => {
+        in_arg = true;
+        quote = Some(c);
+      }
+      (None, c) if c.is_whitespace() => {
+        if in_arg {
+          args.push(std::mem::take(&mut arg));
+          in_arg = false;
+        }
+      }
+      (None, c) => {
+        in_arg = true;
+        arg.push(c);
+      }
+    }
+  }
+  if in_arg {
+    args.push(arg);
+  }
+
+  args
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_dump() {
+    let json = r#"{
+      "recipes": {
+        "build": {
+          "private": false,
+          "parameters": [
+            {"name": "target", "default": null, "kind": "singular"},
+            {"name": "mode", "default": "debug", "kind": "singular"},
+            {"name": "flags", "default": null, "kind": "star"}
+          ]
+        },
+        "_helper": {"private": false, "parameters": []}
+      }
+    }"#;
+    let dump: Dump = serde_yaml::from_str(json).unwrap();
+    let build = &dump.recipes["build"];
+    let params = build
+      .parameters
+      .iter()
+      .map(Parameter::signature)
+      .collect::<Vec<_>>();
+    assert_eq!(params, vec!["target", "mode='debug'", "*flags"]);
+  }
+
+  #[test]
+  fn split_quoted_args() {
+    assert_eq!(split_args("  a  b "), vec!["a", "b"]);
+    assert_eq!(split_args("'a b' \"c d\""), vec!["a b", "c d"]);
+    assert_eq!(
+      split_args(r#"a\ b "c\"d" 'e\f'"#),
+      vec!["a b", "c\"d", "e\\f"]
+    );
+    assert_eq!(split_args("'' x"), vec!["", "x"]);
+    assert_eq!(split_args("'unclosed arg"), vec!["unclosed arg"]);
+  }
+}
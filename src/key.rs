@@ -51,6 +51,14 @@ pub struct Key {
 
 impl Key {
   pub fn new(code: KeyCode, mods: KeyModifiers) -> Key {
+    // Terminals report Shift+Tab as BackTab, with or without Shift.
+    let (code, mods) = match code {
+      KeyCode::BackTab => (code, mods - KeyModifiers::SHIFT),
+      KeyCode::Tab if mods.contains(KeyModifiers::SHIFT) => {
+        (KeyCode::BackTab, mods - KeyModifiers::SHIFT)
+      }
+      _ => (code, mods),
+    };
     Self { code, mods }
   }
 
@@ -250,6 +258,14 @@ mod tests {
       Key::parse("<Tab>").unwrap(),
       Key::new(KeyCode::Tab, KeyModifiers::NONE)
     );
+    assert_eq!(
+      Key::parse("<S-Tab>").unwrap(),
+      Key::new(KeyCode::BackTab, KeyModifiers::SHIFT)
+    );
+    assert_eq!(
+      Key::parse("<S-Tab>").unwrap().to_string(),
+      "<S-Tab>".to_string()
+    );
     assert_eq!(
       Key::parse("<C-Enter>").unwrap(),
       Key::new(KeyCode::Enter, KeyModifiers::CONTROL)
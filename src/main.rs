@@ -1,28 +1,43 @@
 mod app;
 mod client;
 mod clipboard;
+mod compose;
 mod config;
 mod config_lua;
 mod ctl;
+mod dedup;
+mod deps;
 mod encode_term;
 mod error;
 mod event;
+mod headless;
+mod highlight;
 mod host;
+mod just;
 mod kernel;
 mod key;
 mod keymap;
+mod make;
 mod modal;
 mod mouse;
 mod package_json;
+mod placeholder;
 mod proc;
+mod proc_format;
 mod protocol;
+mod run_cmd;
+mod session;
 mod settings;
 mod state;
+#[cfg(test)]
+mod test_util;
 mod theme;
+mod title_format;
 mod ui_keymap;
 mod ui_procs;
 mod ui_term;
 mod ui_zoom_tip;
+mod watch;
 mod widgets;
 mod yaml_val;
 
@@ -30,17 +45,23 @@ use std::{io::Read, path::Path};
 
 use anyhow::{bail, Result};
 use app::{start_kernel_process, start_kernel_thread};
-use clap::{arg, command, ArgMatches, Command};
+use clap::{arg, command, ArgAction, ArgMatches, Command};
 use client::client_main;
-use config::{CmdConfig, Config, ConfigContext, ProcConfig, ServerConfig};
+use compose::load_compose_procs;
+use config::{
+  check_config, CmdConfig, Config, ConfigContext, ProcConfig, ServerConfig,
+};
 use config_lua::load_lua_config;
 use ctl::run_ctl;
 use flexi_logger::{FileSpec, LoggerHandle};
+use headless::run_headless;
 use host::{receiver::MsgReceiver, sender::MsgSender};
+use just::load_just_procs;
 use keymap::Keymap;
-use package_json::load_npm_procs;
-use proc::StopSignal;
+use make::load_make_procs;
+use package_json::{load_npm_procs, PackageManager};
 use serde_yaml::Value;
+use session::Session;
 use settings::Settings;
 use yaml_val::Val;
 
@@ -74,7 +95,8 @@ fn setup_logger(target: LogTarget) -> LoggerHandle {
 #[tokio::main]
 async fn main() -> Result<(), std::io::Error> {
   match run_app().await {
-    Ok(()) => Ok(()),
+    Ok(0) => Ok(()),
+    Ok(code) => std::process::exit(code),
     Err(err) => {
       eprintln!("Error: {:?}", err);
       Ok(())
@@ -82,18 +104,36 @@ async fn main() -> Result<(), std::io::Error> {
   }
 }
 
-async fn run_app() -> anyhow::Result<()> {
+/// Returns the exit code for mprocs.
+async fn run_app() -> anyhow::Result<i32> {
   let matches = command!()
     .arg(arg!(-c --config [PATH] "Config path [default: mprocs.yaml]"))
     .arg(arg!(-s --server [PATH] "Remote control server address. Example: 127.0.0.1:4050."))
     .arg(arg!(--ctl [YAML] "Send yaml/json encoded command to running mprocs"))
     .arg(arg!(--names [NAMES] "Names for processes provided by cli arguments. Separated by comma."))
     .arg(arg!(--npm "Run scripts from package.json. Scripts are not started by default."))
+    .arg(arg!(--"npm-root-only" "With --npm, skip scripts of workspace packages."))
+    .arg(arg!(--"npm-runner" [RUNNER] "With --npm, run scripts with npm, yarn, pnpm or bun [default: detected by lockfile]"))
+    .arg(arg!(--just "Run recipes from justfile. Recipes are not started by default."))
+    .arg(arg!(--make "Run targets from Makefile. Targets are not started by default."))
+    .arg(arg!(--"make-prefix" [PREFIX] "With --make, prefix for process names"))
+    .arg(arg!(--compose "Show services from docker compose file. Services are not started by default."))
+    .arg(arg!(--start [NAMES] "Start these processes even if they have autostart disabled. Separated by comma."))
+    .arg(arg!(--restore [PATH] "Restore session saved with the save-session command"))
+    .arg(arg!(--interleave "Show output of all processes in one pane, prefixed with process names."))
+    .arg(arg!(--headless "Run processes without the UI and print their output. Exits when all processes exit."))
+    .arg(arg!(--"wait-ready" [NAME] "With --headless, start other processes once this process is ready (see ready_when). Can be repeated.").num_args(1).action(ArgAction::Append))
+    .arg(arg!(--check "Validate the config, print all problems and exit."))
+    .arg(arg!(--env [VAR] "Set environment variable for all processes. Example: --env DEBUG=1. Can be repeated.").num_args(1).action(ArgAction::Append).value_parser(parse_env_var))
     .arg(arg!([COMMANDS]... "Commands to run (if omitted, commands from config will be run)"))
     // .subcommand(Command::new("server"))
     // .subcommand(Command::new("attach"))
     .get_matches();
 
+  if matches.get_flag("check") {
+    return Ok(run_check(&matches));
+  }
+
   let config_value = load_config_value(&matches)
     .map_err(|e| anyhow::Error::msg(format!("[{}] {}", "config", e)))?;
 
@@ -104,9 +144,10 @@ async fn run_app() -> anyhow::Result<()> {
     anyhow::Error::msg(format!("[{}] {}", "global settings", e))
   })?;
   // merge ./mprocs.yaml
-  if let Some((value, _)) = &config_value {
+  if let Some((value, ctx)) = &config_value {
     settings
       .merge_value(Val::new(value)?)
+      .map_err(|e| ctx.locate_error(e))
       .map_err(|e| anyhow::Error::msg(format!("[{}] {}", "local config", e)))?;
   }
 
@@ -115,7 +156,8 @@ async fn run_app() -> anyhow::Result<()> {
 
   let config = {
     let mut config = if let Some((v, ctx)) = config_value {
-      Config::from_value(&v, &ctx, &settings)?
+      Config::from_value(&v, &ctx, &settings)
+        .map_err(|e| ctx.locate_error(e))?
     } else {
       Config::make_default(&settings)
     };
@@ -124,8 +166,10 @@ async fn run_app() -> anyhow::Result<()> {
       config.server = Some(ServerConfig::from_str(server_addr)?);
     }
 
+    config.interleave = matches.get_flag("interleave");
+
     if let Some(ctl_arg) = matches.get_one::<String>("ctl") {
-      return run_ctl(ctl_arg, &config).await;
+      return run_ctl(ctl_arg, &config).await.map(|()| 0);
     }
 
     if let Some(cmds) = matches.get_many::<String>("COMMANDS") {
@@ -135,29 +179,73 @@ async fn run_app() -> anyhow::Result<()> {
       let procs = cmds
         .into_iter()
         .enumerate()
-        .map(|(i, cmd)| ProcConfig {
-          name: names
+        .map(|(i, cmd)| {
+          let name = names
             .get(i)
-            .map_or_else(|| cmd.to_string(), |s| s.to_string()),
-          cmd: CmdConfig::Shell {
-            shell: cmd.to_string(),
-          },
-          env: None,
-          cwd: None,
-          autostart: true,
-          autorestart: false,
-          stop: StopSignal::default(),
-          mouse_scroll_speed: settings.mouse_scroll_speed,
-          scrollback_len: settings.scrollback_len,
+            .map_or_else(|| cmd.to_string(), |s| s.to_string());
+          ProcConfig::from_settings(
+            name,
+            CmdConfig::Shell {
+              shell: cmd.to_string(),
+            },
+            &settings,
+          )
         })
         .collect::<Vec<_>>();
 
       config.procs = procs;
     } else if matches.get_flag("npm") {
-      let procs = load_npm_procs(&settings)?;
+      let root_only = matches.get_flag("npm-root-only");
+      let manager = matches
+        .get_one::<String>("npm-runner")
+        .map(|runner| PackageManager::from_name(runner))
+        .transpose()?;
+      let procs = load_npm_procs(&settings, root_only, manager)?;
+      config.procs = procs;
+    } else if matches.get_flag("just") {
+      let procs = load_just_procs(&settings)
+        .map_err(|e| anyhow::Error::msg(format!("[{}] {}", "just", e)))?;
+      config.procs = procs;
+    } else if matches.get_flag("make") {
+      let prefix = matches
+        .get_one::<String>("make-prefix")
+        .map_or("", |s| s.as_str());
+      let procs = load_make_procs(&settings, prefix)
+        .map_err(|e| anyhow::Error::msg(format!("[{}] {}", "make", e)))?;
+      config.procs = procs;
+    } else if matches.get_flag("compose") {
+      let procs = load_compose_procs(&settings)
+        .map_err(|e| anyhow::Error::msg(format!("[{}] {}", "compose", e)))?;
       config.procs = procs;
     }
 
+    if let Some(path) = matches.get_one::<String>("restore") {
+      let session = Session::load(Path::new(path))
+        .map_err(|e| anyhow::Error::msg(format!("[{}] {}", "session", e)))?;
+      session.apply(&mut config, &settings);
+    }
+
+    if let Some(names) = matches.get_one::<String>("start") {
+      for name in names.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        match config.procs.iter_mut().find(|proc| proc.name == name) {
+          Some(proc) => proc.autostart = true,
+          None => bail!("--start: no process \"{}\".", name),
+        }
+      }
+    }
+
+    // Variables from the command line take precedence over the `env` of
+    // processes.
+    if let Some(vars) = matches.get_many::<(String, String)>("env") {
+      let vars = vars.collect::<Vec<_>>();
+      for proc in &mut config.procs {
+        let env = proc.env.get_or_insert_with(Default::default);
+        for (key, val) in &vars {
+          env.insert(key.clone(), Some(val.clone()));
+        }
+      }
+    }
+
     config
   };
 
@@ -170,13 +258,22 @@ async fn run_app() -> anyhow::Result<()> {
     // }
     // Some(("server", _args)) => {
     //   let logger = setup_logger(LogTarget::Stderr);
-    //   let ret = start_kernel_process(config, keymap).await;
+    //   let ret = start_kernel_process(config, settings, keymap).await;
     //   drop(logger);
     //   ret
     // }
     Some((cmd, _args)) => {
       bail!("Unexpected command: {}", cmd);
     }
+    None if matches.get_flag("headless") => {
+      let logger = setup_logger(LogTarget::File);
+      let wait_ready = matches
+        .get_many::<String>("wait-ready")
+        .map_or(Vec::new(), |names| names.cloned().collect());
+      let ret = run_headless(config, wait_ready).await;
+      drop(logger);
+      ret
+    }
     None => {
       let logger = setup_logger(LogTarget::File);
 
@@ -193,64 +290,137 @@ async fn run_app() -> anyhow::Result<()> {
         (sender, receiver)
       };
 
-      start_kernel_thread(
+      let kernel = start_kernel_thread(
         config,
+        settings,
         keymap,
         (srv_to_clt_sender, clt_to_srv_receiver),
       )
       .await?;
 
-      let ret = client_main(clt_to_srv_sender, srv_to_clt_receiver).await;
+      let ret =
+        client_main(clt_to_srv_sender, srv_to_clt_receiver, false).await;
       drop(logger);
-      ret
+      ret?;
+      kernel.await?
+    }
+  }
+}
+
+/// Validates global settings and the config without starting anything.
+/// Prints every problem as `<file>:<line>:<col>: <problem>` (or
+/// `<file>: <problem>` if the position is unknown). Returns the exit code.
+fn run_check(matches: &ArgMatches) -> i32 {
+  fn problem(ctx: &ConfigContext, err: anyhow::Error) -> String {
+    match ctx.error_pos(&err) {
+      Some(pos) => format!(
+        "{}:{}:{}: {}",
+        pos.path.to_string_lossy(),
+        pos.line,
+        pos.col,
+        err
+      ),
+      None => format!("{}: {}", ctx.path.to_string_lossy(), err),
+    }
+  }
+
+  let mut problems = Vec::new();
+
+  let mut settings = Settings::default();
+  if let Err(err) = settings.merge_from_xdg() {
+    match settings.get_xdg_config_path() {
+      Some(path) => {
+        let ctx = ConfigContext {
+          path,
+          includes: Vec::new(),
+        };
+        problems.push(problem(&ctx, err));
+      }
+      None => problems.push(format!("global settings: {}", err)),
+    }
+  }
+
+  let config_path = match load_config_value(matches) {
+    Ok(Some((value, ctx))) => {
+      match Val::new(&value).and_then(|val| settings.merge_value(val)) {
+        Ok(()) => {
+          let mut keymap = Keymap::new();
+          if let Err(err) = settings.add_to_keymap(&mut keymap) {
+            problems.push(problem(&ctx, err));
+          }
+          for err in check_config(&value, &ctx, &settings) {
+            problems.push(problem(&ctx, err));
+          }
+        }
+        Err(err) => problems.push(problem(&ctx, err)),
+      }
+      ctx.path.to_string_lossy().to_string()
     }
+    Ok(None) => "mprocs.yaml".to_string(),
+    Err(err) => {
+      let path = matches
+        .get_one::<String>("config")
+        .map_or("config", |path| path.as_str())
+        .to_string();
+      problems.push(format!("{}: {}", path, err));
+      path
+    }
+  };
+
+  if problems.is_empty() {
+    println!("{}: OK", config_path);
+    return 0;
+  }
+  for problem in problems {
+    println!("{}", problem);
   }
+  1
 }
 
 fn load_config_value(
   matches: &ArgMatches,
 ) -> Result<Option<(Value, ConfigContext)>> {
   if let Some(path) = matches.get_one::<String>("config") {
-    return Ok(Some((
-      read_value(path)?,
-      ConfigContext { path: path.into() },
-    )));
+    return Ok(Some(read_config(path)?));
   }
 
   {
     let path = "mprocs.lua";
     if Path::new(path).is_file() {
-      return Ok(Some((
-        read_value(path)?,
-        ConfigContext { path: path.into() },
-      )));
+      return Ok(Some(read_config(path)?));
     }
   }
 
   {
     let path = "mprocs.yaml";
     if Path::new(path).is_file() {
-      return Ok(Some((
-        read_value(path)?,
-        ConfigContext { path: path.into() },
-      )));
+      return Ok(Some(read_config(path)?));
     }
   }
 
   {
     let path = "mprocs.json";
     if Path::new(path).is_file() {
-      return Ok(Some((
-        read_value(path)?,
-        ConfigContext { path: path.into() },
-      )));
+      return Ok(Some(read_config(path)?));
     }
   }
 
   Ok(None)
 }
 
-fn read_value(path: &str) -> Result<Value> {
+/// Parses a `KEY=VAL` argument of `--env`.
+fn parse_env_var(arg: &str) -> std::result::Result<(String, String), String> {
+  match arg.split_once('=') {
+    Some((key, val))
+      if !key.is_empty() && !key.contains(char::is_whitespace) =>
+    {
+      Ok((key.to_string(), val.to_string()))
+    }
+    _ => Err(format!("expected KEY=VALUE, got '{}'", arg)),
+  }
+}
+
+fn read_config(path: &str) -> Result<(Value, ConfigContext)> {
   // Open the file in read-only mode with buffer.
   let file = match std::fs::File::open(path) {
     Ok(file) => file,
@@ -275,5 +445,13 @@ fn read_value(path: &str) -> Result<Value> {
     _ => bail!("Supported config extensions: lua, yaml, yml, json."),
   };
   value.apply_merge().unwrap();
-  Ok(value)
+  let mut ctx = ConfigContext {
+    path: path.into(),
+    includes: Vec::new(),
+  };
+  if ext != "lua" {
+    value = config::resolve_includes(value, Path::new(path), &mut ctx.includes)
+      .map_err(|e| ctx.locate_error(e))?;
+  }
+  Ok((value, ctx))
 }
@@ -0,0 +1,118 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use regex::Regex;
+
+use crate::{
+  config::{CmdConfig, ProcConfig},
+  settings::Settings,
+};
+
+/// Makefile names in the order make looks for them.
+const MAKEFILES: [&str; 3] = ["GNUmakefile", "makefile", "Makefile"];
+
+pub fn load_make_procs(
+  settings: &Settings,
+  prefix: &str,
+) -> Result<Vec<ProcConfig>> {
+  let path = match MAKEFILES.iter().find(|name| Path::new(name).is_file()) {
+    Some(path) => path,
+    None => bail!("Makefile not found."),
+  };
+  let text = std::fs::read_to_string(path)?;
+
+  let procs = parse_targets(&text).into_iter().map(|target| {
+    let name = format!("{}{}", prefix, target);
+    ProcConfig {
+      autostart: false,
+      ..ProcConfig::from_settings(
+        name,
+        CmdConfig::Cmd {
+          cmd: vec!["make".to_string(), target],
+        },
+        settings,
+      )
+    }
+  });
+  Ok(procs.collect())
+}
+
+/// Explicit targets of a Makefile in the order they are defined. Special
+/// targets (like `.PHONY`), pattern rules and variable assignments are
+/// skipped.
+fn parse_targets(text: &str) -> Vec<String> {
+  let target_re = Regex::new(r"^([a-zA-Z0-9_-]+)\s*:").unwrap();
+
+  let mut targets = Vec::new();
+  let mut in_define = false;
+  let mut continued = false;
+  for line in text.lines() {
+    // Lines joined with a backslash belong to the previous line.
+    let is_continuation = continued;
+    continued = line.ends_with('\\');
+    if is_continuation {
+      continue;
+    }
+
+    let trimmed = line.trim_start();
+    if in_define {
+      if trimmed.starts_with("endef") {
+        in_define = false;
+      }
+      continue;
+    }
+    if trimmed.starts_with("define ") || trimmed == "define" {
+      in_define = true;
+      continue;
+    }
+    // Recipe lines start with a tab.
+    if line.starts_with('\t') {
+      continue;
+    }
+
+    if let Some(m) = target_re.captures(line) {
+      let rest = &line[m.get(0).unwrap().end()..];
+      // `:=` and `::=` are assignments.
+      if rest.starts_with('=') || rest.starts_with(":=") {
+        continue;
+      }
+      let target = m[1].to_string();
+      if !targets.contains(&target) {
+        targets.push(target);
+      }
+    }
+  }
+  targets
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn targets() {
+    let text = "\
+CC := gcc
+FLAGS ::= -O2
+.PHONY: build test
+build: main.o
+\tgcc -o app main.o
+%.o: %.c
+\t$(CC) -c $<
+test:
+\tcargo test \\
+\t  --all
+lint-all :
+define HELP
+not-a-target: x
+endef
+build: extra
+clean::
+\trm -f app
+";
+    assert_eq!(
+      parse_targets(text),
+      vec!["build", "test", "lint-all", "clean"]
+    );
+  }
+}
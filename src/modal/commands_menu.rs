@@ -10,8 +10,13 @@ use tui::{
 use tui_input::Input;
 
 use crate::{
-  app::LoopAction, error::ResultLogger, event::AppEvent, state::State,
-  theme::Theme, widgets::text_input::TextInput,
+  app::LoopAction,
+  error::ResultLogger,
+  event::{menu_commands, AppEvent},
+  keymap::{Keymap, KeymapGroup},
+  state::State,
+  theme::Theme,
+  widgets::text_input::TextInput,
 };
 
 use super::modal::Modal;
@@ -19,19 +24,37 @@ use super::modal::Modal;
 pub struct CommandsMenuModal {
   input: Input,
   list_state: ListState,
-  items: Vec<CommandInfo>,
+  commands: Vec<CommandInfo>,
+  /// Indices of commands matching the input.
+  items: Vec<usize>,
   app_sender: UnboundedSender<AppEvent>,
 }
 
 impl CommandsMenuModal {
-  pub fn new(app_sender: UnboundedSender<AppEvent>) -> Self {
+  pub fn new(keymap: &Keymap, app_sender: UnboundedSender<AppEvent>) -> Self {
+    let commands = get_commands(keymap);
     CommandsMenuModal {
       input: Input::default(),
       list_state: ListState::default().with_selected(Some(0)),
-      items: get_commands(""),
+      items: (0..commands.len()).collect(),
+      commands,
       app_sender,
     }
   }
+
+  fn move_selection(&mut self, down: bool) {
+    if self.items.is_empty() {
+      return;
+    }
+    let index = self.list_state.selected().unwrap_or(0);
+    let index = match down {
+      true if index >= self.items.len() - 1 => 0,
+      true => index + 1,
+      false if index == 0 => self.items.len() - 1,
+      false => index - 1,
+    };
+    self.list_state.select(Some(index));
+  }
 }
 
 impl Modal for CommandsMenuModal {
@@ -55,10 +78,10 @@ impl Modal for CommandsMenuModal {
           .app_sender
           .send(AppEvent::CloseCurrentModal)
           .log_ignore();
-        if let Some((_, _, event)) =
-          self.list_state.selected().and_then(|i| self.items.get(i))
-        {
-          self.app_sender.send(event.clone()).unwrap();
+        let selected =
+          self.list_state.selected().and_then(|i| self.items.get(i));
+        if let Some(info) = selected.and_then(|i| self.commands.get(*i)) {
+          self.app_sender.send(info.event.clone()).unwrap();
         }
         // Skip because AddProc event will immediately rerender.
         return true;
@@ -81,13 +104,16 @@ impl Modal for CommandsMenuModal {
         modifiers,
         ..
       }) if modifiers == &KeyModifiers::CONTROL => {
-        let index = self.list_state.selected().unwrap_or(0);
-        let index = if index >= self.items.len() - 1 {
-          0
-        } else {
-          index + 1
-        };
-        self.list_state.select(Some(index));
+        self.move_selection(true);
+        loop_action.render();
+        return true;
+      }
+      Event::Key(KeyEvent {
+        code: KeyCode::Down,
+        modifiers,
+        ..
+      }) if modifiers.is_empty() => {
+        self.move_selection(true);
         loop_action.render();
         return true;
       }
@@ -96,13 +122,16 @@ impl Modal for CommandsMenuModal {
         modifiers,
         ..
       }) if modifiers == &KeyModifiers::CONTROL => {
-        let index = self.list_state.selected().unwrap_or(0);
-        let index = if index == 0 {
-          self.items.len() - 1
-        } else {
-          index - 1
-        };
-        self.list_state.select(Some(index));
+        self.move_selection(false);
+        loop_action.render();
+        return true;
+      }
+      Event::Key(KeyEvent {
+        code: KeyCode::Up,
+        modifiers,
+        ..
+      }) if modifiers.is_empty() => {
+        self.move_selection(false);
         loop_action.render();
         return true;
       }
@@ -114,7 +143,8 @@ impl Modal for CommandsMenuModal {
       let res = self.input.handle(req);
       if let Some(res) = res {
         if res.value {
-          self.items = get_commands(self.input.value());
+          self.items = filter_commands(&self.commands, self.input.value());
+          self.list_state.select(Some(0));
         }
       }
       loop_action.render();
@@ -166,18 +196,27 @@ impl Modal for CommandsMenuModal {
     let list_items = self
       .items
       .iter()
-      .map(|(cmd, desc, _event)| {
-        let line = Line::from(vec![
-          Span::styled(*cmd, Style::reset().fg(tui::style::Color::White)),
-          "  ".into(),
-          Span::styled(
-            desc,
-            Style::reset()
-              .fg(tui::style::Color::DarkGray)
-              .add_modifier(Modifier::ITALIC),
-          ),
-        ]);
-        ListItem::new(line)
+      .filter_map(|i| self.commands.get(*i))
+      .map(|info| {
+        let mut spans = vec![Span::styled(
+          info.cmd,
+          Style::reset().fg(tui::style::Color::White),
+        )];
+        if !info.keys.is_empty() {
+          spans.push("  ".into());
+          spans.push(Span::styled(
+            info.keys.as_str(),
+            Style::reset().fg(tui::style::Color::Yellow),
+          ));
+        }
+        spans.push("  ".into());
+        spans.push(Span::styled(
+          info.desc.as_str(),
+          Style::reset()
+            .fg(tui::style::Color::DarkGray)
+            .add_modifier(Modifier::ITALIC),
+        ));
+        ListItem::new(Line::from(spans))
       })
       .collect::<Vec<_>>();
     let list = tui::widgets::List::new(list_items)
@@ -218,44 +257,89 @@ impl Modal for CommandsMenuModal {
   }
 }
 
-type CommandInfo = (&'static str, String, AppEvent);
+struct CommandInfo {
+  cmd: &'static str,
+  desc: String,
+  /// Keys bound to the command.
+  keys: String,
+  event: AppEvent,
+}
+
+fn get_commands(keymap: &Keymap) -> Vec<CommandInfo> {
+  menu_commands()
+    .into_iter()
+    .map(|(cmd, event)| {
+      let mut keys: Vec<String> = Vec::new();
+      for group in [KeymapGroup::Procs, KeymapGroup::Term, KeymapGroup::Copy] {
+        if let Some(key) = keymap.resolve_key(group, &event) {
+          let key = key.to_string();
+          if !keys.contains(&key) {
+            keys.push(key);
+          }
+        }
+      }
+      CommandInfo {
+        cmd,
+        desc: event.desc(),
+        keys: keys.join(" "),
+        event,
+      }
+    })
+    .collect()
+}
 
-fn get_commands(search: &str) -> Vec<CommandInfo> {
-  let events = [
-    // ("quit-or-ask", AppEvent::QuitOrAsk),
-    ("quit", AppEvent::Quit),
-    ("force-quit", AppEvent::ForceQuit),
-    ("toggle-focus", AppEvent::ToggleFocus),
-    ("focus-term", AppEvent::FocusTerm),
-    ("zoom", AppEvent::Zoom),
-    ("show-commands-menu", AppEvent::ShowCommandsMenu),
-    ("next-proc", AppEvent::NextProc),
-    ("prev-proc", AppEvent::PrevProc),
-    ("start-proc", AppEvent::StartProc),
-    ("term-proc", AppEvent::TermProc),
-    ("kill-proc", AppEvent::KillProc),
-    ("restart-proc", AppEvent::RestartProc),
-    ("duplicate-proc", AppEvent::DuplicateProc),
-    ("force-restart-proc", AppEvent::ForceRestartProc),
-    ("show-add-proc", AppEvent::ShowAddProc),
-    ("show-rename-proc", AppEvent::ShowRenameProc),
-    ("show-remove-proc", AppEvent::ShowRemoveProc),
-    ("close-current-modal", AppEvent::CloseCurrentModal),
-    ("scroll-down", AppEvent::ScrollDown),
-    ("scroll-up", AppEvent::ScrollUp),
-    ("copy-mode-enter", AppEvent::CopyModeEnter),
-    ("copy-mode-leave", AppEvent::CopyModeLeave),
-    ("copy-mode-end", AppEvent::CopyModeEnd),
-    ("copy-mode-copy", AppEvent::CopyModeCopy),
-  ];
+/// Indices of commands matching `search`, best matches first.
+fn filter_commands(commands: &[CommandInfo], search: &str) -> Vec<usize> {
+  let mut matches = commands
+    .iter()
+    .enumerate()
+    .filter_map(|(i, info)| {
+      let score =
+        fuzzy_score(search, info.cmd).max(fuzzy_score(search, &info.desc))?;
+      Some((i, score))
+    })
+    .collect::<Vec<_>>();
+  // Stable sort keeps the menu order for equal scores.
+  matches.sort_by_key(|(_, score)| -score);
+  matches.into_iter().map(|(i, _)| i).collect()
+}
 
-  let mut result = Vec::new();
-  for (cmd, event) in events {
-    let desc = event.desc();
-    if cmd.contains(search) || desc.contains(search) {
-      result.push((cmd, desc, event));
+/// Matches chars of `query` in order, ignoring case. Consecutive chars and
+/// chars at word starts score more.
+fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+  let text = text
+    .chars()
+    .flat_map(char::to_lowercase)
+    .collect::<Vec<_>>();
+  let mut score = 0;
+  let mut start = 0;
+  let mut prev = None;
+  for c in query.chars().flat_map(char::to_lowercase) {
+    let found = start + text[start..].iter().position(|t| *t == c)?;
+    score += 1;
+    if found > 0 && prev == Some(found - 1) {
+      score += 4;
+    }
+    if found == 0 || !text[found - 1].is_alphanumeric() {
+      score += 2;
     }
+    prev = Some(found);
+    start = found + 1;
   }
+  Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
 
-  result
+  #[test]
+  fn fuzzy() {
+    assert_eq!(fuzzy_score("", "quit"), Some(0));
+    assert_eq!(fuzzy_score("xyz", "quit"), None);
+    assert!(fuzzy_score("rp", "restart-proc").is_some());
+    assert!(fuzzy_score("RP", "restart-proc").is_some());
+    assert!(fuzzy_score("cr", "restart-proc").is_none());
+    assert!(fuzzy_score("kill", "kill-proc") > fuzzy_score("kill", "k-i-l-l"));
+  }
 }
@@ -1,6 +1,10 @@
 pub mod add_proc;
 pub mod commands_menu;
+pub mod confirm;
+pub mod copy_search;
+pub mod edit_proc_cmd;
 pub mod modal;
+pub mod proc_args;
 pub mod quit;
 pub mod remove_proc;
 pub mod rename_proc;
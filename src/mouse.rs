@@ -1,6 +1,11 @@
+use std::time::{Duration, Instant};
+
 use crossterm::event::{KeyModifiers, MouseEventKind};
 use tui::prelude::Rect;
 
+/// Wheel scrolls in the same direction within this time are accelerated.
+const SCROLL_ACCEL_WINDOW: Duration = Duration::from_millis(150);
+
 #[derive(Debug)]
 pub struct MouseEvent {
   pub kind: MouseEventKind,
@@ -26,3 +31,28 @@ impl MouseEvent {
     ret
   }
 }
+
+/// Speeds up scrolling while the mouse wheel is turned quickly.
+#[derive(Debug, Default)]
+pub struct ScrollAccel {
+  last: Option<(Instant, bool, usize)>,
+}
+
+impl ScrollAccel {
+  /// Returns the number of lines to scroll by for a wheel event. Each quick
+  /// consecutive event in the same direction raises the multiplier of
+  /// `speed` by one, up to `max`.
+  pub fn lines(&mut self, up: bool, speed: usize, max: usize) -> usize {
+    let now = Instant::now();
+    let mult = match self.last {
+      Some((time, last_up, mult))
+        if last_up == up && now.duration_since(time) < SCROLL_ACCEL_WINDOW =>
+      {
+        (mult + 1).min(max)
+      }
+      _ => 1,
+    };
+    self.last = Some((now, up, mult));
+    speed * mult
+  }
+}
@@ -1,24 +1,105 @@
-use std::{fs::File, io::BufReader};
+use std::{
+  fs::File,
+  io::BufReader,
+  path::{Path, PathBuf},
+};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use indexmap::IndexMap;
 use serde::Deserialize;
 
 use crate::{
   config::{CmdConfig, ProcConfig},
-  proc::StopSignal,
   settings::Settings,
 };
 
 #[derive(Deserialize)]
 struct Package {
+  name: Option<String>,
+  #[serde(default)]
   scripts: IndexMap<String, String>,
+  workspaces: Option<Workspaces>,
+}
+
+/// `workspaces` field of package.json. Yarn also accepts an object.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Workspaces {
+  List(Vec<String>),
+  Object {
+    #[serde(default)]
+    packages: Vec<String>,
+  },
+}
+
+/// Runs scripts with `<manager> run <script>`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PackageManager {
+  Npm,
+  Yarn,
+  Pnpm,
+  Bun,
+}
+
+/// Lockfiles in the order they are checked.
+const LOCKFILES: [(&str, PackageManager); 5] = [
+  ("package-lock.json", PackageManager::Npm),
+  ("yarn.lock", PackageManager::Yarn),
+  ("pnpm-lock.yaml", PackageManager::Pnpm),
+  ("bun.lockb", PackageManager::Bun),
+  ("bun.lock", PackageManager::Bun),
+];
+
+impl PackageManager {
+  pub fn from_name(name: &str) -> Result<Self> {
+    match name {
+      "npm" => Ok(PackageManager::Npm),
+      "yarn" => Ok(PackageManager::Yarn),
+      "pnpm" => Ok(PackageManager::Pnpm),
+      "bun" => Ok(PackageManager::Bun),
+      _ => bail!("Expected npm, yarn, pnpm or bun. Got: {}", name),
+    }
+  }
+
+  /// Detects the package manager by the lockfile. Defaults to npm.
+  fn detect(dir: &Path) -> Self {
+    LOCKFILES
+      .iter()
+      .find(|(lockfile, _)| dir.join(lockfile).is_file())
+      .map_or(PackageManager::Npm, |(_, manager)| *manager)
+  }
+
+  fn command(self) -> &'static str {
+    match self {
+      PackageManager::Npm => "npm",
+      PackageManager::Yarn => "yarn",
+      PackageManager::Pnpm => "pnpm",
+      PackageManager::Bun => "bun",
+    }
+  }
+}
+
+/// pnpm-workspace.yaml
+#[derive(Deserialize)]
+struct PnpmWorkspace {
+  #[serde(default)]
+  packages: Vec<String>,
 }
 
-pub fn load_npm_procs(settings: &Settings) -> Result<Vec<ProcConfig>> {
-  let file = File::open("package.json")?;
+fn read_package(dir: &Path) -> Result<Package> {
+  let file = File::open(dir.join("package.json"))?;
   let reader = BufReader::new(file);
-  let package: Package = serde_yaml::from_reader(reader)?;
+  Ok(serde_yaml::from_reader(reader)?)
+}
+
+pub fn load_npm_procs(
+  settings: &Settings,
+  root_only: bool,
+  manager: Option<PackageManager>,
+) -> Result<Vec<ProcConfig>> {
+  let package = read_package(Path::new("."))?;
+  let manager =
+    manager.unwrap_or_else(|| PackageManager::detect(Path::new(".")));
 
   let mut paths = if let Ok(path_var) = std::env::var("PATH") {
     let paths = std::env::split_paths(&path_var)
@@ -29,6 +110,51 @@ pub fn load_npm_procs(settings: &Settings) -> Result<Vec<ProcConfig>> {
     Vec::with_capacity(1)
   };
   paths.push("./node_modules/.bin".to_string());
+  let env = path_env(&paths)?;
+
+  let workspaces = if root_only {
+    Vec::new()
+  } else {
+    workspace_patterns(package.workspaces)?
+  };
+
+  let mut procs =
+    script_procs(settings, manager, None, None, package.scripts, &env);
+
+  if !workspaces.is_empty() {
+    // Scripts of workspace packages also see binaries of the root package.
+    let root_bin = std::env::current_dir()?.join("node_modules/.bin");
+    paths.push(root_bin.to_string_lossy().to_string());
+    let env = path_env(&paths)?;
+
+    for dir in find_workspace_dirs(&workspaces) {
+      let package = match read_package(&dir) {
+        Ok(package) => package,
+        Err(err) => {
+          log::warn!("Failed to read {}/package.json: {}", dir.display(), err);
+          continue;
+        }
+      };
+      let name = package.name.unwrap_or_else(|| {
+        dir
+          .file_name()
+          .map_or_else(String::new, |n| n.to_string_lossy().to_string())
+      });
+      procs.extend(script_procs(
+        settings,
+        manager,
+        Some(&name),
+        Some(dir),
+        package.scripts,
+        &env,
+      ));
+    }
+  }
+
+  Ok(procs)
+}
+
+fn path_env(paths: &[String]) -> Result<IndexMap<String, Option<String>>> {
   let mut env = IndexMap::with_capacity(1);
   env.insert(
     "PATH".to_string(),
@@ -38,18 +164,255 @@ pub fn load_npm_procs(settings: &Settings) -> Result<Vec<ProcConfig>> {
       )
     })?),
   );
+  Ok(env)
+}
+
+fn script_procs(
+  settings: &Settings,
+  manager: PackageManager,
+  prefix: Option<&str>,
+  cwd: Option<PathBuf>,
+  scripts: IndexMap<String, String>,
+  env: &IndexMap<String, Option<String>>,
+) -> Vec<ProcConfig> {
+  scripts
+    .into_iter()
+    .map(|(script, _)| {
+      let name = match prefix {
+        Some(prefix) => format!("{}:{}", prefix, script),
+        None => script.clone(),
+      };
+      let cmd = CmdConfig::Cmd {
+        cmd: vec![manager.command().to_string(), "run".to_string(), script],
+      };
+      ProcConfig {
+        cwd: cwd.as_ref().map(|cwd| cwd.clone().into_os_string()),
+        env: Some(env.clone()),
+        autostart: false,
+        ..ProcConfig::from_settings(name, cmd, settings)
+      }
+    })
+    .collect()
+}
+
+/// Workspace globs from package.json (npm, yarn) or pnpm-workspace.yaml.
+fn workspace_patterns(workspaces: Option<Workspaces>) -> Result<Vec<String>> {
+  let patterns = match workspaces {
+    Some(Workspaces::List(patterns)) => patterns,
+    Some(Workspaces::Object { packages }) => packages,
+    None => match File::open("pnpm-workspace.yaml") {
+      Ok(file) => {
+        let workspace: PnpmWorkspace =
+          serde_yaml::from_reader(BufReader::new(file))?;
+        workspace.packages
+      }
+      Err(_) => Vec::new(),
+    },
+  };
+  Ok(patterns)
+}
+
+/// Package directories matching the workspace globs. Patterns starting with
+/// `!` exclude directories.
+fn find_workspace_dirs(patterns: &[String]) -> Vec<PathBuf> {
+  let (excludes, includes): (Vec<_>, Vec<_>) =
+    patterns.iter().partition(|p| p.starts_with('!'));
+
+  let mut dirs = Vec::new();
+  for pattern in includes {
+    for dir in expand_glob(pattern) {
+      let excluded = excludes
+        .iter()
+        .any(|p| glob_match(p.trim_start_matches('!'), &dir));
+      if !excluded
+        && !dirs.contains(&dir)
+        && !dir.as_os_str().is_empty()
+        && dir.join("package.json").is_file()
+      {
+        dirs.push(dir);
+      }
+    }
+  }
+  dirs
+}
+
+/// Directories matching a glob with `*`, `?` and `**` segments.
+fn expand_glob(pattern: &str) -> Vec<PathBuf> {
+  let mut dirs = vec![PathBuf::from(".")];
+  for segment in pattern.trim_start_matches("./").split('/') {
+    dirs = match segment {
+      "" | "." => dirs,
+      "**" => {
+        let mut all = Vec::new();
+        for dir in dirs {
+          collect_subdirs(&dir, &mut all);
+          all.push(dir);
+        }
+        all
+      }
+      segment if segment.contains(['*', '?']) => dirs
+        .iter()
+        .flat_map(|dir| subdirs(dir))
+        .filter(|dir| {
+          dir
+            .file_name()
+            .map_or(false, |n| wildcard_match(segment, &n.to_string_lossy()))
+        })
+        .collect(),
+      segment => dirs
+        .into_iter()
+        .map(|dir| dir.join(segment))
+        .filter(|dir| dir.is_dir())
+        .collect(),
+    };
+  }
+  dirs
+    .into_iter()
+    .map(|dir| dir.strip_prefix(".").map(Path::to_path_buf).unwrap_or(dir))
+    .collect()
+}
+
+fn subdirs(dir: &Path) -> Vec<PathBuf> {
+  let mut dirs = match std::fs::read_dir(dir) {
+    Ok(entries) => entries
+      .filter_map(|entry| entry.ok())
+      .filter(|entry| entry.file_type().map_or(false, |t| t.is_dir()))
+      .filter(|entry| entry.file_name() != "node_modules")
+      .map(|entry| entry.path())
+      .collect::<Vec<_>>(),
+    Err(_) => Vec::new(),
+  };
+  dirs.sort();
+  dirs
+}
+
+fn collect_subdirs(dir: &Path, out: &mut Vec<PathBuf>) {
+  for sub in subdirs(dir) {
+    collect_subdirs(&sub, out);
+    out.push(sub);
+  }
+}
+
+/// Matches a relative directory path against a glob pattern.
+fn glob_match(pattern: &str, path: &Path) -> bool {
+  let pattern = pattern.trim_start_matches("./");
+  let segments = path
+    .components()
+    .map(|c| c.as_os_str().to_string_lossy().to_string())
+    .collect::<Vec<_>>();
+  let pattern = pattern
+    .split('/')
+    .filter(|s| !s.is_empty())
+    .collect::<Vec<_>>();
+  segments_match(&pattern, &segments)
+}
+
+fn segments_match(pattern: &[&str], segments: &[String]) -> bool {
+  match pattern.split_first() {
+    None => segments.is_empty(),
+    Some((&"**", rest)) => {
+      (0..=segments.len()).any(|i| segments_match(rest, &segments[i..]))
+    }
+    Some((first, rest)) => match segments.split_first() {
+      Some((segment, segments)) => {
+        wildcard_match(first, segment) && segments_match(rest, segments)
+      }
+      None => false,
+    },
+  }
+}
+
+/// Matches a single path segment against a pattern with `*` and `?`.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+  let pattern = pattern.chars().collect::<Vec<_>>();
+  let name = name.chars().collect::<Vec<_>>();
+  let (mut p, mut n) = (0, 0);
+  let mut star: Option<(usize, usize)> = None;
+  while n < name.len() {
+    match pattern.get(p) {
+      Some('*') => {
+        star = Some((p, n));
+        p += 1;
+      }
+      Some('?') => {
+        p += 1;
+        n += 1;
+      }
+      Some(c) if *c == name[n] => {
+        p += 1;
+        n += 1;
+      }
+      _ => match star {
+        Some((star_p, star_n)) => {
+          p = star_p + 1;
+          n = star_n + 1;
+          star = Some((star_p, star_n + 1));
+        }
+        None => return false,
+      },
+    }
+  }
+  pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::temp_path;
+
+  #[test]
+  fn wildcards() {
+    assert!(wildcard_match("*", "app"));
+    assert!(wildcard_match("app-*", "app-web"));
+    assert!(wildcard_match("a?p*b", "apxxb"));
+    assert!(!wildcard_match("app-*", "lib-web"));
+    assert!(!wildcard_match("a?", "a"));
+  }
+
+  #[test]
+  fn glob_paths() {
+    let path = Path::new("packages/web");
+    assert!(glob_match("packages/*", path));
+    assert!(glob_match("./packages/*", path));
+    assert!(glob_match("**/web", path));
+    assert!(glob_match("packages/**", path));
+    assert!(!glob_match("apps/*", path));
+    assert!(!glob_match("packages", path));
+  }
+
+  fn detect_in(name: &str, lockfile: Option<&str>) -> PackageManager {
+    let dir = temp_path(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    if let Some(lockfile) = lockfile {
+      std::fs::write(dir.join(lockfile), "").unwrap();
+    }
+    let manager = PackageManager::detect(&dir);
+    std::fs::remove_dir_all(&dir).unwrap();
+    manager
+  }
+
+  #[test]
+  fn detect_by_lockfile() {
+    use PackageManager::*;
+    assert_eq!(detect_in("npm", Some("package-lock.json")), Npm);
+    assert_eq!(detect_in("yarn", Some("yarn.lock")), Yarn);
+    assert_eq!(detect_in("pnpm", Some("pnpm-lock.yaml")), Pnpm);
+    assert_eq!(detect_in("bun", Some("bun.lockb")), Bun);
+    assert_eq!(detect_in("none", None), Npm);
+  }
+
+  #[test]
+  fn workspaces_field() {
+    let package: Package =
+      serde_yaml::from_str(r#"{"workspaces": ["packages/*"]}"#).unwrap();
+    let patterns = workspace_patterns(package.workspaces).unwrap();
+    assert_eq!(patterns, vec!["packages/*"]);
 
-  let procs = package.scripts.into_iter().map(|(name, cmd)| ProcConfig {
-    name,
-    cmd: CmdConfig::Shell { shell: cmd },
-    cwd: None,
-    env: Some(env.clone()),
-    autostart: false,
-    autorestart: false,
-
-    stop: StopSignal::default(),
-    mouse_scroll_speed: settings.mouse_scroll_speed,
-    scrollback_len: settings.scrollback_len,
-  });
-  Ok(procs.collect())
+    let package: Package =
+      serde_yaml::from_str(r#"{"workspaces": {"packages": ["apps/*"]}}"#)
+        .unwrap();
+    let patterns = workspace_patterns(package.workspaces).unwrap();
+    assert_eq!(patterns, vec!["apps/*"]);
+  }
 }
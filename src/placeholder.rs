@@ -0,0 +1,193 @@
+use anyhow::{bail, Result};
+
+use crate::proc::handle::ProcHandle;
+
+/// Attributes of a process available as placeholders in commands: `{name}`,
+/// `{pid}`, `{exit}` and `{cwd}`.
+#[derive(Debug, Default)]
+pub struct ProcVars {
+  pub name: Option<String>,
+  /// Only while the process is running.
+  pub pid: Option<u32>,
+  /// Only after the process exited.
+  pub exit: Option<u32>,
+  pub cwd: Option<String>,
+}
+
+impl ProcVars {
+  pub fn from_proc(proc: &ProcHandle) -> Self {
+    let cwd = match &proc.cfg().cwd {
+      Some(cwd) => Some(cwd.to_string_lossy().to_string()),
+      None => std::env::current_dir()
+        .ok()
+        .map(|cwd| cwd.to_string_lossy().to_string()),
+    };
+    ProcVars {
+      name: Some(proc.name().to_string()),
+      pid: proc.pid(),
+      exit: proc.exit_code(),
+      cwd,
+    }
+  }
+
+  fn get(&self, key: &str) -> Option<String> {
+    match key {
+      "name" => self.name.clone(),
+      "pid" => self.pid.map(|pid| pid.to_string()),
+      "exit" => self.exit.map(|code| code.to_string()),
+      "cwd" => self.cwd.clone(),
+      _ => None,
+    }
+  }
+
+  /// Replaces placeholders in the shell command. Values are quoted for the
+  /// shell. Unknown placeholders and ones without a value (like `{pid}` of a
+  /// stopped process) are left as is, or are an error if `strict`. Only
+  /// `{identifier}` is a placeholder, so `${...}` and things like
+  /// `awk '{print $1}'` are left alone.
+  pub fn expand(&self, template: &str, strict: bool) -> Result<String> {
+    let mut buf = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+      buf.push_str(&rest[..start]);
+      let key = rest[start + 1..]
+        .split_once('}')
+        .map(|(key, _)| key)
+        .filter(|key| is_identifier(key));
+      let key = match key {
+        Some(key) if !buf.ends_with('$') => key,
+        _ => {
+          buf.push('{');
+          rest = &rest[start + 1..];
+          continue;
+        }
+      };
+      let placeholder = &rest[start..start + key.len() + 2];
+      match self.get(key) {
+        Some(value) => buf.push_str(&shell_quote(&value)),
+        None if strict => {
+          bail!("Undefined placeholder: \"{}\"", placeholder)
+        }
+        None => buf.push_str(placeholder),
+      }
+      rest = &rest[start + placeholder.len()..];
+    }
+    buf.push_str(rest);
+    Ok(buf)
+  }
+}
+
+fn is_identifier(s: &str) -> bool {
+  let mut chars = s.chars();
+  chars
+    .next()
+    .map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+    && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn is_shell_safe(s: &str) -> bool {
+  !s.is_empty()
+    && s
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || "_-+=.,:/@%".contains(c))
+}
+
+/// Quotes the value as a single word for `sh -c`.
+#[cfg(unix)]
+fn shell_quote(value: &str) -> String {
+  if is_shell_safe(value) {
+    value.to_string()
+  } else {
+    format!("'{}'", value.replace('\'', "'\\''"))
+  }
+}
+
+/// Quotes the value as a single word for `cmd.exe`. Double quotes can't be
+/// escaped there, so they are dropped.
+#[cfg(windows)]
+fn shell_quote(value: &str) -> String {
+  if is_shell_safe(value) && !value.contains('%') {
+    value.to_string()
+  } else {
+    format!("\"{}\"", value.replace('"', ""))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn vars() -> ProcVars {
+    ProcVars {
+      name: Some("server".to_string()),
+      pid: Some(42),
+      exit: None,
+      cwd: Some("/srv".to_string()),
+    }
+  }
+
+  #[test]
+  fn name() {
+    assert_eq!(
+      vars().expand("logs/{name}.txt", false).unwrap(),
+      "logs/server.txt"
+    );
+  }
+
+  #[test]
+  fn pid() {
+    assert_eq!(vars().expand("gdb -p {pid}", false).unwrap(), "gdb -p 42");
+  }
+
+  #[test]
+  fn exit() {
+    let vars = ProcVars {
+      exit: Some(1),
+      ..vars()
+    };
+    assert_eq!(vars.expand("echo {exit}", false).unwrap(), "echo 1");
+  }
+
+  #[test]
+  fn cwd() {
+    assert_eq!(vars().expand("code {cwd}", false).unwrap(), "code /srv");
+  }
+
+  #[test]
+  fn undefined() {
+    assert_eq!(
+      vars().expand("{exit} {foo}", false).unwrap(),
+      "{exit} {foo}"
+    );
+    assert!(vars().expand("echo {exit}", true).is_err());
+    assert!(vars().expand("echo {foo}", true).is_err());
+    assert!(ProcVars::default().expand("{name}", true).is_err());
+  }
+
+  #[test]
+  fn literal() {
+    assert_eq!(
+      vars().expand("echo ${HOME} {name", true).unwrap(),
+      "echo ${HOME} {name"
+    );
+    assert_eq!(
+      vars().expand("awk '{print $1}' {name}.log", true).unwrap(),
+      "awk '{print $1}' server.log"
+    );
+    assert_eq!(vars().expand("{{name}}", true).unwrap(), "{server}");
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn quoted() {
+    let vars = ProcVars {
+      name: Some("a b; rm -rf ~".to_string()),
+      cwd: Some("/it's".to_string()),
+      ..vars()
+    };
+    assert_eq!(
+      vars.expand("echo {name} {cwd}", false).unwrap(),
+      "echo 'a b; rm -rf ~' '/it'\\''s'"
+    );
+  }
+}
@@ -1,9 +1,22 @@
+use crate::{
+  config::{CmdConfig, ProcConfig},
+  watch::WatchAction,
+};
+
 use super::{
-  msg::{ProcCmd, ProcEvent},
-  CopyMode, Proc,
+  msg::{ExitStatus, ProcCmd, ProcEvent},
+  usage::{SessionStats, Usage, UsageTracker},
+  CopyMode, Proc, SelectionSize,
 };
 
-use std::time::Instant;
+use std::{
+  collections::HashMap,
+  sync::atomic::Ordering,
+  time::{Duration, Instant},
+};
+
+use tui::style::Color;
+use unicode_width::UnicodeWidthStr;
 
 /// Amount of time a process has to stay up for autorestart to trigger
 const RESTART_THRESHOLD_SECONDS: f64 = 1.0;
@@ -12,12 +25,37 @@ pub struct ProcHandle {
   id: usize,
   name: String,
   is_up: bool,
-  exit_code: Option<u32>,
+  exit_status: Option<ExitStatus>,
 
   pub to_restart: bool,
   pub autorestart: bool,
+  /// Autostart was toggled at runtime.
+  autostart_changed: bool,
   last_start: Option<Instant>,
+  /// Number of times the process was started.
+  starts: usize,
+  /// Start was requested but the process hasn't started yet.
+  starting: bool,
+  /// The process was asked to stop since it was started.
+  stop_requested: bool,
+  /// Dependencies (`deps`) that are not ready yet. The process is started
+  /// once all of them are ready.
+  waiting_for: Vec<String>,
+  /// Output matched `ready_when` since the process was started.
+  ready: bool,
   changed: bool,
+  last_output: Option<Instant>,
+  /// No output for the `dim_idle_procs` duration.
+  idle: bool,
+  /// Output line count when the proc was last viewed.
+  seen_lines: usize,
+  wrap: bool,
+  /// Runs of identical lines are displayed as a single line.
+  dedup_lines: bool,
+  h_offset: usize,
+  foreground_cmd: Option<String>,
+  usage: Option<Usage>,
+  usage_tracker: UsageTracker,
 
   proc: Proc,
 }
@@ -28,29 +66,170 @@ impl ProcHandle {
       id: proc.id,
       name,
       is_up: false,
-      exit_code: None,
+      exit_status: None,
       to_restart: false,
       autorestart,
+      autostart_changed: false,
       last_start: None,
+      starts: 0,
+      starting: false,
+      stop_requested: false,
+      waiting_for: Vec::new(),
+      ready: false,
       changed: false,
+      last_output: None,
+      idle: false,
+      seen_lines: 0,
+      wrap: true,
+      dedup_lines: proc.cfg.dedup_lines,
+      h_offset: 0,
+      foreground_cmd: None,
+      usage: None,
+      usage_tracker: UsageTracker::default(),
       proc,
     }
   }
 
   pub fn send(&mut self, cmd: ProcCmd) {
+    match cmd {
+      ProcCmd::Start if !self.is_up => {
+        self.starting = true;
+        self.stop_requested = false;
+      }
+      ProcCmd::Stop | ProcCmd::Kill if self.is_up => self.stop_requested = true,
+      _ => (),
+    }
     self.proc.handle_cmd(cmd)
   }
 
+  /// Whether the process sends its output as `ProcEvent::Output` lines.
+  pub fn set_line_output(&self, enabled: bool) {
+    self.proc.line_output.store(enabled, Ordering::Relaxed);
+  }
+
+  /// Marks the process as waiting for a `ProcEvent::ScheduledStart`. Such
+  /// process is created without autostart, so that it isn't started right
+  /// away, but it is still shown and saved as autostarted.
+  pub fn schedule_start(&mut self) {
+    self.starting = true;
+    self.proc.cfg.autostart = true;
+  }
+
+  /// Delays the start of the process until all `deps` are ready (see
+  /// `deps::procs_to_start`).
+  pub fn wait_for(&mut self, deps: Vec<String>) {
+    self.waiting_for = deps;
+  }
+
+  /// Dependencies the process is waiting for before it is started.
+  pub fn waiting_for(&self) -> &[String] {
+    &self.waiting_for
+  }
+
+  /// Removes dependencies that are ready from the waiting list. Returns true
+  /// if the list changed.
+  pub fn update_waiting(&mut self, ready: &[&str]) -> bool {
+    let len = self.waiting_for.len();
+    self
+      .waiting_for
+      .retain(|dep| !ready.contains(&dep.as_str()));
+    self.waiting_for.len() != len
+  }
+
+  /// Whether processes that depend on this one can start: it is running and
+  /// its output matched `ready_when`, if set.
+  pub fn is_ready(&self) -> bool {
+    let has_ready_pattern = self
+      .proc
+      .cfg
+      .watch
+      .iter()
+      .any(|w| w.action == WatchAction::Ready);
+    self.is_up && (self.ready || !has_ready_pattern)
+  }
+
+  pub fn mark_ready(&mut self) {
+    self.ready = true;
+  }
+
+  /// Whether the process is down and not about to be started.
+  pub fn is_finished(&self) -> bool {
+    !self.is_up && !self.starting
+  }
+
   pub fn rename(&mut self, name: &str) {
     self.name.replace_range(.., &name);
   }
 
+  /// Changes the command used on the next start.
+  pub fn set_cmd(&mut self, cmd: CmdConfig) {
+    self.proc.cfg.cmd = cmd;
+    self.proc.cmd = (&self.proc.cfg).into();
+  }
+
+  /// Appends arguments to the configured command for the next starts.
+  pub fn set_args(&mut self, args: &[String]) {
+    self.proc.cmd = (&self.proc.cfg).into();
+    self.proc.cmd.args(args);
+  }
+
   pub fn id(&self) -> usize {
     self.id
   }
 
   pub fn exit_code(&self) -> Option<u32> {
-    self.exit_code
+    self.exit_status.map(|status| status.code)
+  }
+
+  pub fn exit_status(&self) -> Option<ExitStatus> {
+    self.exit_status
+  }
+
+  /// Name, pid and command line of a running proc, e.g. for bug reports.
+  pub fn info(&self) -> Option<String> {
+    let pid = self.pid()?;
+    let cmd = self.proc.cfg.cmd.command_line();
+    Some(format!("{} pid={} cmd={}", self.name, pid, cmd))
+  }
+
+  pub fn pid(&self) -> Option<u32> {
+    if self.is_up {
+      self.proc.pid()
+    } else {
+      None
+    }
+  }
+
+  /// Time since the process was started, if it is running.
+  pub fn uptime(&self) -> Option<Duration> {
+    match self.last_start {
+      Some(last_start) if self.is_up => Some(last_start.elapsed()),
+      _ => None,
+    }
+  }
+
+  /// Number of times the process was started again after the first start.
+  pub fn restarts(&self) -> usize {
+    self.starts.saturating_sub(1)
+  }
+
+  /// Whether the process failed to start or exited with a non-zero code.
+  pub fn is_failed(&self) -> bool {
+    !self.is_up
+      && (self.spawn_error().is_some()
+        || self.exit_code().map_or(false, |code| code != 0))
+  }
+
+  /// Exit code of the process if it failed on its own: exited with a
+  /// non-zero code without being stopped by mprocs, or failed to start (127).
+  pub fn failure_code(&self) -> Option<u32> {
+    if self.is_up || self.stop_requested {
+      return None;
+    }
+    if self.spawn_error().is_some() {
+      return Some(127);
+    }
+    self.exit_code().filter(|code| *code != 0)
   }
 
   pub fn lock_view(&self) -> ProcViewFrame {
@@ -64,6 +243,14 @@ impl ProcHandle {
     }
   }
 
+  /// Error message if the last attempt to start the proc failed.
+  pub fn spawn_error(&self) -> Option<&str> {
+    match &self.proc.inst {
+      super::ProcState::Error(err) => Some(err),
+      super::ProcState::None | super::ProcState::Some(_) => None,
+    }
+  }
+
   pub fn name(&self) -> &str {
     &self.name
   }
@@ -72,6 +259,137 @@ impl ProcHandle {
     self.is_up
   }
 
+  pub fn cfg(&self) -> &ProcConfig {
+    &self.proc.cfg
+  }
+
+  /// Color of the name in prefixed output, if set in the config.
+  pub fn color(&self) -> Option<Color> {
+    self.proc.cfg.color
+  }
+
+  pub fn critical(&self) -> bool {
+    self.proc.cfg.critical
+  }
+
+  /// Whether the process is started at startup. Changed at runtime it only
+  /// matters for sessions saved with `save-session`.
+  pub fn autostart(&self) -> bool {
+    self.proc.cfg.autostart
+  }
+
+  pub fn autostart_changed(&self) -> bool {
+    self.autostart_changed
+  }
+
+  pub fn toggle_autostart(&mut self) {
+    self.proc.cfg.autostart = !self.proc.cfg.autostart;
+    self.autostart_changed = !self.autostart_changed;
+  }
+
+  pub fn toggle_autorestart(&mut self) {
+    self.autorestart = !self.autorestart;
+  }
+
+  pub fn scrollback_len(&self) -> usize {
+    self.proc.scrollback_len
+  }
+
+  /// Size of captured output when `raw_passthrough` is enabled.
+  pub fn raw_len(&self) -> Option<usize> {
+    match &self.proc.inst {
+      super::ProcState::Some(inst) => inst
+        .raw
+        .as_ref()
+        .map(|raw| raw.lock().map_or(0, |raw| raw.len())),
+      _ => None,
+    }
+  }
+
+  /// Last value of `update_foreground_cmd`.
+  pub fn foreground_cmd(&self) -> Option<&str> {
+    self.foreground_cmd.as_deref()
+  }
+
+  /// Returns true if the foreground command changed.
+  pub fn update_foreground_cmd(&mut self) -> bool {
+    let cmd = if self.is_up {
+      self.proc.foreground_cmd()
+    } else {
+      None
+    };
+    let changed = cmd != self.foreground_cmd;
+    self.foreground_cmd = cmd;
+    changed
+  }
+
+  /// Returns true if the proc became idle or active. Procs are idle when
+  /// they haven't printed anything for `after` since the last output or
+  /// start.
+  pub fn update_idle(&mut self, after: Duration) -> bool {
+    let idle = self
+      .last_output
+      .max(self.last_start)
+      .map_or(true, |at| at.elapsed() >= after);
+    let changed = idle != self.idle;
+    self.idle = idle;
+    changed
+  }
+
+  /// Last value of `update_idle`.
+  pub fn is_idle(&self) -> bool {
+    self.idle
+  }
+
+  /// Last value of `update_usage`.
+  pub fn usage(&self) -> Option<Usage> {
+    self.usage
+  }
+
+  /// Returns true if the usage changed.
+  pub fn update_usage(
+    &mut self,
+    now: Instant,
+    sessions: &HashMap<u32, SessionStats>,
+  ) -> bool {
+    let stats = match self.proc.pid() {
+      Some(pid) if self.is_up => sessions.get(&pid),
+      _ => None,
+    };
+    let usage = match stats {
+      Some(stats) => Some(self.usage_tracker.update(now, *stats)),
+      None => {
+        self.usage_tracker.reset();
+        None
+      }
+    };
+    let changed = usage != self.usage;
+    self.usage = usage;
+    changed
+  }
+
+  /// Window title and icon name set by the process.
+  pub fn term_title(&self) -> (String, String) {
+    self.proc.lock_vt().map_or_else(Default::default, |vt| {
+      let screen = vt.screen();
+      (screen.title().to_string(), screen.icon_name().to_string())
+    })
+  }
+
+  /// Number of invalid characters in the output. Usually caused by binary
+  /// output or a wrong locale.
+  pub fn decode_errors(&self) -> usize {
+    self.proc.lock_vt().map_or(0, |vt| vt.screen().errors())
+  }
+
+  /// Number of lines dropped from scrollback because of `scrollback_bytes`.
+  pub fn scrollback_trimmed(&self) -> usize {
+    self
+      .proc
+      .lock_vt()
+      .map_or(0, |vt| vt.screen().scrollback_trimmed())
+  }
+
   pub fn changed(&self) -> bool {
     self.changed
   }
@@ -80,21 +398,136 @@ impl ProcHandle {
     &self.proc.copy_mode
   }
 
+  pub fn normal_screen_view(&self) -> bool {
+    self.proc.normal_screen_view()
+  }
+
+  pub fn selection_size(&mut self) -> Option<SelectionSize> {
+    self.proc.selection_size()
+  }
+
+  /// Width of the line numbers column. Zero when line numbers are disabled.
+  pub fn gutter(&self) -> u16 {
+    self.proc.gutter()
+  }
+
+  /// When disabled, wrapped rows are displayed as a single truncated line.
+  pub fn wrap(&self) -> bool {
+    self.wrap
+  }
+
+  pub fn toggle_wrap(&mut self) {
+    self.wrap = !self.wrap;
+    self.h_offset = 0;
+  }
+
+  /// Whether runs of identical lines are collapsed in the view.
+  pub fn dedup_lines(&self) -> bool {
+    self.dedup_lines
+  }
+
+  pub fn toggle_dedup_lines(&mut self) {
+    self.dedup_lines = !self.dedup_lines;
+  }
+
+  /// First displayed column when wrapping is disabled.
+  pub fn h_offset(&self) -> usize {
+    self.h_offset
+  }
+
+  pub fn scroll_left(&mut self, n: usize) {
+    self.h_offset = self.h_offset.saturating_sub(n);
+  }
+
+  pub fn scroll_right(&mut self, n: usize) {
+    if !self.wrap {
+      self.h_offset = (self.h_offset + n).min(self.max_h_offset());
+    }
+  }
+
+  /// How far the widest line extends past the right edge.
+  fn max_h_offset(&self) -> usize {
+    let vt = match self.proc.lock_vt() {
+      Some(vt) => vt,
+      None => return 0,
+    };
+    let screen = vt.screen();
+    let cols = screen.size().1;
+
+    let mut widest = 0;
+    let mut width = 0;
+    for (row, text) in screen.rows(0, cols).enumerate() {
+      if screen.row_wrapped(row as u16) {
+        width += cols as usize;
+      } else {
+        widest = widest.max(width + text.width());
+        width = 0;
+      }
+    }
+    widest.max(width).saturating_sub(cols as usize)
+  }
+
   pub fn focus(&mut self) {
     self.changed = false;
   }
 
+  pub fn scroll_to_bottom(&mut self) {
+    self.proc.scroll_down_lines(usize::MAX);
+  }
+
+  /// Remembers how much output was seen before switching to another proc.
+  pub fn blur(&mut self) {
+    self.seen_lines = self.output_lines();
+  }
+
+  /// Number of lines printed since the proc was last viewed, if it has unseen
+  /// output. Lines redrawn in place (e.g. progress bars) are not counted.
+  pub fn unread_lines(&self) -> Option<usize> {
+    if self.changed {
+      Some(self.output_lines().saturating_sub(self.seen_lines))
+    } else {
+      None
+    }
+  }
+
+  fn output_lines(&self) -> usize {
+    self.proc.lock_vt().map_or(0, |vt| {
+      let screen = vt.screen();
+      let (row, _col) = screen.cursor_position();
+      screen.rows_above() + screen.scrollback() + row as usize
+    })
+  }
+
+  pub fn set_visible(&mut self, visible: bool) {
+    self.proc.set_visible(visible);
+  }
+
   pub fn duplicate(&self) -> Self {
     let proc = self.proc.duplicate();
     Self {
       id: proc.id,
       name: self.name.clone(),
       is_up: false,
-      exit_code: None,
+      exit_status: None,
       to_restart: false,
       autorestart: self.autorestart,
+      autostart_changed: self.autostart_changed,
       last_start: None,
+      starts: 0,
+      starting: false,
+      stop_requested: false,
+      waiting_for: Vec::new(),
+      ready: false,
       changed: false,
+      last_output: None,
+      idle: false,
+      seen_lines: 0,
+      wrap: true,
+      dedup_lines: self.dedup_lines,
+      h_offset: 0,
+      foreground_cmd: None,
+      usage: None,
+      usage_tracker: UsageTracker::default(),
       proc,
     }
   }
@@ -107,11 +540,22 @@ impl ProcHandle {
         if !selected {
           self.changed = true;
         }
+        self.last_output = Some(Instant::now());
+        self.idle = false;
+        self.proc.sync_gutter();
+        // Go back to the left edge once long lines are gone.
+        if self.h_offset > 0 {
+          self.h_offset = self.h_offset.min(self.max_h_offset());
+        }
       }
-      ProcEvent::Stopped(exit_code) => {
+      ProcEvent::Stopped(status) => {
+        let exit_code = status.code;
         self.is_up = false;
-        self.exit_code = Some(exit_code);
-        if self.autorestart && !self.to_restart && exit_code != 0 {
+        self.exit_status = Some(status);
+        if self.autorestart
+          && !self.to_restart
+          && self.proc.cfg.autorestart_on.matches(exit_code)
+        {
           match self.last_start {
             Some(last_start) => {
               let elapsed_time = Instant::now().duration_since(last_start);
@@ -129,8 +573,29 @@ impl ProcHandle {
       }
       ProcEvent::Started => {
         self.last_start = Some(Instant::now());
+        self.starts += 1;
+        self.starting = false;
         self.is_up = true;
+        self.ready = false;
+        self.waiting_for.clear();
+      }
+      ProcEvent::SpawnFailed(err) => {
+        log::warn!("Process \"{}\": {}", self.name, err);
+        self.is_up = false;
+        self.starting = false;
+        self.exit_status = None;
+      }
+      ProcEvent::ScheduledStart => {
+        // Skip if the proc was started by the user meanwhile.
+        if self.last_start.is_none() && self.exit_status.is_none() {
+          self.send(ProcCmd::Start);
+        }
       }
+      ProcEvent::Reply(bytes) => self.proc.write_reply(&bytes),
+      ProcEvent::Paste(text) => self.send(ProcCmd::Paste(text)),
+      ProcEvent::Watch(WatchAction::Ready, _) => self.mark_ready(),
+      // Handled by `App`.
+      ProcEvent::Watch(..) | ProcEvent::Flash(_) | ProcEvent::Output(_) => (),
     }
   }
 }
@@ -1,10 +1,14 @@
 pub mod handle;
 pub mod msg;
+pub mod reader;
+pub mod signal;
+pub mod usage;
+pub mod writer;
 
 use std::fmt::Debug;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::{Arc, RwLock};
-use std::thread::{self, spawn};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::spawn;
 use std::time::Duration;
 
 use anyhow::bail;
@@ -14,26 +18,33 @@ use portable_pty::MasterPty;
 use portable_pty::{native_pty_system, ChildKiller, CommandBuilder, PtySize};
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::UnboundedSender;
-use tokio::task::spawn_blocking;
 use tui::layout::Rect;
 use vt100::MouseProtocolMode;
 
 use crate::config::ProcConfig;
+use crate::dedup::dedup_text;
 use crate::encode_term::{encode_key, encode_mouse_event, KeyCodeEncodeModes};
 use crate::error::ResultLogger;
-use crate::event::CopyMove;
+use crate::event::{CopyMove, PromptDir};
 use crate::key::Key;
-use crate::mouse::MouseEvent;
+use crate::mouse::{MouseEvent, ScrollAccel};
+use crate::watch::Watcher;
 use crate::yaml_val::Val;
 
 use self::handle::ProcHandle;
-use self::msg::{ProcCmd, ProcEvent};
+use self::msg::{ExitStatus, ProcCmd, ProcEvent};
+use self::reader::{spawn_reader, ProcOutput, ReadOptions};
+use self::signal::Signal;
+use self::writer::PtyWriter;
 
 pub struct Inst {
   pub vt: VtWrap,
+  /// Unparsed output, when `raw_passthrough` is enabled.
+  pub raw: Option<RawWrap>,
 
   pub pid: u32,
   pub master: Box<dyn MasterPty + Send>,
+  pub writer: PtyWriter,
   pub killer: Box<dyn ChildKiller + Send + Sync>,
 
   pub running: Arc<AtomicBool>,
@@ -50,16 +61,145 @@ impl Debug for Inst {
 
 pub type VtWrap = Arc<RwLock<vt100::Parser>>;
 
+/// Max time to wait for the remaining output after a process exited.
+const READER_DONE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Max amount of unparsed output kept for a hidden proc.
+const LAZY_PARSE_BUF_LEN: usize = 1024 * 1024;
+
+/// Output of a hidden proc that wasn't passed to the parser yet. Used when
+/// `lazy_parse` setting is enabled.
+#[derive(Default)]
+pub struct LazyOutput {
+  visible: bool,
+  buf: Vec<u8>,
+  scan: EscScan,
+}
+
+pub type LazyWrap = Arc<Mutex<LazyOutput>>;
+
+pub type RawWrap = Arc<Mutex<Vec<u8>>>;
+
+impl LazyOutput {
+  pub fn process(&mut self, vt: &VtWrap, bytes: &[u8]) {
+    // OSC sequences (e.g. window title) and queries are parsed right away, so
+    // that the proc state is up to date and the proc gets replies even when
+    // it is hidden.
+    let urgent = self.scan.feed(bytes);
+    if self.visible
+      || urgent
+      || self.buf.len() + bytes.len() > LAZY_PARSE_BUF_LEN
+    {
+      self.flush(vt);
+      if let Ok(mut vt) = vt.write() {
+        vt.process(bytes);
+      }
+    } else {
+      self.buf.extend_from_slice(bytes);
+    }
+  }
+
+  fn flush(&mut self, vt: &VtWrap) {
+    if !self.buf.is_empty() {
+      if let Ok(mut vt) = vt.write() {
+        vt.process(&self.buf);
+      }
+      self.buf.clear();
+    }
+  }
+}
+
+/// Finds escape sequences in output that shouldn't wait in `LazyOutput`:
+/// OSC, DCS (e.g. DECRQSS) and CSI queries (e.g. DA, DSR, XTVERSION or size
+/// reports). Keeps its state between chunks, so sequences split across reads
+/// are found too.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum EscScan {
+  #[default]
+  Ground,
+  Esc,
+  Csi,
+}
+
+impl EscScan {
+  /// Final bytes of CSI sequences that may ask for a reply.
+  const CSI_QUERIES: &'static [u8] = b"cnpqtu";
+
+  /// Returns whether any sequence was found in `bytes`.
+  fn feed(&mut self, bytes: &[u8]) -> bool {
+    let mut found = false;
+    for &b in bytes {
+      *self = match (*self, b) {
+        (_, 0x1b) => EscScan::Esc,
+        (EscScan::Esc, b']' | b'P') => {
+          found = true;
+          EscScan::Ground
+        }
+        (EscScan::Esc, b'[') => EscScan::Csi,
+        // Parameters and intermediate bytes.
+        (EscScan::Csi, 0x20..=0x3f) => EscScan::Csi,
+        (EscScan::Csi, 0x40..=0x7e) => {
+          found |= Self::CSI_QUERIES.contains(&b);
+          EscScan::Ground
+        }
+        _ => EscScan::Ground,
+      };
+    }
+    found
+  }
+}
+
+/// What a process instance is started with, taken from its `Proc`.
+struct SpawnOptions<'a> {
+  cfg: &'a ProcConfig,
+  cmd: CommandBuilder,
+  /// Can differ from `cfg.scrollback_len` after `set-scrollback`.
+  scrollback_len: usize,
+  lazy: Option<LazyWrap>,
+  line_output: Arc<AtomicBool>,
+  read_options: ReadOptions,
+}
+
 impl Inst {
   fn spawn(
     id: usize,
-    cmd: CommandBuilder,
     tx: UnboundedSender<(usize, ProcEvent)>,
     size: &Size,
-    scrollback_len: usize,
+    opts: SpawnOptions,
+    history: Option<VtWrap>,
   ) -> anyhow::Result<Self> {
-    let vt = vt100::Parser::new(size.height, size.width, scrollback_len);
-    let vt = Arc::new(RwLock::new(vt));
+    let cfg = opts.cfg;
+    let vt = match history {
+      Some(vt) => {
+        if let Ok(mut vt) = vt.write() {
+          write_restart_separator(&mut vt);
+        }
+        vt
+      }
+      None => {
+        let mut vt =
+          vt100::Parser::new(size.height, size.width, opts.scrollback_len);
+        vt.set_scrollback_bytes(cfg.scrollback_bytes);
+        vt.set_cell_size_pixels(cfg.cell_pixel_size);
+        vt.set_tab_width(cfg.tab_width);
+        vt.set_follow_output(cfg.follow_output);
+        vt.set_alternate_screen_disabled(cfg.disable_alt_screen);
+        vt.set_collapse_progress(cfg.collapse_progress);
+        vt.set_terminal_name(&format!("mprocs({})", env!("CARGO_PKG_VERSION")));
+        {
+          let tx = tx.clone();
+          vt.set_reply_sender(vt100::ReplySender::new(move |bytes| {
+            let _r = tx.send((id, ProcEvent::Reply(bytes)));
+          }));
+        }
+        Arc::new(RwLock::new(vt))
+      }
+    };
+    let raw = if cfg.raw_passthrough {
+      Some(RawWrap::default())
+    } else {
+      None
+    };
 
     let pty_system = native_pty_system();
     let pair = pty_system.openpty(PtySize {
@@ -70,64 +210,57 @@ impl Inst {
     })?;
 
     let running = Arc::new(AtomicBool::new(true));
-    let mut child = pair.slave.spawn_command(cmd)?;
+    let mut child = pair.slave.spawn_command(opts.cmd)?;
     let pid = child.process_id().unwrap_or(0);
     let killer = child.clone_killer();
 
     let _r = tx.send((id, ProcEvent::Started));
 
-    let mut reader = pair.master.try_clone_reader().unwrap();
-
-    {
-      let tx = tx.clone();
-      let vt = vt.clone();
-      let running = running.clone();
-      spawn_blocking(move || {
-        let mut buf = [0; 4 * 1024];
-        loop {
-          if !running.load(Ordering::Relaxed) {
-            break;
-          }
-
-          match reader.read(&mut buf[..]) {
-            Ok(count) => {
-              if count > 0 {
-                if let Ok(mut vt) = vt.write() {
-                  vt.process(&buf[..count]);
-                  match tx.send((id, ProcEvent::Render)) {
-                    Ok(_) => (),
-                    Err(_) => break,
-                  }
-                }
-              } else {
-                thread::sleep(Duration::from_millis(10));
-              }
-            }
-            _ => break,
-          }
-        }
-      });
-    }
+    let output = ProcOutput {
+      vt: vt.clone(),
+      lazy: opts.lazy,
+      raw: raw.clone(),
+      watcher: (!cfg.watch.is_empty()).then(|| Watcher::new(cfg.watch.clone())),
+      line_output: opts.line_output,
+      options: opts.read_options,
+    };
+    let reader = spawn_reader(
+      id,
+      pair.master.as_ref(),
+      output,
+      tx.clone(),
+      running.clone(),
+    )?;
+    let writer = PtyWriter::spawn(pair.master.as_ref(), reader.nonblocking)?;
 
     {
       let tx = tx.clone();
       let running = running.clone();
       spawn(move || {
         // Block until program exits
-        let exit_code = match child.wait() {
-          Ok(status) => status.exit_code(),
-          Err(_e) => 1,
+        let status = match child.wait() {
+          Ok(status) => ExitStatus::from_pty(&status),
+          Err(_e) => ExitStatus {
+            code: 1,
+            signal: None,
+          },
         };
+        // Let the reader send the rest of the output before `Stopped`.
+        // Children of the process can keep the pty open, so the wait is
+        // limited.
+        let _r = reader.done.recv_timeout(READER_DONE_TIMEOUT);
         running.store(false, Ordering::Relaxed);
-        let _result = tx.send((id, ProcEvent::Stopped(exit_code)));
+        let _result = tx.send((id, ProcEvent::Stopped(status)));
       });
     }
 
     let inst = Inst {
       vt,
+      raw,
 
       pid,
       master: pair.master,
+      writer,
       killer,
 
       running,
@@ -155,20 +288,84 @@ impl Inst {
   }
 }
 
+/// Copies text to the clipboard and lets the user know how much was copied.
+fn copy_to_clipboard(
+  tx: &UnboundedSender<(usize, ProcEvent)>,
+  id: usize,
+  text: &str,
+) {
+  crate::clipboard::copy(text);
+  let lines = text.lines().count();
+  let msg = if lines == 1 {
+    "Copied 1 line".to_string()
+  } else {
+    format!("Copied {} lines", lines)
+  };
+  let _r = tx.send((id, ProcEvent::Flash(msg)));
+}
+
+/// Reads the clipboard with `read` in a blocking task, so that the clipboard
+/// tool doesn't stall the event loop, and sends the text back as
+/// `ProcEvent::Paste`.
+pub fn paste_clipboard(
+  tx: UnboundedSender<(usize, ProcEvent)>,
+  id: usize,
+  read: fn() -> Option<String>,
+) {
+  tokio::task::spawn_blocking(move || {
+    if let Some(text) = read() {
+      let _r = tx.send((id, ProcEvent::Paste(text)));
+    }
+  });
+}
+
+/// Adds a line separating output of the previous run from the next one, when
+/// `keep_history_on_restart` is enabled.
+fn write_restart_separator(vt: &mut vt100::Parser) {
+  let mut buf = String::new();
+  if vt.screen().alternate_screen() {
+    buf.push_str("\x1b[?1049l");
+  }
+  buf.push_str("\x1b[0m");
+  if vt.screen().cursor_position().1 > 0 {
+    buf.push_str("\r\n");
+  }
+  let time = chrono::Local::now().format("%H:%M:%S");
+  buf.push_str(&format!(
+    "\x1b[2m──── restarted at {} ────\x1b[0m\r\n",
+    time
+  ));
+  vt.process(buf.as_bytes());
+}
+
 pub struct Proc {
   pub id: usize,
   pub to_restart: bool,
+  pub cfg: ProcConfig,
   pub cmd: CommandBuilder,
   size: Size,
+  /// Width of the line numbers column. Subtracted from the pty width.
+  gutter: u16,
 
   stop_signal: StopSignal,
   mouse_scroll_speed: usize,
+  scroll_accel: ScrollAccel,
   scrollback_len: usize,
+  lazy: Option<LazyWrap>,
+  read_options: ReadOptions,
+  /// Send output lines as `ProcEvent::Output`.
+  pub line_output: Arc<AtomicBool>,
 
   pub tx: UnboundedSender<(usize, ProcEvent)>,
 
   pub inst: ProcState,
   pub copy_mode: CopyMode,
+  /// Size of the copy mode selection with the range it was computed for.
+  selection_size: Option<(Pos, Pos, SelectionSize)>,
+  /// Copy mode shows the normal screen while the alternate one is in use.
+  normal_screen_view: bool,
+  /// Last copy mode search and whether it was backward.
+  search: Option<(String, bool)>,
 }
 
 static NEXT_PROC_ID: AtomicUsize = AtomicUsize::new(1);
@@ -203,30 +400,59 @@ impl StopSignal {
       serde_yaml::Value::Mapping(map) => {
         if map.len() == 1 {
           if let Some(keys) = map.get("send-keys") {
-            let keys: Vec<Key> = serde_yaml::from_value(keys.clone())?;
+            let keys: Vec<Key> = serde_yaml::from_value(keys.clone())
+              .map_err(|err| val.error_at(err.to_string()))?;
             return Ok(Self::SendKeys(keys));
           }
         }
       }
       _ => (),
     }
-    bail!("Unexpected 'stop' value: {:?}.", val.raw());
+    bail!(val.error_at(
+      "Unexpected 'stop' value. Expected \"SIGINT\", \"SIGTERM\", \"SIGKILL\", \
+       \"hard-kill\" or {send-keys: [...]}"
+    ));
+  }
+}
+
+/// Exit codes that trigger autorestart.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum RestartOn {
+  /// Any non-zero code.
+  #[default]
+  Failure,
+  Codes(Vec<u32>),
+  /// Any non-zero code except these.
+  AllExcept(Vec<u32>),
+}
+
+impl RestartOn {
+  pub fn matches(&self, exit_code: u32) -> bool {
+    match self {
+      RestartOn::Failure => exit_code != 0,
+      RestartOn::Codes(codes) => codes.contains(&exit_code),
+      RestartOn::AllExcept(codes) => {
+        exit_code != 0 && !codes.contains(&exit_code)
+      }
+    }
   }
 }
 
 pub fn create_proc(
   name: String,
   cfg: &ProcConfig,
+  read_options: ReadOptions,
   tx: UnboundedSender<(usize, ProcEvent)>,
   size: Rect,
 ) -> ProcHandle {
-  let proc = Proc::new(cfg, tx, size);
+  let proc = Proc::new(cfg, read_options, tx, size);
   ProcHandle::from_proc(name, proc, cfg.autorestart)
 }
 
 impl Proc {
   pub fn new(
     cfg: &ProcConfig,
+    read_options: ReadOptions,
     tx: UnboundedSender<(usize, ProcEvent)>,
     size: Rect,
   ) -> Self {
@@ -235,21 +461,34 @@ impl Proc {
     let mut proc = Proc {
       id,
       to_restart: false,
+      cfg: cfg.clone(),
       cmd: cfg.into(),
       size,
+      gutter: gutter_width(cfg.line_numbers, 0),
 
       stop_signal: cfg.stop.clone(),
       mouse_scroll_speed: cfg.mouse_scroll_speed,
+      scroll_accel: ScrollAccel::default(),
       scrollback_len: cfg.scrollback_len,
+      lazy: if cfg.lazy_parse {
+        Some(LazyWrap::default())
+      } else {
+        None
+      },
+      read_options,
+      line_output: Arc::default(),
 
       tx,
 
       inst: ProcState::None,
       copy_mode: CopyMode::None(None),
+      selection_size: None,
+      normal_screen_view: false,
+      search: None,
     };
 
     if cfg.autostart {
-      proc.spawn_new_inst();
+      proc.spawn_new_inst(None);
     }
 
     proc
@@ -260,42 +499,76 @@ impl Proc {
     let proc = Self {
       id,
       to_restart: false,
+      cfg: self.cfg.clone(),
       cmd: self.cmd.clone(),
       size: self.size.clone(),
+      gutter: gutter_width(self.cfg.line_numbers, 0),
 
       stop_signal: self.stop_signal.clone(),
       mouse_scroll_speed: self.mouse_scroll_speed,
+      scroll_accel: ScrollAccel::default(),
       scrollback_len: self.scrollback_len,
+      lazy: self.lazy.as_ref().map(|_| LazyWrap::default()),
+      read_options: self.read_options,
+      line_output: Arc::new(AtomicBool::new(
+        self.line_output.load(Ordering::Relaxed),
+      )),
 
       tx: self.tx.clone(),
 
       inst: ProcState::None,
       copy_mode: CopyMode::None(None),
+      selection_size: None,
+      normal_screen_view: false,
+      search: None,
     };
     proc
   }
 
-  fn spawn_new_inst(&mut self) {
+  fn spawn_new_inst(&mut self, history: Option<VtWrap>) {
     assert_matches!(self.inst, ProcState::None);
 
-    let spawned = Inst::spawn(
-      self.id,
-      self.cmd.clone(),
-      self.tx.clone(),
-      &self.size,
-      self.scrollback_len,
-    );
+    if let Some(lazy) = &self.lazy {
+      if let Ok(mut lazy) = lazy.lock() {
+        match &history {
+          Some(vt) => lazy.flush(vt),
+          None => lazy.buf.clear(),
+        }
+      }
+    }
+    self.gutter = gutter_width(self.cfg.line_numbers, 0);
+
+    let opts = SpawnOptions {
+      cfg: &self.cfg,
+      cmd: self.cmd.clone(),
+      scrollback_len: self.scrollback_len,
+      lazy: self.lazy.clone(),
+      line_output: self.line_output.clone(),
+      read_options: self.read_options,
+    };
+    let spawned =
+      Inst::spawn(self.id, self.tx.clone(), &self.term_size(), opts, history);
     let inst = match spawned {
       Ok(inst) => ProcState::Some(inst),
-      Err(err) => ProcState::Error(err.to_string()),
+      Err(err) => {
+        let msg = format!("Failed to start: {:#}", err);
+        let _r = self.tx.send((self.id, ProcEvent::SpawnFailed(msg.clone())));
+        ProcState::Error(msg)
+      }
     };
     self.inst = inst;
   }
 
   pub fn start(&mut self) {
     if !self.is_up() {
+      let history = match &self.inst {
+        ProcState::Some(inst) if self.cfg.keep_history_on_restart => {
+          Some(inst.vt.clone())
+        }
+        _ => None,
+      };
       self.inst = ProcState::None;
-      self.spawn_new_inst();
+      self.spawn_new_inst(history);
     }
   }
 
@@ -372,25 +645,143 @@ impl Proc {
     }
   }
 
+  /// Sends a signal requested by the user and reports the result.
+  #[cfg(unix)]
+  fn send_user_signal(&mut self, signal: Signal) {
+    let msg = if self.is_up() {
+      self.send_signal(signal.number());
+      format!("Sent {}", signal)
+    } else {
+      "Process is not running".to_string()
+    };
+    let _r = self.tx.send((self.id, ProcEvent::Flash(msg)));
+  }
+
+  #[cfg(not(unix))]
+  fn send_user_signal(&mut self, signal: Signal) {
+    let msg = match signal.name() {
+      _ if !self.is_up() => "Process is not running".to_string(),
+      "SIGTERM" | "SIGKILL" => {
+        self.kill();
+        format!("Killed process instead of sending {}", signal)
+      }
+      _ => {
+        log::warn!("{} signal is ignored on Windows", signal);
+        format!("{} is not supported on Windows", signal)
+      }
+    };
+    let _r = self.tx.send((self.id, ProcEvent::Flash(msg)));
+  }
+
   pub fn resize(&mut self, size: Rect) {
-    let size = Size::new(size);
+    self.size = Size::new(size);
     if let ProcState::Some(inst) = &self.inst {
-      inst.resize(&size);
+      inst.resize(&self.term_size());
+    }
+  }
+
+  pub fn pid(&self) -> Option<u32> {
+    match &self.inst {
+      ProcState::Some(inst) => Some(inst.pid),
+      ProcState::None | ProcState::Error(_) => None,
+    }
+  }
+
+  /// Name of the program running in the foreground of the pty, if it is not
+  /// the proc itself (e.g. a command started from a shell). Linux only.
+  pub fn foreground_cmd(&self) -> Option<String> {
+    #[cfg(target_os = "linux")]
+    if let ProcState::Some(inst) = &self.inst {
+      let pgid = inst.master.process_group_leader()?;
+      if pgid <= 0 || pgid as u32 == inst.pid {
+        return None;
+      }
+      return read_proc_name(pgid as u32);
+    }
+    None
+  }
+
+  /// Size of the pty. Smaller than the window when line numbers are shown.
+  fn term_size(&self) -> Size {
+    Size {
+      width: self.size.width.saturating_sub(self.gutter).max(3),
+      height: self.size.height,
+    }
+  }
+
+  pub fn gutter(&self) -> u16 {
+    self.gutter
+  }
+
+  /// Whether copy mode shows the normal screen hidden by a full-screen
+  /// program.
+  pub fn normal_screen_view(&self) -> bool {
+    match self.copy_mode {
+      CopyMode::None(_) => false,
+      CopyMode::Start(_, _) | CopyMode::Range(_, _, _) => {
+        self.normal_screen_view
+      }
+    }
+  }
+
+  /// Size of the text selected in copy mode. The result is cached until the
+  /// selection changes, since it is shown on every render.
+  pub fn selection_size(&mut self) -> Option<SelectionSize> {
+    let (screen, start, end) = match &self.copy_mode {
+      CopyMode::Range(screen, start, end) => (screen, start, end),
+      CopyMode::None(_) | CopyMode::Start(_, _) => return None,
+    };
+    match &self.selection_size {
+      Some((start_, end_, size)) if start_ == start && end_ == end => {
+        return Some(*size)
+      }
+      _ => (),
+    }
+    let (low, high) = Pos::to_low_high(start, end);
+    let text = screen.get_selected_text(low.x, low.y, high.x, high.y);
+    let size = SelectionSize {
+      lines: text.lines().count().max(1),
+      chars: text.chars().count(),
+    };
+    self.selection_size = Some((start.clone(), end.clone(), size));
+    Some(size)
+  }
+
+  /// Widens the line numbers column when the numbers get longer. It never
+  /// shrinks while the process is running to avoid resizing the pty back and
+  /// forth.
+  pub fn sync_gutter(&mut self) {
+    if !self.cfg.line_numbers {
+      return;
+    }
+    let lines = self.lock_vt().map_or(0, |vt| {
+      let screen = vt.screen();
+      screen.rows_above() + screen.scrollback() + screen.size().0 as usize
+    });
+    let gutter = gutter_width(true, lines);
+    if gutter > self.gutter {
+      self.gutter = gutter;
+      if let ProcState::Some(inst) = &self.inst {
+        inst.resize(&self.term_size());
+      }
     }
-    self.size = size;
   }
 
   pub fn send_key(&mut self, key: &Key) {
     if self.is_up() {
-      let application_cursor_keys = self
-        .lock_vt()
-        .map_or(false, |vt| vt.screen().application_cursor());
+      let (application_cursor_keys, kitty_flags) =
+        self.lock_vt().map_or((false, 0), |vt| {
+          let screen = vt.screen();
+          (screen.application_cursor(), screen.kitty_keyboard_flags())
+        });
       let encoder = encode_key(
         key,
         KeyCodeEncodeModes {
-          enable_csi_u_key_encoding: true,
+          enable_csi_u_key_encoding: self.cfg.kitty_keyboard
+            || kitty_flags != 0,
           application_cursor_keys,
-          newline_mode: false,
+          newline_mode: self.cfg.enter_sends,
+          key_encoding: self.cfg.key_encoding,
         },
       );
       match encoder {
@@ -404,6 +795,36 @@ impl Proc {
     }
   }
 
+  /// Sends text as if it was pasted into a terminal.
+  pub fn paste(&mut self, text: &str) {
+    let mut text = text;
+    if text.len() > PASTE_MAX_LEN {
+      log::warn!("Pasted text is too long ({} bytes), cutting.", text.len());
+      let mut end = PASTE_MAX_LEN;
+      while !text.is_char_boundary(end) {
+        end -= 1;
+      }
+      text = &text[..end];
+    }
+
+    let bracketed_paste = self
+      .lock_vt()
+      .map_or(false, |vt| vt.screen().bracketed_paste());
+    let mut bytes = Vec::with_capacity(text.len() + 12);
+    if bracketed_paste {
+      // Don't let the pasted text end the paste early.
+      let text = text.replace("\x1b[201~", "");
+      bytes.extend_from_slice(b"\x1b[200~");
+      bytes.extend_from_slice(text.as_bytes());
+      bytes.extend_from_slice(b"\x1b[201~");
+    } else {
+      // Terminals send CR for new lines when pasting.
+      let text = text.replace("\r\n", "\r").replace('\n', "\r");
+      bytes.extend_from_slice(text.as_bytes());
+    }
+    self.write_all(&bytes);
+  }
+
   pub fn write_all(&mut self, bytes: &[u8]) {
     if self.is_up() {
       if let Some(mut vt) = self.lock_vt_mut() {
@@ -412,11 +833,48 @@ impl Proc {
         }
       }
       if let ProcState::Some(inst) = &mut self.inst {
-        inst.master.write_all(bytes).log_ignore();
+        inst.writer.write(bytes);
       }
     }
   }
 
+  /// Unlike `write_all`, doesn't scroll to the bottom.
+  pub fn write_reply(&mut self, bytes: &[u8]) {
+    if let ProcState::Some(inst) = &self.inst {
+      inst.writer.write(bytes);
+    }
+  }
+
+  /// Hidden procs don't parse their output when `lazy_parse` is enabled.
+  /// Becoming visible parses the buffered output.
+  pub fn set_visible(&mut self, visible: bool) {
+    if let Some(lazy) = &self.lazy {
+      if let Ok(mut lazy) = lazy.lock() {
+        lazy.visible = visible;
+        if visible {
+          if let ProcState::Some(inst) = &self.inst {
+            lazy.flush(&inst.vt);
+          }
+        }
+      }
+    }
+  }
+
+  fn flush_lazy(&mut self) {
+    if let (Some(lazy), ProcState::Some(inst)) = (&self.lazy, &self.inst) {
+      if let Ok(mut lazy) = lazy.lock() {
+        lazy.flush(&inst.vt);
+      }
+    }
+  }
+
+  pub fn set_scrollback_len(&mut self, len: usize) {
+    self.scrollback_len = len;
+    if let Some(mut vt) = self.lock_vt_mut() {
+      vt.set_scrollback_len(len);
+    }
+  }
+
   pub fn scroll_up_lines(&mut self, n: usize) {
     match &mut self.copy_mode {
       CopyMode::None(_) => {
@@ -463,6 +921,79 @@ impl Proc {
     screen.set_scrollback(pos);
   }
 
+  /// Scrolls to the previous or next marked row (prompt or marker) found by
+  /// `find`.
+  fn scroll_to_row(
+    &mut self,
+    dir: PromptDir,
+    find: fn(&vt100::Screen, bool) -> Option<usize>,
+  ) {
+    let forward = dir == PromptDir::Next;
+    match &mut self.copy_mode {
+      CopyMode::None(_) => {
+        if let Some(mut vt) = self.lock_vt_mut() {
+          if let Some(pos) = find(vt.screen(), forward) {
+            vt.set_scrollback(pos);
+          }
+        }
+      }
+      CopyMode::Start(screen, _) | CopyMode::Range(screen, _, _) => {
+        if let Some(pos) = find(screen, forward) {
+          screen.set_scrollback(pos);
+        }
+      }
+    }
+  }
+
+  /// Moves the copy mode cursor to the next match of the last search (in the
+  /// opposite direction if `reverse`) and scrolls the match to the middle of
+  /// the screen.
+  fn copy_mode_search(&mut self, reverse: bool) {
+    let (query, backward) = match &self.search {
+      Some(search) => search.clone(),
+      None => return,
+    };
+    match &mut self.copy_mode {
+      CopyMode::None(_) => (),
+      CopyMode::Start(screen, pos) | CopyMode::Range(screen, _, pos) => {
+        let from = (pos.y, pos.x.max(0) as u16);
+        match screen.find(&query, from, backward == reverse) {
+          Some((y, x)) => {
+            *pos = Pos { y, x: x as i32 };
+            let rows = screen.size().0 as i32;
+            screen.set_scrollback((rows / 2 - y).max(0) as usize);
+          }
+          None => {
+            let msg = format!("Pattern not found: {}", query);
+            let _r = self.tx.send((self.id, ProcEvent::Flash(msg)));
+          }
+        }
+      }
+    }
+  }
+
+  /// Copies output of the last command marked by shell integration. Copies
+  /// the visible screen if there are no marks.
+  fn copy_last_command_output(&self) {
+    let copy = |screen: &vt100::Screen| match screen.last_command_output() {
+      Some(text) => copy_to_clipboard(&self.tx, self.id, &text),
+      None => {
+        log::warn!("No command output marks found, copying the screen.");
+        copy_to_clipboard(&self.tx, self.id, &screen.contents());
+      }
+    };
+    match &self.copy_mode {
+      CopyMode::None(_) => {
+        if let Some(vt) = self.lock_vt() {
+          copy(vt.screen());
+        }
+      }
+      CopyMode::Start(screen, _) | CopyMode::Range(screen, _, _) => {
+        copy(screen)
+      }
+    }
+  }
+
   pub fn scroll_half_screen_up(&mut self) {
     self.scroll_up_lines(self.size.height as usize / 2);
   }
@@ -471,15 +1002,40 @@ impl Proc {
     self.scroll_down_lines(self.size.height as usize / 2);
   }
 
-  pub fn handle_mouse(&mut self, event: MouseEvent) {
+  /// Lines to scroll by a mouse wheel event.
+  fn wheel_scroll_lines(&mut self, up: bool) -> usize {
+    if self.cfg.scroll_acceleration {
+      self.scroll_accel.lines(
+        up,
+        self.mouse_scroll_speed,
+        self.cfg.scroll_acceleration_max,
+      )
+    } else {
+      self.mouse_scroll_speed
+    }
+  }
+
+  pub fn handle_mouse(&mut self, mut event: MouseEvent) {
+    event.x = (event.x - self.gutter as i32).max(0);
     let copy_mode = match self.copy_mode {
       CopyMode::None(_) => false,
       CopyMode::Start(_, _) | CopyMode::Range(_, _, _) => true,
     };
-    let mouse_mode = self
-      .lock_vt()
-      .map(|vt| vt.screen().mouse_protocol_mode())
-      .unwrap_or_default();
+    // With `mouse: false` the proc is treated as if it never asked for mouse
+    // events, so selection and scrolling keep working.
+    let mouse_mode = if self.cfg.mouse {
+      self
+        .lock_vt()
+        .map(|vt| vt.screen().mouse_protocol_mode())
+        .unwrap_or_default()
+    } else {
+      MouseProtocolMode::None
+    };
+    let wheel_lines = match event.kind {
+      MouseEventKind::ScrollUp => self.wheel_scroll_lines(true),
+      MouseEventKind::ScrollDown => self.wheel_scroll_lines(false),
+      _ => self.mouse_scroll_speed,
+    };
 
     if copy_mode {
       match event.kind {
@@ -522,13 +1078,13 @@ impl Proc {
         MouseEventKind::ScrollDown => match &mut self.copy_mode {
           CopyMode::None(_) => unreachable!(),
           CopyMode::Start(screen, _) | CopyMode::Range(screen, _, _) => {
-            Self::scroll_screen_down(screen, self.mouse_scroll_speed);
+            Self::scroll_screen_down(screen, wheel_lines);
           }
         },
         MouseEventKind::ScrollUp => match &mut self.copy_mode {
           CopyMode::None(_) => unreachable!(),
           CopyMode::Start(screen, _) | CopyMode::Range(screen, _, _) => {
-            Self::scroll_screen_up(screen, self.mouse_scroll_speed);
+            Self::scroll_screen_up(screen, wheel_lines);
           }
         },
         MouseEventKind::ScrollLeft => (),
@@ -547,6 +1103,13 @@ impl Proc {
                   )));
                 }
               }
+              MouseButton::Middle if self.cfg.middle_click_paste => {
+                paste_clipboard(
+                  self.tx.clone(),
+                  self.id,
+                  crate::clipboard::paste_primary,
+                );
+              }
               MouseButton::Right | MouseButton::Middle => (),
             },
             MouseEventKind::Up(_) => (),
@@ -563,18 +1126,19 @@ impl Proc {
                     unreachable!()
                   }
                 };
+                self.selection_size = None;
               }
             }
             MouseEventKind::Drag(_) => (),
             MouseEventKind::Moved => (),
             MouseEventKind::ScrollDown => {
               if let Some(mut vt) = inst.vt.write().log_get() {
-                Self::scroll_vt_down(&mut vt, self.mouse_scroll_speed);
+                Self::scroll_vt_down(&mut vt, wheel_lines);
               }
             }
             MouseEventKind::ScrollUp => {
               if let Some(mut vt) = inst.vt.write().log_get() {
-                Self::scroll_vt_up(&mut vt, self.mouse_scroll_speed);
+                Self::scroll_vt_up(&mut vt, wheel_lines);
               }
             }
             MouseEventKind::ScrollLeft => (),
@@ -585,7 +1149,7 @@ impl Proc {
           | MouseProtocolMode::ButtonMotion
           | MouseProtocolMode::AnyMotion => {
             let seq = encode_mouse_event(event);
-            let _r = inst.master.write_all(seq.as_bytes());
+            inst.writer.write(seq.as_bytes());
           }
         }
       }
@@ -595,6 +1159,10 @@ impl Proc {
 
 impl Proc {
   pub fn handle_cmd(&mut self, cmd: ProcCmd) {
+    // Commands may read the screen (scrolling, copy mode) or change its size,
+    // so buffered output has to be parsed first.
+    self.flush_lazy();
+
     match cmd {
       ProcCmd::Start => self.start(),
       ProcCmd::Stop => self.stop(),
@@ -602,24 +1170,74 @@ impl Proc {
 
       ProcCmd::SendKey(key) => self.send_key(&key),
       ProcCmd::SendMouse(event) => self.handle_mouse(event),
+      ProcCmd::Paste(text) => self.paste(&text),
 
       ProcCmd::ScrollUp => self.scroll_half_screen_up(),
       ProcCmd::ScrollDown => self.scroll_half_screen_down(),
       ProcCmd::ScrollUpLines { n } => self.scroll_up_lines(n),
       ProcCmd::ScrollDownLines { n } => self.scroll_down_lines(n),
+      ProcCmd::ScrollToPrompt { dir } => {
+        self.scroll_to_row(dir, vt100::Screen::prompt_scrollback)
+      }
+      ProcCmd::AddMarker { label } => {
+        if let Some(mut vt) = self.lock_vt_mut() {
+          vt.add_marker(&format!("◆ {}", label));
+        }
+      }
+      ProcCmd::ScrollToMarker { dir } => {
+        self.scroll_to_row(dir, vt100::Screen::marker_scrollback)
+      }
+      ProcCmd::ClearMarkers => {
+        if let Some(mut vt) = self.lock_vt_mut() {
+          vt.clear_markers();
+        }
+      }
+      ProcCmd::SetScrollback { n } => self.set_scrollback_len(n),
+      ProcCmd::ResetDecodeErrors => {
+        if let Some(mut vt) = self.lock_vt_mut() {
+          vt.reset_errors();
+        }
+      }
 
       ProcCmd::CopyModeEnter => match &mut self.inst {
         ProcState::None => (),
+        // Raw output is not displayed, so there is nothing to select. Copy
+        // all of it instead.
+        ProcState::Some(Inst { raw: Some(raw), .. }) => {
+          if let Ok(raw) = raw.lock() {
+            let text = String::from_utf8_lossy(&raw);
+            copy_to_clipboard(&self.tx, self.id, &text);
+          }
+        }
         ProcState::Some(inst) => {
           let screen = inst.vt.read().unwrap().screen().clone();
           let y = (screen.size().0 - 1) as i32;
           self.copy_mode = CopyMode::Start(screen, Pos { y, x: 0 });
+          self.selection_size = None;
+          self.normal_screen_view = false;
         }
         ProcState::Error(_) => (),
       },
       ProcCmd::CopyModeLeave => {
         self.copy_mode = CopyMode::None(None);
       }
+      ProcCmd::ToggleAltView => {
+        if self.normal_screen_view() {
+          self.copy_mode = CopyMode::None(None);
+        } else if let ProcState::Some(inst) = &self.inst {
+          let vt = inst.vt.read().unwrap();
+          if vt.screen().alternate_screen() {
+            let screen = vt.screen().normal_screen();
+            let y = (screen.size().0 - 1) as i32;
+            self.copy_mode = CopyMode::Start(screen, Pos { y, x: 0 });
+            self.selection_size = None;
+            self.normal_screen_view = true;
+          } else {
+            let msg = "Alternate screen is not in use".to_string();
+            let _r = self.tx.send((self.id, ProcEvent::Flash(msg)));
+          }
+        }
+      }
       ProcCmd::CopyModeMove { dir } => match &self.inst {
         ProcState::None => (),
         ProcState::Some(inst) => {
@@ -627,7 +1245,8 @@ impl Proc {
           let screen = vt.screen();
           match &mut self.copy_mode {
             CopyMode::None(_) => (),
-            CopyMode::Start(_, pos_) | CopyMode::Range(_, _, pos_) => {
+            CopyMode::Start(copy_screen, pos_)
+            | CopyMode::Range(copy_screen, _, pos_) => {
               match dir {
                 CopyMove::Up => {
                   if pos_.y > -(screen.scrollback_len() as i32) {
@@ -650,6 +1269,7 @@ impl Proc {
                   }
                 }
               };
+              scroll_to_cursor(copy_screen, pos_, self.cfg.copy_scrolloff);
             }
           }
         }
@@ -666,13 +1286,23 @@ impl Proc {
       ProcCmd::CopyModeCopy => {
         if let CopyMode::Range(screen, start, end) = &self.copy_mode {
           let (low, high) = Pos::to_low_high(start, end);
-          let text = screen.get_selected_text(low.x, low.y, high.x, high.y);
-
-          // TODO: send copy event instead
-          crate::clipboard::copy(text.as_str());
+          let mut text = screen.get_selected_text(low.x, low.y, high.x, high.y);
+          if self.cfg.dedup_copy {
+            text = dedup_text(&text);
+          }
+          copy_to_clipboard(&self.tx, self.id, &text);
         }
         self.copy_mode = CopyMode::None(None);
       }
+      ProcCmd::CopyModeSearch { query, backward } => {
+        self.search = Some((query, backward));
+        self.copy_mode_search(false);
+      }
+      ProcCmd::CopyModeSearchAgain { reverse } => {
+        self.copy_mode_search(reverse)
+      }
+      ProcCmd::CopyLastCommandOutput => self.copy_last_command_output(),
+      ProcCmd::SendSignal(signal) => self.send_user_signal(signal),
 
       ProcCmd::Resize { x, y, w, h } => self.resize(Rect {
         x,
@@ -684,6 +1314,51 @@ impl Proc {
   }
 }
 
+/// Scrolls the copy mode screen to keep `scrolloff` lines visible above and
+/// below the cursor, like in vim.
+fn scroll_to_cursor(screen: &mut vt100::Screen, pos: &Pos, scrolloff: usize) {
+  let rows = screen.size().0 as i32;
+  let scrolloff = (scrolloff as i32).min((rows - 1) / 2);
+  let scrollback = screen.scrollback() as i32;
+  let row = pos.y + scrollback;
+  if row < scrolloff {
+    screen.set_scrollback((scrollback + scrolloff - row) as usize);
+  } else if row > rows - 1 - scrolloff {
+    let scrollback = scrollback - (row - (rows - 1 - scrolloff));
+    screen.set_scrollback(scrollback.max(0) as usize);
+  }
+}
+
+#[cfg(target_os = "linux")]
+fn read_proc_name(pid: u32) -> Option<String> {
+  let cmdline = std::fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+  let arg0 = cmdline.split(|b| *b == 0).next().unwrap_or_default();
+  let arg0 = String::from_utf8_lossy(arg0);
+  let name = arg0.rsplit('/').next().unwrap_or_default();
+  if !name.is_empty() {
+    return Some(name.to_string());
+  }
+  // Kernel threads and zombies have empty cmdline.
+  let comm = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+  Some(comm.trim_end().to_string())
+}
+
+/// Max size of pasted text. Input is queued until the process reads it, so
+/// huge pastes would take a lot of memory.
+const PASTE_MAX_LEN: usize = 1024 * 1024;
+
+/// Line numbers column has room for at least this many digits.
+const LINE_NUMBERS_MIN_DIGITS: usize = 4;
+
+fn gutter_width(line_numbers: bool, lines: usize) -> u16 {
+  if line_numbers {
+    let digits = lines.to_string().len().max(LINE_NUMBERS_MIN_DIGITS);
+    digits as u16 + 1
+  } else {
+    0
+  }
+}
+
 fn translate_mouse_pos(event: &MouseEvent, scrollback: usize) -> Pos {
   Pos {
     y: event.y - scrollback as i32,
@@ -712,6 +1387,13 @@ pub enum CopyMode {
   Range(vt100::Screen, Pos, Pos),
 }
 
+/// Number of lines and chars selected in copy mode.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SelectionSize {
+  pub lines: usize,
+  pub chars: usize,
+}
+
 impl Default for CopyMode {
   fn default() -> Self {
     CopyMode::None(None)
@@ -762,3 +1444,68 @@ impl Pos {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn restart_on() {
+    assert!(!RestartOn::Failure.matches(0));
+    assert!(RestartOn::Failure.matches(1));
+
+    let on = RestartOn::Codes(vec![137]);
+    assert!(on.matches(137));
+    assert!(!on.matches(1));
+    assert!(!on.matches(0));
+
+    let unless = RestartOn::AllExcept(vec![1]);
+    assert!(!unless.matches(1));
+    assert!(!unless.matches(0));
+    assert!(unless.matches(137));
+  }
+
+  #[test]
+  fn lazy_escapes() {
+    let mut scan = EscScan::default();
+    assert!(!scan.feed(b"\x1b[1;31mred\x1b[0m\x1b[2J"));
+    assert!(scan.feed(b"\x1b[6n"));
+    assert!(scan.feed(b"\x1b[>q"));
+    assert!(scan.feed(b"\x1bP$qm\x1b\\"));
+    // Split across reads.
+    assert!(!scan.feed(b"text\x1b"));
+    assert!(scan.feed(b"]0;title\x07"));
+    assert!(!scan.feed(b"\x1b[1"));
+    assert!(scan.feed(b"8t"));
+  }
+
+  #[test]
+  fn restart_separator() {
+    let mut vt = vt100::Parser::new(5, 40, 100);
+    vt.process(b"first run");
+    write_restart_separator(&mut vt);
+    vt.process(b"second run");
+
+    let contents = vt.screen().contents();
+    let lines = contents.lines().collect::<Vec<_>>();
+    assert_eq!(lines[0], "first run");
+    assert!(lines[1].starts_with("──── restarted at "));
+    assert_eq!(lines[2], "second run");
+  }
+
+  #[test]
+  fn copy_scrolloff() {
+    let mut vt = vt100::Parser::new(5, 10, 100);
+    for i in 0..20 {
+      vt.process(format!("{}\r\n", i).as_bytes());
+    }
+    let mut screen = vt.screen().clone();
+
+    scroll_to_cursor(&mut screen, &Pos { y: 1, x: 0 }, 2);
+    assert_eq!(screen.scrollback(), 1);
+    scroll_to_cursor(&mut screen, &Pos { y: -3, x: 0 }, 2);
+    assert_eq!(screen.scrollback(), 5);
+    scroll_to_cursor(&mut screen, &Pos { y: 4, x: 0 }, 2);
+    assert_eq!(screen.scrollback(), 0);
+  }
+}
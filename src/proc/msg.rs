@@ -1,4 +1,10 @@
-use crate::{event::CopyMove, key::Key, mouse::MouseEvent};
+use crate::{
+  event::{CopyMove, PromptDir},
+  key::Key,
+  mouse::MouseEvent,
+  proc::signal::Signal,
+  watch::WatchAction,
+};
 
 #[derive(Debug)]
 pub enum ProcCmd {
@@ -8,17 +14,29 @@ pub enum ProcCmd {
 
   SendKey(Key),
   SendMouse(MouseEvent),
+  Paste(String),
 
   ScrollUp,
   ScrollDown,
   ScrollUpLines { n: usize },
   ScrollDownLines { n: usize },
+  ScrollToPrompt { dir: PromptDir },
+  AddMarker { label: String },
+  ScrollToMarker { dir: PromptDir },
+  ClearMarkers,
+  SetScrollback { n: usize },
+  ResetDecodeErrors,
 
   CopyModeEnter,
   CopyModeLeave,
+  ToggleAltView,
   CopyModeMove { dir: CopyMove },
   CopyModeEnd,
   CopyModeCopy,
+  CopyModeSearch { query: String, backward: bool },
+  CopyModeSearchAgain { reverse: bool },
+  CopyLastCommandOutput,
+  SendSignal(Signal),
 
   Resize { x: u16, y: u16, w: u16, h: u16 },
 }
@@ -26,6 +44,70 @@ pub enum ProcCmd {
 #[derive(Debug)]
 pub enum ProcEvent {
   Render,
-  Stopped(u32),
+  Stopped(ExitStatus),
   Started,
+  SpawnFailed(String),
+  /// Delayed autostart (`start_delay` or `start_stagger_ms`) is due.
+  ScheduledStart,
+  /// Output line matched a watch pattern.
+  Watch(WatchAction, String),
+  /// Response to a terminal query to be written to the pty.
+  Reply(Vec<u8>),
+  /// Message to show to the user for a moment.
+  Flash(String),
+  /// Clipboard text read in the background to be pasted into the proc.
+  Paste(String),
+  /// Line of output without escape sequences. Sent only when line output is
+  /// enabled for the proc (headless mode).
+  Output(String),
+}
+
+/// How a process finished.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExitStatus {
+  /// Processes terminated by a signal get `128 + signal` like in shells.
+  pub code: u32,
+  /// Signal that terminated the process. Always `None` on Windows.
+  pub signal: Option<i32>,
+}
+
+impl ExitStatus {
+  pub fn from_pty(status: &portable_pty::ExitStatus) -> Self {
+    match status.signal_number() {
+      Some(signal) => ExitStatus {
+        code: 128 + signal as u32,
+        signal: Some(signal),
+      },
+      None => ExitStatus {
+        code: status.exit_code(),
+        signal: None,
+      },
+    }
+  }
+
+  /// Signal name, e.g. "SIGTERM".
+  pub fn signal_name(&self) -> Option<String> {
+    let signal = self.signal?;
+    #[cfg(unix)]
+    let name = match signal {
+      libc::SIGHUP => Some("SIGHUP"),
+      libc::SIGINT => Some("SIGINT"),
+      libc::SIGQUIT => Some("SIGQUIT"),
+      libc::SIGILL => Some("SIGILL"),
+      libc::SIGABRT => Some("SIGABRT"),
+      libc::SIGBUS => Some("SIGBUS"),
+      libc::SIGFPE => Some("SIGFPE"),
+      libc::SIGKILL => Some("SIGKILL"),
+      libc::SIGSEGV => Some("SIGSEGV"),
+      libc::SIGPIPE => Some("SIGPIPE"),
+      libc::SIGALRM => Some("SIGALRM"),
+      libc::SIGTERM => Some("SIGTERM"),
+      libc::SIGUSR1 => Some("SIGUSR1"),
+      libc::SIGUSR2 => Some("SIGUSR2"),
+      _ => None,
+    };
+    #[cfg(not(unix))]
+    let name: Option<&str> = None;
+    Some(name.map_or_else(|| format!("signal {}", signal), str::to_string))
+  }
 }
@@ -0,0 +1,383 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use portable_pty::MasterPty;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::spawn_blocking;
+
+use crate::watch::{LineSplitter, Watcher};
+
+use super::msg::ProcEvent;
+use super::{LazyWrap, RawWrap, VtWrap};
+
+pub const DEFAULT_READ_BUF_LEN: usize = 4 * 1024;
+pub const READ_BUF_LEN_MIN: usize = 1024;
+pub const READ_BUF_LEN_MAX: usize = 1024 * 1024;
+/// Max amount of output parsed at once (unless reads are bigger).
+const READ_BATCH_LEN: usize = 64 * 1024;
+pub const DEFAULT_READ_BATCH_DELAY_MS: u64 = 2;
+pub const READ_BATCH_DELAY_MAX_MS: u64 = 1000;
+
+/// Started pty reader.
+pub struct Reader {
+  /// The pty was switched to non-blocking mode for async reading.
+  pub nonblocking: bool,
+  /// Disconnects once all output was read and processed.
+  pub done: std::sync::mpsc::Receiver<()>,
+}
+
+/// How output of a process is read (`pty_read_buf` and `read_batch_delay_ms`
+/// settings).
+#[derive(Clone, Copy, Debug)]
+pub struct ReadOptions {
+  /// Max amount of output read from the pty at once.
+  pub buf_len: usize,
+  /// Max time to wait for more output before parsing what was read.
+  pub batch_delay: Duration,
+}
+
+impl ReadOptions {
+  fn batch_len(&self) -> usize {
+    READ_BATCH_LEN.max(self.buf_len)
+  }
+}
+
+/// Output state of a process instance passed to the reader.
+pub struct ProcOutput {
+  pub vt: VtWrap,
+  pub lazy: Option<LazyWrap>,
+  pub raw: Option<RawWrap>,
+  pub watcher: Option<Watcher>,
+  /// Whether to send output lines as `ProcEvent::Output`.
+  pub line_output: Arc<AtomicBool>,
+  pub options: ReadOptions,
+}
+
+/// Reads process output, passes it to the parser and sends
+/// `ProcEvent::Render`. On unix the pty is read asynchronously, so idle procs
+/// don't hold a thread. Otherwise (or if the async setup fails) blocking
+/// reader threads are used.
+pub fn spawn_reader(
+  id: usize,
+  master: &dyn MasterPty,
+  output: ProcOutput,
+  tx: UnboundedSender<(usize, ProcEvent)>,
+  running: Arc<AtomicBool>,
+) -> anyhow::Result<Reader> {
+  let (done_tx, done) = std::sync::mpsc::channel();
+  let options = output.options;
+  let output = Output {
+    vt: output.vt,
+    lazy: output.lazy,
+    raw: output.raw,
+    watcher: output.watcher.map(|watcher| Arc::new(Mutex::new(watcher))),
+    line_output: output.line_output,
+    lines: Default::default(),
+  };
+
+  #[cfg(unix)]
+  if let Some(fd) = master.as_raw_fd() {
+    let spawned = unix::spawn_async_reader(
+      id,
+      fd,
+      output.clone(),
+      options,
+      tx.clone(),
+      done_tx.clone(),
+    );
+    match spawned {
+      Ok(()) => {
+        return Ok(Reader {
+          nonblocking: true,
+          done,
+        })
+      }
+      Err(err) => {
+        log::warn!("Async pty reader failed, using threads: {}", err);
+      }
+    }
+  }
+
+  spawn_thread_reader(id, master, output, options, tx, running, done_tx)?;
+  Ok(Reader {
+    nonblocking: false,
+    done,
+  })
+}
+
+/// Where the read output goes.
+#[derive(Clone)]
+struct Output {
+  vt: VtWrap,
+  lazy: Option<LazyWrap>,
+  raw: Option<RawWrap>,
+  watcher: Option<Arc<Mutex<Watcher>>>,
+  /// Whether to send output lines as `ProcEvent::Output`.
+  line_output: Arc<AtomicBool>,
+  lines: Arc<Mutex<LineSplitter>>,
+}
+
+impl Output {
+  /// Passes output to the parser and sends events for lines matching watch
+  /// patterns (or for all lines if line output is enabled).
+  fn process(
+    &self,
+    id: usize,
+    bytes: &[u8],
+    tx: &UnboundedSender<(usize, ProcEvent)>,
+  ) {
+    if let Some(watcher) = &self.watcher {
+      if let Ok(mut watcher) = watcher.lock() {
+        for (action, line) in watcher.feed(bytes) {
+          let _r = tx.send((id, ProcEvent::Watch(action, line)));
+        }
+      }
+    }
+
+    if self.line_output.load(Ordering::Relaxed) {
+      if let Ok(mut lines) = self.lines.lock() {
+        for line in lines.feed(bytes) {
+          let _r = tx.send((id, ProcEvent::Output(line)));
+        }
+      }
+    }
+
+    if let Some(raw) = &self.raw {
+      if let Ok(mut raw) = raw.lock() {
+        raw.extend_from_slice(bytes);
+      }
+      return;
+    }
+
+    match &self.lazy {
+      Some(lazy) => {
+        if let Ok(mut lazy) = lazy.lock() {
+          lazy.process(&self.vt, bytes);
+        }
+      }
+      None => {
+        if let Ok(mut vt) = self.vt.write() {
+          vt.process(bytes);
+        }
+      }
+    }
+  }
+
+  /// Sends the last output line even if it doesn't end with a newline.
+  fn finish(&self, id: usize, tx: &UnboundedSender<(usize, ProcEvent)>) {
+    if self.line_output.load(Ordering::Relaxed) {
+      if let Ok(mut lines) = self.lines.lock() {
+        if let Some(line) = lines.finish() {
+          let _r = tx.send((id, ProcEvent::Output(line)));
+        }
+      }
+    }
+  }
+}
+
+#[cfg(unix)]
+pub(super) use unix::dup_nonblocking;
+
+fn spawn_thread_reader(
+  id: usize,
+  master: &dyn MasterPty,
+  output: Output,
+  options: ReadOptions,
+  tx: UnboundedSender<(usize, ProcEvent)>,
+  running: Arc<AtomicBool>,
+  done: std::sync::mpsc::Sender<()>,
+) -> anyhow::Result<()> {
+  let mut reader = master.try_clone_reader()?;
+
+  let (chunk_tx, chunk_rx) = std::sync::mpsc::sync_channel::<Vec<u8>>(64);
+
+  spawn_blocking(move || {
+    let mut buf = vec![0; options.buf_len];
+    loop {
+      if !running.load(Ordering::Relaxed) {
+        break;
+      }
+
+      match reader.read(&mut buf[..]) {
+        Ok(count) => {
+          if count > 0 {
+            if chunk_tx.send(buf[..count].to_vec()).is_err() {
+              break;
+            }
+          } else {
+            thread::sleep(Duration::from_millis(10));
+          }
+        }
+        _ => break,
+      }
+    }
+  });
+
+  spawn_blocking(move || {
+    let batch_len = options.batch_len();
+    let mut pending = Vec::with_capacity(batch_len);
+    while let Ok(chunk) = chunk_rx.recv() {
+      // A full read means that more output is likely on the way. Wait a bit
+      // for it to parse and render everything at once. Smaller reads (e.g.
+      // echo of typed keys) are handled right away.
+      let burst = chunk.len() == options.buf_len;
+      pending.extend_from_slice(&chunk);
+      let deadline = Instant::now() + options.batch_delay;
+      while pending.len() < batch_len {
+        let next = if burst {
+          let now = Instant::now();
+          if now >= deadline {
+            break;
+          }
+          chunk_rx.recv_timeout(deadline - now).ok()
+        } else {
+          chunk_rx.try_recv().ok()
+        };
+        match next {
+          Some(chunk) => pending.extend_from_slice(&chunk),
+          None => break,
+        }
+      }
+
+      output.process(id, &pending, &tx);
+      pending.clear();
+
+      match tx.send((id, ProcEvent::Render)) {
+        Ok(_) => (),
+        Err(_) => break,
+      }
+    }
+    output.finish(id, &tx);
+    drop(done);
+  });
+
+  Ok(())
+}
+
+#[cfg(unix)]
+mod unix {
+  use std::io;
+  use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+  use tokio::io::unix::AsyncFd;
+  use tokio::sync::mpsc::UnboundedSender;
+
+  use super::{Output, ProcEvent, ReadOptions};
+
+  pub fn spawn_async_reader(
+    id: usize,
+    fd: RawFd,
+    output: Output,
+    options: ReadOptions,
+    tx: UnboundedSender<(usize, ProcEvent)>,
+    done: std::sync::mpsc::Sender<()>,
+  ) -> anyhow::Result<()> {
+    let fd = AsyncFd::new(dup_nonblocking(fd)?)?;
+
+    tokio::spawn(async move {
+      let batch_len = options.batch_len();
+      let mut buf = vec![0; options.buf_len];
+      let mut pending = Vec::with_capacity(batch_len);
+      loop {
+        let count = match read_async(&fd, &mut buf).await {
+          Ok(0) | Err(_) => break,
+          Ok(count) => count,
+        };
+        pending.extend_from_slice(&buf[..count]);
+
+        // Same batching as in the thread reader: wait for more output only
+        // after a full read.
+        let burst = count == options.buf_len;
+        let deadline = tokio::time::Instant::now() + options.batch_delay;
+        let mut eof = false;
+        while pending.len() < batch_len {
+          let result = if burst {
+            let read = read_async(&fd, &mut buf);
+            match tokio::time::timeout_at(deadline, read).await {
+              Ok(result) => result,
+              Err(_elapsed) => break,
+            }
+          } else {
+            match read_fd(fd.get_ref(), &mut buf) {
+              Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+              result => result,
+            }
+          };
+          match result {
+            Ok(0) | Err(_) => {
+              eof = true;
+              break;
+            }
+            Ok(count) => pending.extend_from_slice(&buf[..count]),
+          }
+        }
+
+        output.process(id, &pending, &tx);
+        pending.clear();
+
+        if tx.send((id, ProcEvent::Render)).is_err() || eof {
+          break;
+        }
+      }
+      output.finish(id, &tx);
+      drop(done);
+    });
+
+    Ok(())
+  }
+
+  /// Duplicates the fd and switches it to non-blocking mode. The flag is
+  /// shared with the master fd, so the pty has to be written asynchronously
+  /// too (see `PtyWriter`).
+  pub fn dup_nonblocking(fd: RawFd) -> io::Result<OwnedFd> {
+    let fd = unsafe { libc::dup(fd) };
+    if fd < 0 {
+      return Err(io::Error::last_os_error());
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    let flags = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GETFL) };
+    if flags < 0 {
+      return Err(io::Error::last_os_error());
+    }
+    let flags = flags | libc::O_NONBLOCK;
+    if unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFL, flags) } < 0 {
+      return Err(io::Error::last_os_error());
+    }
+    Ok(fd)
+  }
+
+  async fn read_async(
+    fd: &AsyncFd<OwnedFd>,
+    buf: &mut [u8],
+  ) -> io::Result<usize> {
+    loop {
+      let mut guard = fd.readable().await?;
+      match guard.try_io(|fd| read_fd(fd.get_ref(), buf)) {
+        Ok(result) => return result,
+        Err(_would_block) => continue,
+      }
+    }
+  }
+
+  fn read_fd(fd: &OwnedFd, buf: &mut [u8]) -> io::Result<usize> {
+    let count = unsafe {
+      libc::read(
+        fd.as_raw_fd(),
+        buf.as_mut_ptr() as *mut libc::c_void,
+        buf.len(),
+      )
+    };
+    if count < 0 {
+      let err = io::Error::last_os_error();
+      match err.raw_os_error() {
+        // EIO means that the slave side was closed.
+        Some(libc::EIO) => Ok(0),
+        _ => Err(err),
+      }
+    } else {
+      Ok(count as usize)
+    }
+  }
+}
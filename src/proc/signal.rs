@@ -0,0 +1,143 @@
+use std::fmt::Display;
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
+/// Signals that can be sent to a process with the `send-signal` command.
+const SIGNALS: &[&str] = &[
+  "SIGHUP", "SIGINT", "SIGQUIT", "SIGILL", "SIGABRT", "SIGKILL", "SIGUSR1",
+  "SIGUSR2", "SIGPIPE", "SIGALRM", "SIGTERM", "SIGCONT", "SIGSTOP", "SIGTSTP",
+  "SIGWINCH",
+];
+
+/// Signal sent to a process by the `send-signal` command. It is kept by name,
+/// so that the same keymap works on all platforms.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Signal {
+  name: &'static str,
+}
+
+impl Signal {
+  /// Parses a signal name with or without the "SIG" prefix (e.g. "SIGQUIT"
+  /// or "quit") or a signal number.
+  pub fn parse(text: &str) -> anyhow::Result<Self> {
+    if let Ok(number) = text.parse::<i32>() {
+      return Self::from_number(number);
+    }
+    let upper = text.trim().to_ascii_uppercase();
+    let full = if upper.starts_with("SIG") {
+      upper
+    } else {
+      format!("SIG{}", upper)
+    };
+    match SIGNALS.iter().copied().find(|&name| name == full) {
+      Some(name) => Ok(Signal { name }),
+      None => bail!("Unknown signal: \"{}\".", text),
+    }
+  }
+
+  #[cfg(unix)]
+  fn from_number(number: i32) -> anyhow::Result<Self> {
+    let found = SIGNALS
+      .iter()
+      .copied()
+      .find(|&name| Signal { name }.number() == number);
+    match found {
+      Some(name) => Ok(Signal { name }),
+      None => bail!("Unsupported signal number: {}.", number),
+    }
+  }
+
+  #[cfg(not(unix))]
+  fn from_number(number: i32) -> anyhow::Result<Self> {
+    bail!("Signal numbers are not supported on Windows: {}.", number);
+  }
+
+  pub fn name(&self) -> &'static str {
+    self.name
+  }
+
+  #[cfg(unix)]
+  pub fn number(&self) -> libc::c_int {
+    match self.name {
+      "SIGHUP" => libc::SIGHUP,
+      "SIGINT" => libc::SIGINT,
+      "SIGQUIT" => libc::SIGQUIT,
+      "SIGILL" => libc::SIGILL,
+      "SIGABRT" => libc::SIGABRT,
+      "SIGKILL" => libc::SIGKILL,
+      "SIGUSR1" => libc::SIGUSR1,
+      "SIGUSR2" => libc::SIGUSR2,
+      "SIGPIPE" => libc::SIGPIPE,
+      "SIGALRM" => libc::SIGALRM,
+      "SIGTERM" => libc::SIGTERM,
+      "SIGCONT" => libc::SIGCONT,
+      "SIGSTOP" => libc::SIGSTOP,
+      "SIGTSTP" => libc::SIGTSTP,
+      "SIGWINCH" => libc::SIGWINCH,
+      _ => unreachable!("Signal not in SIGNALS: {}", self.name),
+    }
+  }
+}
+
+impl Display for Signal {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(self.name)
+  }
+}
+
+impl Serialize for Signal {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: serde::Serializer,
+  {
+    serializer.serialize_str(self.name)
+  }
+}
+
+impl<'de> Deserialize<'de> for Signal {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: serde::Deserializer<'de>,
+  {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+      Number(i32),
+      Name(String),
+    }
+
+    let text = match Raw::deserialize(deserializer)? {
+      Raw::Number(number) => number.to_string(),
+      Raw::Name(name) => name,
+    };
+    Signal::parse(text.as_str())
+      .map_err(|err| serde::de::Error::custom(err.to_string()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse() {
+    assert_eq!(Signal::parse("SIGQUIT").unwrap().name(), "SIGQUIT");
+    assert_eq!(Signal::parse("usr1").unwrap().name(), "SIGUSR1");
+    assert!(Signal::parse("SIGFOO").is_err());
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn parse_number() {
+    assert_eq!(Signal::parse("3").unwrap().name(), "SIGQUIT");
+    assert!(Signal::parse("0").is_err());
+  }
+
+  #[test]
+  fn deserialize() {
+    let signal: Signal = serde_yaml::from_str("quit").unwrap();
+    assert_eq!(signal.name(), "SIGQUIT");
+    assert!(serde_yaml::from_str::<Signal>("nope").is_err());
+  }
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// CPU and memory used by a proc together with all processes in its session.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Usage {
+  /// Percent of a single core.
+  pub cpu: f32,
+  /// Resident memory in bytes.
+  pub rss: u64,
+}
+
+/// Totals of all processes in a session.
+#[derive(Clone, Copy, Default)]
+pub struct SessionStats {
+  /// Consumed CPU time in clock ticks.
+  ticks: u64,
+  rss: u64,
+}
+
+/// Computes CPU usage from the difference between two samples.
+#[derive(Default)]
+pub struct UsageTracker {
+  last: Option<(Instant, u64)>,
+}
+
+impl UsageTracker {
+  pub fn update(&mut self, now: Instant, stats: SessionStats) -> Usage {
+    let cpu = match self.last {
+      Some((last_time, last_ticks)) => {
+        let secs = now.duration_since(last_time).as_secs_f32();
+        let ticks = stats.ticks.saturating_sub(last_ticks) as f32;
+        if secs > 0.0 {
+          ticks / clock_ticks_per_sec() / secs * 100.0
+        } else {
+          0.0
+        }
+      }
+      None => 0.0,
+    };
+    self.last = Some((now, stats.ticks));
+    Usage {
+      cpu,
+      rss: stats.rss,
+    }
+  }
+
+  pub fn reset(&mut self) {
+    self.last = None;
+  }
+}
+
+/// Reads stats of all processes grouped by session id. Procs are started in
+/// a new session, so their session id is the pid of the proc. Returns an
+/// empty map on platforms other than Linux.
+pub fn read_sessions() -> HashMap<u32, SessionStats> {
+  #[cfg(target_os = "linux")]
+  {
+    linux::read_sessions()
+  }
+  #[cfg(not(target_os = "linux"))]
+  {
+    HashMap::new()
+  }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+  use std::collections::HashMap;
+
+  use super::SessionStats;
+
+  pub fn read_sessions() -> HashMap<u32, SessionStats> {
+    let mut sessions = HashMap::new();
+    let entries = match std::fs::read_dir("/proc") {
+      Ok(entries) => entries,
+      Err(_) => return sessions,
+    };
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(0) as u64;
+    for entry in entries.flatten() {
+      let is_pid = entry
+        .file_name()
+        .to_str()
+        .map_or(false, |name| name.bytes().all(|b| b.is_ascii_digit()));
+      if !is_pid {
+        continue;
+      }
+      let stat = match std::fs::read_to_string(entry.path().join("stat")) {
+        Ok(stat) => stat,
+        Err(_) => continue,
+      };
+      if let Some((sid, ticks, rss_pages)) = parse_stat(&stat) {
+        let session: &mut SessionStats = sessions.entry(sid).or_default();
+        session.ticks += ticks;
+        session.rss += rss_pages * page_size;
+      }
+    }
+    sessions
+  }
+
+  /// Returns session id, utime + stime and rss (in pages) from
+  /// `/proc/<pid>/stat`.
+  fn parse_stat(stat: &str) -> Option<(u32, u64, u64)> {
+    // Process name can contain spaces and parens, so fields are counted from
+    // the last ')'.
+    let rest = &stat[stat.rfind(')')? + 1..];
+    let fields = rest.split_whitespace().collect::<Vec<_>>();
+    let sid = fields.get(3)?.parse().ok()?;
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    let rss = fields.get(21)?.parse::<i64>().ok()?.max(0) as u64;
+    Some((sid, utime + stime, rss))
+  }
+}
+
+fn clock_ticks_per_sec() -> f32 {
+  #[cfg(unix)]
+  {
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks > 0 {
+      return ticks as f32;
+    }
+  }
+  100.0
+}
+
+/// Formats bytes compactly, e.g. "140M".
+pub fn format_bytes(bytes: u64) -> String {
+  const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+  let mut value = bytes as f64 / 1024.0;
+  let mut unit = 0;
+  while value >= 1024.0 && unit + 1 < UNITS.len() {
+    value /= 1024.0;
+    unit += 1;
+  }
+  if value < 10.0 {
+    format!("{:.1}{}", value, UNITS[unit])
+  } else {
+    format!("{:.0}{}", value, UNITS[unit])
+  }
+}
@@ -0,0 +1,119 @@
+use std::io::Write;
+
+use portable_pty::MasterPty;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::task::spawn_blocking;
+
+/// Writes input to the pty of a process. Writes are queued and done by a
+/// separate task, so a process that doesn't read its input can't block the
+/// caller (and the UI).
+#[derive(Clone)]
+pub struct PtyWriter {
+  tx: UnboundedSender<Vec<u8>>,
+}
+
+impl PtyWriter {
+  /// `nonblocking` tells whether the pty was switched to non-blocking mode
+  /// by the async reader. Then it is written asynchronously too, otherwise
+  /// by a blocking thread.
+  pub fn spawn(
+    master: &dyn MasterPty,
+    nonblocking: bool,
+  ) -> anyhow::Result<Self> {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+
+    #[cfg(unix)]
+    if nonblocking {
+      if let Some(fd) = master.as_raw_fd() {
+        unix::spawn_async_writer(fd, rx)?;
+        return Ok(PtyWriter { tx });
+      }
+    }
+    #[cfg(not(unix))]
+    let _ = nonblocking;
+
+    spawn_thread_writer(master, rx)?;
+    Ok(PtyWriter { tx })
+  }
+
+  pub fn write(&self, bytes: &[u8]) {
+    if !bytes.is_empty() {
+      let _r = self.tx.send(bytes.to_vec());
+    }
+  }
+}
+
+fn spawn_thread_writer(
+  master: &dyn MasterPty,
+  mut rx: UnboundedReceiver<Vec<u8>>,
+) -> anyhow::Result<()> {
+  let mut writer = master.try_clone_writer()?;
+  spawn_blocking(move || {
+    while let Some(bytes) = rx.blocking_recv() {
+      if let Err(err) = writer.write_all(&bytes) {
+        log::warn!("Failed to write to pty: {}", err);
+        break;
+      }
+    }
+  });
+  Ok(())
+}
+
+#[cfg(unix)]
+mod unix {
+  use std::io;
+  use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+
+  use tokio::io::unix::AsyncFd;
+  use tokio::sync::mpsc::UnboundedReceiver;
+
+  use crate::proc::reader::dup_nonblocking;
+
+  pub fn spawn_async_writer(
+    fd: RawFd,
+    mut rx: UnboundedReceiver<Vec<u8>>,
+  ) -> anyhow::Result<()> {
+    let fd = AsyncFd::new(dup_nonblocking(fd)?)?;
+    tokio::spawn(async move {
+      while let Some(bytes) = rx.recv().await {
+        if let Err(err) = write_async(&fd, &bytes).await {
+          log::warn!("Failed to write to pty: {}", err);
+          break;
+        }
+      }
+    });
+    Ok(())
+  }
+
+  async fn write_async(
+    fd: &AsyncFd<OwnedFd>,
+    mut bytes: &[u8],
+  ) -> io::Result<()> {
+    while !bytes.is_empty() {
+      let mut guard = fd.writable().await?;
+      match guard.try_io(|fd| write_fd(fd.get_ref(), bytes)) {
+        Ok(Ok(0)) => return Err(io::ErrorKind::WriteZero.into()),
+        Ok(Ok(count)) => bytes = &bytes[count..],
+        Ok(Err(err)) if err.kind() == io::ErrorKind::Interrupted => (),
+        Ok(Err(err)) => return Err(err),
+        Err(_would_block) => continue,
+      }
+    }
+    Ok(())
+  }
+
+  fn write_fd(fd: &OwnedFd, bytes: &[u8]) -> io::Result<usize> {
+    let count = unsafe {
+      libc::write(
+        fd.as_raw_fd(),
+        bytes.as_ptr() as *const libc::c_void,
+        bytes.len(),
+      )
+    };
+    if count < 0 {
+      Err(io::Error::last_os_error())
+    } else {
+      Ok(count as usize)
+    }
+  }
+}
@@ -0,0 +1,161 @@
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use serde_yaml::Value;
+
+use crate::yaml_val::Val;
+
+/// Template for entries of the process list, e.g. `"{status} {name}"`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProcFormat(Vec<Part>);
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+  Text(String),
+  Field(Field),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Field {
+  Status,
+  Name,
+  Pid,
+  Exit,
+  Uptime,
+}
+
+impl ProcFormat {
+  pub fn parse(src: &str) -> Result<Self> {
+    let mut parts = Vec::new();
+    let mut rest = src;
+    while let Some(start) = rest.find('{') {
+      if start > 0 {
+        parts.push(Part::Text(rest[..start].to_string()));
+      }
+      let end = match rest[start..].find('}') {
+        Some(end) => start + end,
+        None => bail!("Unclosed \"{{\" in process list format: \"{}\"", src),
+      };
+      let field = match &rest[start + 1..end] {
+        "status" => Field::Status,
+        "name" => Field::Name,
+        "pid" => Field::Pid,
+        "exit" => Field::Exit,
+        "uptime" => Field::Uptime,
+        other => bail!("Unknown field in process list format: \"{}\"", other),
+      };
+      parts.push(Part::Field(field));
+      rest = &rest[end + 1..];
+    }
+    if !rest.is_empty() {
+      parts.push(Part::Text(rest.to_string()));
+    }
+    Ok(ProcFormat(parts))
+  }
+
+  /// Calls `text` for literal parts and `field` for placeholders in order.
+  pub fn render<T>(
+    &self,
+    mut text: impl FnMut(&str) -> T,
+    mut field: impl FnMut(Field) -> T,
+  ) -> Vec<T> {
+    self
+      .0
+      .iter()
+      .map(|part| match part {
+        Part::Text(s) => text(s),
+        Part::Field(f) => field(*f),
+      })
+      .collect()
+  }
+
+  /// Whether the rendered text changes over time.
+  pub fn has_uptime(&self) -> bool {
+    self.0.contains(&Part::Field(Field::Uptime))
+  }
+}
+
+/// Text of the `{status}` field.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatusGlyphs {
+  pub up: String,
+  pub down: String,
+  pub failed: String,
+}
+
+impl Default for StatusGlyphs {
+  fn default() -> Self {
+    StatusGlyphs {
+      up: "UP".to_string(),
+      down: "DOWN".to_string(),
+      failed: "DOWN".to_string(),
+    }
+  }
+}
+
+impl StatusGlyphs {
+  pub fn merge_value(&mut self, val: &Val) -> Result<()> {
+    let obj = val.as_object()?;
+    if let Some(up) = obj.get(&Value::from("up")) {
+      self.up = up.as_str()?.to_string();
+    }
+    if let Some(down) = obj.get(&Value::from("down")) {
+      self.down = down.as_str()?.to_string();
+    }
+    if let Some(failed) = obj.get(&Value::from("failed")) {
+      self.failed = failed.as_str()?.to_string();
+    }
+    Ok(())
+  }
+}
+
+/// Formats duration compactly with two most significant units, e.g. "1h05m".
+pub fn format_uptime(duration: Duration) -> String {
+  let secs = duration.as_secs();
+  let (days, hours, mins) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60);
+  if days > 0 {
+    format!("{}d{:02}h", days, hours)
+  } else if hours > 0 {
+    format!("{}h{:02}m", hours, mins)
+  } else if mins > 0 {
+    format!("{}m{:02}s", mins, secs % 60)
+  } else {
+    format!("{}s", secs)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn render() {
+    let format = ProcFormat::parse("{status} {name} ({exit})").unwrap();
+    let text = format
+      .render(
+        |s| s.to_string(),
+        |field| match field {
+          Field::Status => "UP".to_string(),
+          Field::Name => "web".to_string(),
+          _ => String::new(),
+        },
+      )
+      .concat();
+    assert_eq!(text, "UP web ()");
+    assert!(!format.has_uptime());
+  }
+
+  #[test]
+  fn parse_errors() {
+    assert!(ProcFormat::parse("{name").is_err());
+    assert!(ProcFormat::parse("{cmd}").is_err());
+  }
+
+  #[test]
+  fn uptime() {
+    assert_eq!(format_uptime(Duration::from_secs(42)), "42s");
+    assert_eq!(format_uptime(Duration::from_secs(185)), "3m05s");
+    assert_eq!(format_uptime(Duration::from_secs(3900)), "1h05m");
+    assert_eq!(format_uptime(Duration::from_secs(90000)), "1d01h");
+  }
+}
@@ -15,6 +15,11 @@ pub enum SrvToClt {
   CursorShape(CursorStyle),
   Clear,
   Flush,
+  Bell,
+  /// Desktop notification (OSC 9).
+  Notify(String),
+  /// Enables or disables mouse reporting of the terminal.
+  MouseCapture(bool),
   Quit,
 }
 
@@ -50,7 +55,12 @@ impl From<termwiz::escape::csi::CursorStyle> for CursorStyle {
 
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum CltToSrv {
-  Init { width: u16, height: u16 },
+  Init {
+    width: u16,
+    height: u16,
+    /// View-only clients are rendered to but can't send input.
+    readonly: bool,
+  },
   Key(Event),
 }
 
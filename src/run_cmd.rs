@@ -0,0 +1,59 @@
+use std::process::{Command, Stdio};
+
+use crate::placeholder::ProcVars;
+
+/// Starts a shell command that isn't managed by mprocs (`run-command`).
+/// Environment variables describe the process, e.g. `MPROCS_PROC_NAME`.
+/// Output of the command goes to the log.
+pub fn run_command(cmd: &str, vars: &ProcVars) -> anyhow::Result<()> {
+  let mut command = shell_command(cmd);
+  command
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped());
+  if let Some(name) = &vars.name {
+    command.env("MPROCS_PROC_NAME", name);
+  }
+  if let Some(pid) = vars.pid {
+    command.env("MPROCS_PROC_PID", pid.to_string());
+  }
+  if let Some(code) = vars.exit {
+    command.env("MPROCS_PROC_EXIT_CODE", code.to_string());
+  }
+  if let Some(cwd) = &vars.cwd {
+    command.env("MPROCS_PROC_CWD", cwd);
+    command.current_dir(cwd);
+  }
+
+  let child = command.spawn()?;
+  let cmd = cmd.to_string();
+  std::thread::spawn(move || match child.wait_with_output() {
+    Ok(output) => {
+      log::info!("Command \"{}\" exited with {}", cmd, output.status);
+      for line in String::from_utf8_lossy(&output.stdout).lines() {
+        log::info!("[{}] {}", cmd, line);
+      }
+      for line in String::from_utf8_lossy(&output.stderr).lines() {
+        log::warn!("[{}] {}", cmd, line);
+      }
+    }
+    Err(err) => log::error!("Command \"{}\" failed: {}", cmd, err),
+  });
+  Ok(())
+}
+
+#[cfg(unix)]
+fn shell_command(cmd: &str) -> Command {
+  let mut command = Command::new("/bin/sh");
+  command.arg("-c").arg(cmd);
+  command
+}
+
+#[cfg(windows)]
+fn shell_command(cmd: &str) -> Command {
+  use std::os::windows::process::CommandExt;
+
+  let mut command = Command::new("cmd.exe");
+  command.args(["/S", "/C"]).raw_arg(cmd);
+  command
+}
@@ -0,0 +1,212 @@
+use std::{ffi::OsString, path::Path};
+
+use anyhow::{bail, Result};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+use crate::{
+  config::{CmdConfig, Config, ProcConfig},
+  settings::Settings,
+  state::State,
+};
+
+/// Version of the session file format. Increase it when the format changes in
+/// a way older versions of mprocs can't read.
+pub const SESSION_VERSION: u64 = 1;
+
+/// Snapshot of the workspace written by the `save-session` command and read
+/// back by `--restore`. Process output is not saved, procs that were running
+/// are started again.
+#[derive(Deserialize, Serialize)]
+pub struct Session {
+  pub version: u64,
+  #[serde(default)]
+  pub selected: usize,
+  /// Name of the selected proc. Preferred over `selected` when restoring.
+  #[serde(default)]
+  pub selected_name: Option<String>,
+  #[serde(default)]
+  pub proc_list_width: Option<usize>,
+  #[serde(default)]
+  pub procs: Vec<SessionProc>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SessionProc {
+  pub name: String,
+  pub cmd: CmdConfig,
+  #[serde(default)]
+  pub cwd: Option<String>,
+  #[serde(default)]
+  pub env: Option<IndexMap<String, Option<String>>>,
+  #[serde(default)]
+  pub autorestart: bool,
+  /// Set if autostart was toggled at runtime. Otherwise procs that were
+  /// running are started.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub autostart: Option<bool>,
+  #[serde(default)]
+  pub running: bool,
+}
+
+impl Session {
+  pub fn from_state(state: &State, config: &Config) -> Self {
+    let procs = state
+      .procs
+      .iter()
+      .map(|proc| {
+        let cfg = proc.cfg();
+        SessionProc {
+          name: proc.name().to_string(),
+          cmd: cfg.cmd.clone(),
+          cwd: cfg
+            .cwd
+            .as_ref()
+            .map(|cwd| cwd.to_string_lossy().to_string()),
+          env: cfg.env.clone(),
+          autorestart: proc.autorestart,
+          autostart: proc.autostart_changed().then_some(proc.autostart()),
+          running: proc.is_up(),
+        }
+      })
+      .collect();
+
+    Session {
+      version: SESSION_VERSION,
+      selected: state.selected,
+      selected_name: state.get_current_proc().map(|p| p.name().to_string()),
+      proc_list_width: config.proc_list_width.fixed_width(),
+      procs,
+    }
+  }
+
+  pub fn save(&self, path: &Path) -> Result<()> {
+    let file = std::fs::File::create(path)?;
+    serde_yaml::to_writer(file, self)?;
+    Ok(())
+  }
+
+  pub fn load(path: &Path) -> Result<Self> {
+    let file = match std::fs::File::open(path) {
+      Ok(file) => file,
+      Err(err) => match err.kind() {
+        std::io::ErrorKind::NotFound => {
+          bail!("Session file '{}' not found.", path.display());
+        }
+        _kind => return Err(err.into()),
+      },
+    };
+    let value: Value = serde_yaml::from_reader(std::io::BufReader::new(file))?;
+
+    // Check the version before parsing the rest, so that newer files give a
+    // clear error instead of a confusing parse failure.
+    let version = match value.get("version").and_then(|v| v.as_u64()) {
+      Some(version) => version,
+      None => bail!("Session file is missing a version."),
+    };
+    if version > SESSION_VERSION {
+      bail!(
+        "Session file version {} is not supported (max supported: {}).",
+        version,
+        SESSION_VERSION
+      );
+    }
+
+    Ok(serde_yaml::from_value(value)?)
+  }
+
+  /// Replace procs from config with the saved ones. Options that are not
+  /// saved (e.g. `stop`) are taken from the config proc with the same name.
+  pub fn apply(self, config: &mut Config, settings: &Settings) {
+    let procs = self
+      .procs
+      .into_iter()
+      .map(|saved| {
+        let base = config.procs.iter().find(|p| p.name == saved.name);
+        let mut cfg = match base {
+          Some(base) => base.clone(),
+          None => ProcConfig::from_settings(
+            saved.name.clone(),
+            saved.cmd.clone(),
+            settings,
+          ),
+        };
+        cfg.name = saved.name;
+        cfg.cmd = saved.cmd;
+        cfg.cwd = saved.cwd.map(OsString::from);
+        cfg.env = saved.env;
+        cfg.autostart = saved.autostart.unwrap_or(saved.running);
+        cfg.autorestart = saved.autorestart;
+        cfg
+      })
+      .collect::<Vec<_>>();
+
+    config.selected = match &self.selected_name {
+      Some(name) => procs.iter().position(|p| &p.name == name).unwrap_or(0),
+      None if self.selected < procs.len() => self.selected,
+      None => 0,
+    };
+    config.procs = procs;
+    if let Some(width) = self.proc_list_width {
+      config.proc_list_width.set_fixed(width);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::temp_path;
+
+  #[test]
+  fn round_trip() {
+    let session = Session {
+      version: SESSION_VERSION,
+      selected: 1,
+      selected_name: Some("api".to_string()),
+      proc_list_width: Some(40),
+      procs: vec![SessionProc {
+        name: "api".to_string(),
+        cmd: CmdConfig::Cmd {
+          cmd: vec!["cargo".to_string(), "run".to_string()],
+        },
+        cwd: Some("/srv".to_string()),
+        env: Some(IndexMap::from([("PORT".to_string(), Some("80".into()))])),
+        autorestart: true,
+        autostart: Some(false),
+        running: true,
+      }],
+    };
+    let path = temp_path("session-round-trip.yaml");
+    session.save(&path).unwrap();
+    let loaded = Session::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    let loaded = loaded.unwrap();
+
+    assert_eq!(loaded.version, SESSION_VERSION);
+    assert_eq!(loaded.selected, 1);
+    assert_eq!(loaded.selected_name.as_deref(), Some("api"));
+    assert_eq!(loaded.proc_list_width, Some(40));
+    let proc = &loaded.procs[0];
+    assert_eq!(proc.name, "api");
+    assert_eq!(proc.cmd.command_line(), "cargo run");
+    assert_eq!(proc.cwd.as_deref(), Some("/srv"));
+    assert_eq!(proc.env, session.procs[0].env);
+    assert!(proc.autorestart);
+    assert_eq!(proc.autostart, Some(false));
+    assert!(proc.running);
+  }
+
+  #[test]
+  fn newer_version() {
+    let path = temp_path("session-newer-version.yaml");
+    let source = format!("version: {}\nprocs: []\n", SESSION_VERSION + 1);
+    std::fs::write(&path, source).unwrap();
+    let loaded = Session::load(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    let err = loaded.err().unwrap();
+    assert!(err.to_string().contains("is not supported"));
+  }
+}
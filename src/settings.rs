@@ -1,14 +1,29 @@
-use std::{fs::File, io::BufReader, path::PathBuf};
+use std::{fs::File, io::BufReader, path::PathBuf, time::Duration};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+use chrono::format::{Item, StrftimeItems};
 use crossterm::event::{KeyCode, KeyModifiers};
 use indexmap::IndexMap;
 use serde_yaml::Value;
+use tui::style::Color;
 
 use crate::{
-  event::{AppEvent, CopyMove},
+  event::{AppEvent, CopyMove, PromptDir},
+  highlight::{parse_color, Highlight},
   key::Key,
   keymap::Keymap,
+  proc::{
+    reader::{
+      ReadOptions, DEFAULT_READ_BATCH_DELAY_MS, DEFAULT_READ_BUF_LEN,
+      READ_BATCH_DELAY_MAX_MS, READ_BUF_LEN_MAX, READ_BUF_LEN_MIN,
+    },
+    StopSignal,
+  },
+  proc_format::{ProcFormat, StatusGlyphs},
+  theme::BorderStyle,
+  title_format::TitleFormat,
+  ui_term::ScrollbarMode,
+  watch::WatchPattern,
   yaml_val::{value_to_string, Val},
 };
 
@@ -20,7 +35,46 @@ pub struct Settings {
   pub hide_keymap_window: bool,
   pub mouse_scroll_speed: usize,
   pub scrollback_len: usize,
-  pub proc_list_width: usize,
+  pub scrollback_bytes: usize,
+  pub proc_list_width: ProcListWidth,
+  pub max_fps: usize,
+  pub lazy_parse: bool,
+  pub line_numbers: bool,
+  pub keep_history_on_restart: bool,
+  pub highlights: Vec<Highlight>,
+  pub title_format: TitleFormat,
+  pub show_foreground_cmd: bool,
+  pub show_resource_usage: bool,
+  /// Dim procs without output for this long.
+  pub dim_idle_procs: Option<Duration>,
+  pub clock_format: String,
+  pub confirm_kill: bool,
+  pub show_term_title: bool,
+  pub scrollbar: ScrollbarMode,
+  pub border_style: BorderStyle,
+  pub focus_border_color: Color,
+  pub proc_list_format: Option<ProcFormat>,
+  pub status_glyphs: StatusGlyphs,
+  pub watch_patterns: Vec<WatchPattern>,
+  pub cell_pixel_size: Option<(u16, u16)>,
+  pub tab_width: u16,
+  pub pty_read_buf: usize,
+  pub read_batch_delay_ms: u64,
+  pub middle_click_paste: bool,
+  pub scroll_acceleration: bool,
+  pub scroll_acceleration_max: usize,
+  /// Lines kept visible above and below the cursor in copy mode.
+  pub copy_scrolloff: usize,
+  pub keymap_hints: Option<Vec<AppEvent>>,
+  pub sync_focus: bool,
+  pub scroll_to_bottom_on_focus: bool,
+  pub start_stagger_ms: u64,
+  pub on_all_finished: OnAllFinished,
+  pub exit_with_child_code: bool,
+  pub paths_relative_to_config: bool,
+  /// Undefined placeholders in commands are an error instead of being kept.
+  pub strict_placeholders: bool,
+  pub default_stop: StopSignal,
 }
 
 impl Default for Settings {
@@ -32,7 +86,43 @@ impl Default for Settings {
       hide_keymap_window: false,
       mouse_scroll_speed: 5,
       scrollback_len: 1000,
-      proc_list_width: 30,
+      scrollback_bytes: 0,
+      proc_list_width: ProcListWidth::fixed(DEFAULT_PROC_LIST_WIDTH),
+      max_fps: 60,
+      lazy_parse: false,
+      line_numbers: false,
+      keep_history_on_restart: false,
+      highlights: Vec::new(),
+      title_format: TitleFormat::default(),
+      show_foreground_cmd: false,
+      show_resource_usage: false,
+      dim_idle_procs: None,
+      clock_format: String::new(),
+      confirm_kill: false,
+      show_term_title: true,
+      scrollbar: ScrollbarMode::default(),
+      border_style: BorderStyle::default(),
+      focus_border_color: Color::Reset,
+      proc_list_format: None,
+      status_glyphs: StatusGlyphs::default(),
+      watch_patterns: Vec::new(),
+      cell_pixel_size: None,
+      tab_width: 8,
+      pty_read_buf: DEFAULT_READ_BUF_LEN,
+      read_batch_delay_ms: DEFAULT_READ_BATCH_DELAY_MS,
+      middle_click_paste: false,
+      scroll_acceleration: false,
+      scroll_acceleration_max: 4,
+      copy_scrolloff: 0,
+      keymap_hints: None,
+      sync_focus: false,
+      scroll_to_bottom_on_focus: false,
+      start_stagger_ms: 0,
+      on_all_finished: OnAllFinished::default(),
+      exit_with_child_code: false,
+      paths_relative_to_config: true,
+      strict_placeholders: false,
+      default_stop: StopSignal::default(),
     };
     settings.add_defaults();
     settings
@@ -59,7 +149,7 @@ impl Settings {
     Ok(())
   }
 
-  fn get_xdg_config_path(&self) -> Option<std::path::PathBuf> {
+  pub fn get_xdg_config_path(&self) -> Option<std::path::PathBuf> {
     let mut buf = if let Ok(path) = std::env::var("XDG_CONFIG_HOME") {
       PathBuf::from(path)
     } else {
@@ -104,11 +194,14 @@ impl Settings {
         }
 
         for (key, event) in keymap {
-          let key = Key::parse(value_to_string(&key)?.as_str())?;
+          let key = Key::parse(value_to_string(&key)?.as_str())
+            .map_err(|err| event.error_at(err.to_string()))?;
           if event.raw().is_null() {
             into.shift_remove(&key);
           } else {
-            let event: AppEvent = serde_yaml::from_value(event.raw().clone())?;
+            let event: AppEvent =
+              serde_yaml::from_value(event.raw().clone())
+                .map_err(|err| event.error_at(err.to_string()))?;
             into.insert(key, event);
           }
         }
@@ -122,6 +215,19 @@ impl Settings {
     add_keys(&mut self.keymap_term, obj.get(&Value::from("keymap_term")))?;
     add_keys(&mut self.keymap_copy, obj.get(&Value::from("keymap_copy")))?;
 
+    if let Some(hints) = obj.get(&Value::from("keymap_hints")) {
+      self.keymap_hints = match hints.raw() {
+        Value::Null => None,
+        _ => Some(
+          hints
+            .as_array()?
+            .iter()
+            .map(parse_keymap_hint)
+            .collect::<Result<Vec<_>>>()?,
+        ),
+      };
+    }
+
     if let Some(hide_keymap_window) =
       obj.get(&Value::from("hide_keymap_window"))
     {
@@ -138,8 +244,208 @@ impl Settings {
       self.scrollback_len = scrollback.as_usize()?;
     }
 
+    if let Some(scrollback_bytes) = obj.get(&Value::from("scrollback_bytes")) {
+      self.scrollback_bytes = scrollback_bytes.as_usize()?;
+    }
+
     if let Some(proc_list_width) = obj.get(&Value::from("proc_list_width")) {
-      self.proc_list_width = proc_list_width.as_usize()?;
+      self.proc_list_width = ProcListWidth::from_val(proc_list_width)?;
+    }
+
+    if let Some(max_fps) = obj.get(&Value::from("max_fps")) {
+      self.max_fps = max_fps.as_usize()?.max(1);
+    }
+
+    if let Some(lazy_parse) = obj.get(&Value::from("lazy_parse")) {
+      self.lazy_parse = lazy_parse.as_bool()?;
+    }
+
+    if let Some(line_numbers) = obj.get(&Value::from("line_numbers")) {
+      self.line_numbers = line_numbers.as_bool()?;
+    }
+
+    if let Some(keep) = obj.get(&Value::from("keep_history_on_restart")) {
+      self.keep_history_on_restart = keep.as_bool()?;
+    }
+
+    if let Some(highlights) = obj.get(&Value::from("highlights")) {
+      self.highlights = highlights
+        .as_array()?
+        .iter()
+        .map(Highlight::from_val)
+        .collect::<Result<Vec<_>>>()?;
+    }
+
+    if let Some(title_format) = obj.get(&Value::from("title_format")) {
+      self.title_format = TitleFormat::parse(title_format.as_str()?)
+        .map_err(|err| title_format.error_at(err.to_string()))?;
+    }
+
+    if let Some(show_foreground_cmd) =
+      obj.get(&Value::from("show_foreground_cmd"))
+    {
+      self.show_foreground_cmd = show_foreground_cmd.as_bool()?;
+    }
+
+    if let Some(show_resource_usage) =
+      obj.get(&Value::from("show_resource_usage"))
+    {
+      self.show_resource_usage = show_resource_usage.as_bool()?;
+    }
+
+    if let Some(dim) = obj.get(&Value::from("dim_idle_procs")) {
+      self.dim_idle_procs = match dim.raw() {
+        Value::Bool(false) => None,
+        Value::Bool(true) => Some(DEFAULT_IDLE_DURATION),
+        _ => Some(Duration::from_secs(dim.as_usize()? as u64)),
+      };
+    }
+
+    if let Some(clock_format) = obj.get(&Value::from("clock_format")) {
+      let format = clock_format.as_str()?;
+      let invalid = StrftimeItems::new(format).any(|i| i == Item::Error);
+      if invalid {
+        return Err(clock_format.error_at("Invalid clock format"));
+      }
+      self.clock_format = format.to_string();
+    }
+
+    if let Some(confirm_kill) = obj.get(&Value::from("confirm_kill")) {
+      self.confirm_kill = confirm_kill.as_bool()?;
+    }
+
+    if let Some(show) = obj.get(&Value::from("show_term_title")) {
+      self.show_term_title = show.as_bool()?;
+    }
+
+    if let Some(scrollbar) = obj.get(&Value::from("scrollbar")) {
+      self.scrollbar = ScrollbarMode::from_val(scrollbar)?;
+    }
+
+    if let Some(style) = obj.get(&Value::from("border_style")) {
+      self.border_style = BorderStyle::from_val(style)?;
+    }
+
+    if let Some(color) = obj.get(&Value::from("focus_border_color")) {
+      self.focus_border_color = parse_color(color)?;
+    }
+
+    if let Some(format) = obj.get(&Value::from("proc_list_format")) {
+      self.proc_list_format = match format.raw() {
+        Value::Null => None,
+        _ => Some(
+          ProcFormat::parse(format.as_str()?)
+            .map_err(|err| format.error_at(err.to_string()))?,
+        ),
+      };
+    }
+
+    if let Some(glyphs) = obj.get(&Value::from("status_glyphs")) {
+      self.status_glyphs.merge_value(glyphs)?;
+    }
+
+    if let Some(watch_patterns) = obj.get(&Value::from("watch_patterns")) {
+      self.watch_patterns = watch_patterns
+        .as_array()?
+        .iter()
+        .map(WatchPattern::from_val)
+        .collect::<Result<Vec<_>>>()?;
+    }
+
+    if let Some(size) = obj.get(&Value::from("cell_pixel_size")) {
+      self.cell_pixel_size = match size.raw() {
+        Value::Null => None,
+        _ => {
+          let dims = size
+            .as_array()?
+            .iter()
+            .map(|dim| {
+              u16::try_from(dim.as_usize()?)
+                .map_err(|_| dim.error_at("Cell size is too big"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+          match dims.as_slice() {
+            [width, height] => Some((*width, *height)),
+            _ => bail!(size.error_at("Expected [width, height]")),
+          }
+        }
+      };
+    }
+
+    if let Some(tab_width) = obj.get(&Value::from("tab_width")) {
+      self.tab_width = u16::try_from(tab_width.as_usize()?)
+        .map_err(|_| tab_width.error_at("Tab width is too big"))?;
+    }
+
+    if let Some(size) = obj.get(&Value::from("pty_read_buf")) {
+      let len = size.as_usize()?;
+      if !(READ_BUF_LEN_MIN..=READ_BUF_LEN_MAX).contains(&len) {
+        bail!(size.error_at(format!(
+          "Expected a size from {} to {} bytes",
+          READ_BUF_LEN_MIN, READ_BUF_LEN_MAX
+        )));
+      }
+      self.pty_read_buf = len;
+    }
+
+    if let Some(delay) = obj.get(&Value::from("read_batch_delay_ms")) {
+      let ms = delay.as_usize()? as u64;
+      if ms > READ_BATCH_DELAY_MAX_MS {
+        bail!(delay.error_at(format!(
+          "Expected at most {} ms",
+          READ_BATCH_DELAY_MAX_MS
+        )));
+      }
+      self.read_batch_delay_ms = ms;
+    }
+
+    if let Some(paste) = obj.get(&Value::from("middle_click_paste")) {
+      self.middle_click_paste = paste.as_bool()?;
+    }
+
+    if let Some(sync_focus) = obj.get(&Value::from("sync_focus")) {
+      self.sync_focus = sync_focus.as_bool()?;
+    }
+
+    if let Some(scroll) = obj.get(&Value::from("scroll_to_bottom_on_focus")) {
+      self.scroll_to_bottom_on_focus = scroll.as_bool()?;
+    }
+
+    if let Some(accel) = obj.get(&Value::from("scroll_acceleration")) {
+      self.scroll_acceleration = accel.as_bool()?;
+    }
+
+    if let Some(max) = obj.get(&Value::from("scroll_acceleration_max")) {
+      self.scroll_acceleration_max = max.as_usize()?.max(1);
+    }
+
+    if let Some(scrolloff) = obj.get(&Value::from("copy_scrolloff")) {
+      self.copy_scrolloff = scrolloff.as_usize()?;
+    }
+
+    if let Some(stagger) = obj.get(&Value::from("start_stagger_ms")) {
+      self.start_stagger_ms = stagger.as_usize()? as u64;
+    }
+
+    if let Some(action) = obj.get(&Value::from("on_all_finished")) {
+      self.on_all_finished = OnAllFinished::from_val(action)?;
+    }
+
+    if let Some(exit_with_code) = obj.get(&Value::from("exit_with_child_code"))
+    {
+      self.exit_with_child_code = exit_with_code.as_bool()?;
+    }
+
+    if let Some(stop) = obj.get(&Value::from("default_stop")) {
+      self.default_stop = StopSignal::from_val(stop)?;
+    }
+
+    if let Some(relative) = obj.get(&Value::from("paths_relative_to_config")) {
+      self.paths_relative_to_config = relative.as_bool()?;
+    }
+
+    if let Some(strict) = obj.get(&Value::from("strict_placeholders")) {
+      self.strict_placeholders = strict.as_bool()?;
     }
 
     Ok(())
@@ -180,6 +486,19 @@ impl Settings {
       Key::new(KeyCode::Char('k'), KeyModifiers::NONE),
       AppEvent::PrevProc,
     );
+    s.keymap_add_p(
+      Key::new(KeyCode::Tab, KeyModifiers::NONE),
+      AppEvent::NextRunningProc,
+    );
+    s.keymap_add_p(
+      Key::new(KeyCode::BackTab, KeyModifiers::NONE),
+      AppEvent::PrevRunningProc,
+    );
+    s.keymap_add_p(KeyCode::Char('m').into(), AppEvent::ToggleInterleave);
+    s.keymap_add_p(KeyCode::Char('o').into(), AppEvent::CycleProcSort);
+    s.keymap_add_p(KeyCode::Char('O').into(), AppEvent::ReverseProcSort);
+    s.keymap_add_p(KeyCode::Char('>').into(), AppEvent::GrowSidebar);
+    s.keymap_add_p(KeyCode::Char('<').into(), AppEvent::ShrinkSidebar);
     s.keymap_add_p(
       Key::new(KeyCode::Char('s'), KeyModifiers::NONE),
       AppEvent::StartProc,
@@ -218,6 +537,27 @@ impl Settings {
       Key::new(KeyCode::Char('d'), KeyModifiers::NONE),
       AppEvent::ShowRemoveProc,
     );
+    s.keymap_add_p(
+      Key::new(KeyCode::Char('i'), KeyModifiers::NONE),
+      AppEvent::CopyProcInfo,
+    );
+    s.keymap_add_p(
+      Key::new(KeyCode::Char('E'), KeyModifiers::SHIFT),
+      AppEvent::ShowEditProcCmd,
+    );
+
+    s.keymap_add_t(
+      Key::new(KeyCode::Char('v'), KeyModifiers::ALT),
+      AppEvent::Paste,
+    );
+    s.keymap_add_t(
+      Key::new(KeyCode::Left, KeyModifiers::SHIFT),
+      AppEvent::ScrollLeft,
+    );
+    s.keymap_add_t(
+      Key::new(KeyCode::Right, KeyModifiers::SHIFT),
+      AppEvent::ScrollRight,
+    );
 
     // Scrolling in TERM and COPY modes
     for map in [&mut s.keymap_procs, &mut s.keymap_copy] {
@@ -241,6 +581,18 @@ impl Settings {
         Key::new(KeyCode::PageDown, KeyModifiers::NONE),
         AppEvent::ScrollDown,
       );
+      map.insert(
+        Key::new(KeyCode::Char('['), KeyModifiers::NONE),
+        AppEvent::ScrollToPrompt {
+          dir: PromptDir::Prev,
+        },
+      );
+      map.insert(
+        Key::new(KeyCode::Char(']'), KeyModifiers::NONE),
+        AppEvent::ScrollToPrompt {
+          dir: PromptDir::Next,
+        },
+      );
     }
 
     s.keymap_add_p(
@@ -269,6 +621,19 @@ impl Settings {
     s.keymap_add_c(KeyCode::Esc.into(), AppEvent::CopyModeLeave);
     s.keymap_add_c(KeyCode::Char('v').into(), AppEvent::CopyModeEnd);
     s.keymap_add_c(KeyCode::Char('c').into(), AppEvent::CopyModeCopy);
+    s.keymap_add_c(
+      KeyCode::Char('/').into(),
+      AppEvent::ShowCopyModeSearch { backward: false },
+    );
+    s.keymap_add_c(
+      KeyCode::Char('?').into(),
+      AppEvent::ShowCopyModeSearch { backward: true },
+    );
+    s.keymap_add_c(KeyCode::Char('n').into(), AppEvent::CopyModeSearchNext);
+    s.keymap_add_c(
+      Key::new(KeyCode::Char('N'), KeyModifiers::SHIFT),
+      AppEvent::CopyModeSearchPrev,
+    );
     for code in [KeyCode::Up, KeyCode::Char('k')] {
       s.keymap_add_c(code.into(), AppEvent::CopyModeMove { dir: CopyMove::Up });
     }
@@ -310,6 +675,13 @@ impl Settings {
     self.keymap_copy.insert(key, event);
   }
 
+  pub fn read_options(&self) -> ReadOptions {
+    ReadOptions {
+      buf_len: self.pty_read_buf,
+      batch_delay: Duration::from_millis(self.read_batch_delay_ms),
+    }
+  }
+
   pub fn add_to_keymap(&self, keymap: &mut Keymap) -> Result<()> {
     for (key, event) in &self.keymap_procs {
       keymap.bind_p(key.clone(), event.clone());
@@ -324,3 +696,120 @@ impl Settings {
     Ok(())
   }
 }
+
+/// What to do when all processes have exited.
+#[derive(Clone, Debug, Default)]
+pub enum OnAllFinished {
+  /// Keep running.
+  #[default]
+  Keep,
+  /// Quit mprocs.
+  Quit,
+  /// Start the processes that failed again.
+  RestartFailed,
+  /// Run a command.
+  Event(AppEvent),
+}
+
+impl OnAllFinished {
+  /// Accepts "keep", "quit", "restart-failed" or a command (written as in
+  /// keymaps or just its name).
+  pub fn from_val(val: &Val) -> Result<Self> {
+    match val.raw() {
+      Value::String(mode) if mode == "keep" => Ok(OnAllFinished::Keep),
+      Value::String(mode) if mode == "quit" => Ok(OnAllFinished::Quit),
+      Value::String(mode) if mode == "restart-failed" => {
+        Ok(OnAllFinished::RestartFailed)
+      }
+      _ => Ok(OnAllFinished::Event(parse_keymap_hint(val)?)),
+    }
+  }
+}
+
+const DEFAULT_PROC_LIST_WIDTH: usize = 30;
+
+/// Time without output after which a proc is dimmed with
+/// `dim_idle_procs: true`.
+const DEFAULT_IDLE_DURATION: Duration = Duration::from_secs(30);
+
+/// Width of the process list: a number of columns or a percentage of the
+/// screen width, kept within `min` and `max` columns.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProcListWidth {
+  /// Columns, or percent of the screen width if `percent` is true.
+  pub value: usize,
+  pub percent: bool,
+  pub min: usize,
+  pub max: usize,
+}
+
+impl ProcListWidth {
+  pub fn fixed(width: usize) -> Self {
+    ProcListWidth {
+      value: width,
+      percent: false,
+      min: 0,
+      max: usize::MAX,
+    }
+  }
+
+  /// Accepts a number of columns or `{ percent, min, max }`.
+  pub fn from_val(val: &Val) -> Result<Self> {
+    if let Value::Number(_) = val.raw() {
+      return Ok(ProcListWidth::fixed(val.as_usize()?));
+    }
+    let obj = val.as_object()?;
+    let get =
+      |key: &str| obj.get(&Value::from(key)).map(|v| v.as_usize()).transpose();
+    let percent = get("percent")?;
+    let min = get("min")?.unwrap_or(0);
+    let max = get("max")?.unwrap_or(usize::MAX);
+    if percent.map_or(false, |percent| percent > 100) {
+      return Err(val.error_at("\"percent\" must be at most 100"));
+    }
+    if min > max {
+      return Err(val.error_at("\"min\" must not be greater than \"max\""));
+    }
+    Ok(ProcListWidth {
+      value: percent.unwrap_or(DEFAULT_PROC_LIST_WIDTH),
+      percent: percent.is_some(),
+      min,
+      max,
+    })
+  }
+
+  /// Width in columns for a screen `screen_width` columns wide.
+  pub fn resolve(&self, screen_width: u16) -> u16 {
+    let width = if self.percent {
+      screen_width as usize * self.value / 100
+    } else {
+      self.value
+    };
+    width.clamp(self.min, self.max).min(u16::MAX as usize) as u16
+  }
+
+  /// Width in columns, unless it depends on the screen width.
+  pub fn fixed_width(&self) -> Option<usize> {
+    (!self.percent).then_some(self.value)
+  }
+
+  /// Switches to a fixed number of columns. `min` and `max` still apply.
+  pub fn set_fixed(&mut self, width: usize) {
+    self.value = width;
+    self.percent = false;
+  }
+}
+
+/// Hints are commands written as in keymaps. Commands without arguments can
+/// also be written as just the name (e.g. `quit`).
+fn parse_keymap_hint(val: &Val) -> Result<AppEvent> {
+  let raw = match val.raw() {
+    Value::String(name) => {
+      let mut map = serde_yaml::Mapping::new();
+      map.insert(Value::from("c"), Value::from(name.as_str()));
+      Value::Mapping(map)
+    }
+    raw => raw.clone(),
+  };
+  serde_yaml::from_value(raw).map_err(|err| val.error_at(err.to_string()))
+}
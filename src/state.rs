@@ -1,7 +1,14 @@
+use std::{
+  cmp::Ordering,
+  collections::{HashMap, VecDeque},
+  time::{Duration, Instant},
+};
+
 use crate::{
   app::ClientId,
+  event::ProcSort,
   keymap::KeymapGroup,
-  proc::{handle::ProcHandle, CopyMode},
+  proc::{handle::ProcHandle, usage::read_sessions, CopyMode},
 };
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -29,18 +36,83 @@ impl Scope {
   }
 }
 
+#[derive(Clone, Copy, Debug)]
+pub struct ProcDrag {
+  pub from: usize,
+  /// Index the proc is moved to on release.
+  pub to: usize,
+}
+
+/// Selected proc and scope of a single client.
+#[derive(Clone, Copy, Debug)]
+pub struct ClientFocus {
+  pub selected: usize,
+  pub scope: Scope,
+}
+
+/// Max number of lines kept for the interleaved view.
+const INTERLEAVED_MAX_LINES: usize = 10_000;
+
+/// How long a flash message is shown.
+const FLASH_DURATION: Duration = Duration::from_secs(2);
+
 pub struct State {
   pub current_client_id: Option<ClientId>,
+  /// Focus of each client. Empty if `sync_focus` is enabled.
+  pub client_focus: HashMap<ClientId, ClientFocus>,
 
   pub scope: Scope,
   pub procs: Vec<ProcHandle>,
   pub selected: usize,
   pub hide_keymap_window: bool,
+  /// Next key is sent to the process even if it is bound in the keymap.
+  pub literal_next: bool,
+  /// Proc dragged in the process list with the mouse.
+  pub drag: Option<ProcDrag>,
+  /// Border between the process list and the terminal is dragged.
+  pub sidebar_drag: bool,
+  /// Whether clients report mouse events. Disabled to let the outer terminal
+  /// select text.
+  pub mouse_capture: bool,
+  /// Snap procs to the latest output when they get selected instead of
+  /// keeping their scroll position.
+  pub scroll_to_bottom_on_focus: bool,
+  /// Message shown in place of key hints for a moment.
+  pub flash: Option<(String, Instant)>,
+  /// Last sorting of the process list and whether it was descending.
+  pub sort: Option<(ProcSort, bool)>,
+  /// Show output of all procs merged in one pane instead of the selected one.
+  pub interleave: bool,
+  /// Output lines of all procs (with proc ids) in the order they were printed.
+  pub interleaved: VecDeque<(usize, String)>,
 
   pub quitting: bool,
 }
 
 impl State {
+  /// Shows a transient message to the user.
+  pub fn flash<S: Into<String>>(&mut self, msg: S) {
+    self.flash = Some((msg.into(), Instant::now()));
+  }
+
+  /// Current flash message, unless it has expired.
+  pub fn flash_msg(&self) -> Option<&str> {
+    match &self.flash {
+      Some((msg, at)) if at.elapsed() < FLASH_DURATION => Some(msg),
+      _ => None,
+    }
+  }
+
+  /// Removes an expired flash message. Returns true if it was removed.
+  pub fn clear_expired_flash(&mut self) -> bool {
+    if self.flash.is_some() && self.flash_msg().is_none() {
+      self.flash = None;
+      true
+    } else {
+      false
+    }
+  }
+
   pub fn get_current_proc(&self) -> Option<&ProcHandle> {
     self.procs.get(self.selected)
   }
@@ -50,10 +122,149 @@ impl State {
   }
 
   pub fn select_proc(&mut self, index: usize) {
+    let switched = index != self.selected;
+    if switched {
+      if let Some(proc_handle) = self.procs.get_mut(self.selected) {
+        proc_handle.blur();
+      }
+    }
     self.selected = index;
     if let Some(proc_handle) = self.procs.get_mut(index) {
       proc_handle.focus();
+      if switched && self.scroll_to_bottom_on_focus {
+        proc_handle.scroll_to_bottom();
+      }
+    }
+  }
+
+  /// Index of the next (or previous) running proc after the selected one,
+  /// wrapping around. None if no procs are running.
+  pub fn next_running_proc(&self, forward: bool) -> Option<usize> {
+    let len = self.procs.len();
+    (1..=len)
+      .map(|offset| {
+        if forward {
+          (self.selected + offset) % len
+        } else {
+          (self.selected + len - offset % len) % len
+        }
+      })
+      .find(|&i| self.procs[i].is_up())
+  }
+
+  /// Reorders the process list, keeping the same procs selected. The sort is
+  /// stable, so equal procs keep their relative order.
+  pub fn sort_procs(&mut self, by: ProcSort, desc: bool) {
+    let id_at = |procs: &[ProcHandle], i: usize| procs.get(i).map(|p| p.id());
+    let selected = id_at(&self.procs, self.selected);
+    let focus = self
+      .client_focus
+      .iter()
+      .map(|(client_id, focus)| {
+        (*client_id, id_at(&self.procs, focus.selected))
+      })
+      .collect::<Vec<_>>();
+
+    self.procs.sort_by(|a, b| {
+      let ord = compare_procs(a, b, by);
+      if desc {
+        ord.reverse()
+      } else {
+        ord
+      }
+    });
+    self.sort = Some((by, desc));
+
+    let index_of = |procs: &[ProcHandle], id: Option<usize>| {
+      procs.iter().position(|p| Some(p.id()) == id).unwrap_or(0)
+    };
+    self.selected = index_of(&self.procs, selected);
+    for (client_id, id) in focus {
+      if let Some(focus) = self.client_focus.get_mut(&client_id) {
+        focus.selected = index_of(&self.procs, id);
+      }
+    }
+  }
+
+  pub fn push_interleaved(&mut self, proc_id: usize, line: String) {
+    if self.interleaved.len() >= INTERLEAVED_MAX_LINES {
+      self.interleaved.pop_front();
     }
+    self.interleaved.push_back((proc_id, line));
+  }
+
+  /// Moves the proc at `from` to `to`, keeping the same proc selected.
+  pub fn move_proc(&mut self, from: usize, to: usize) {
+    if from == to || from >= self.procs.len() || to >= self.procs.len() {
+      return;
+    }
+    let proc = self.procs.remove(from);
+    self.procs.insert(to, proc);
+    self.sort = None;
+    self.selected = moved_index(self.selected, from, to);
+    for focus in self.client_focus.values_mut() {
+      focus.selected = moved_index(focus.selected, from, to);
+    }
+  }
+
+  /// Makes the focus of the client current.
+  pub fn load_focus(&mut self, client_id: ClientId) {
+    if let Some(focus) = self.client_focus.get(&client_id) {
+      self.selected = focus.selected.min(self.procs.len().saturating_sub(1));
+      self.scope = focus.scope;
+    }
+  }
+
+  /// Remembers the current focus as the focus of the client.
+  pub fn store_focus(&mut self, client_id: ClientId) {
+    let focus = ClientFocus {
+      selected: self.selected,
+      scope: self.scope,
+    };
+    self.client_focus.insert(client_id, focus);
+  }
+
+  /// Lets procs know whether their output is on screen.
+  pub fn update_visible_procs(&mut self) {
+    let selected = self.selected;
+    for (i, proc) in self.procs.iter_mut().enumerate() {
+      let visible = i == selected
+        || self.client_focus.values().any(|focus| focus.selected == i);
+      proc.set_visible(visible);
+    }
+  }
+
+  /// Returns true if any of the procs changed.
+  pub fn update_foreground_cmds(&mut self) -> bool {
+    let mut changed = false;
+    for proc in &mut self.procs {
+      changed |= proc.update_foreground_cmd();
+    }
+    changed
+  }
+
+  /// Returns true if any of the procs became idle or active.
+  pub fn update_idle_procs(&mut self, after: Duration) -> bool {
+    let mut changed = false;
+    for proc in &mut self.procs {
+      changed |= proc.update_idle(after);
+    }
+    changed
+  }
+
+  /// Returns true if any of the procs changed.
+  pub fn update_resource_usage(&mut self) -> bool {
+    let sessions = read_sessions();
+    let now = Instant::now();
+    let mut changed = false;
+    for proc in &mut self.procs {
+      changed |= proc.update_usage(now, &sessions);
+    }
+    changed
+  }
+
+  pub fn get_proc(&self, id: usize) -> Option<&ProcHandle> {
+    self.procs.iter().find(|p| p.id() == id)
   }
 
   pub fn get_proc_mut(&mut self, id: usize) -> Option<&mut ProcHandle> {
@@ -73,6 +284,11 @@ impl State {
     }
   }
 
+  /// Whether all processes have exited and none of them is about to start.
+  pub fn all_procs_finished(&self) -> bool {
+    !self.procs.is_empty() && self.procs.iter().all(|p| p.is_finished())
+  }
+
   pub fn all_procs_down(&self) -> bool {
     self.procs.iter().all(|p| !p.is_up())
   }
@@ -81,3 +297,36 @@ impl State {
     self.hide_keymap_window = !self.hide_keymap_window;
   }
 }
+
+fn compare_procs(a: &ProcHandle, b: &ProcHandle, by: ProcSort) -> Ordering {
+  match by {
+    ProcSort::Name => a.name().cmp(b.name()),
+    ProcSort::Status => status_rank(a).cmp(&status_rank(b)),
+    ProcSort::Uptime => a.uptime().cmp(&b.uptime()),
+    ProcSort::Restarts => a.restarts().cmp(&b.restarts()),
+  }
+}
+
+/// Failed procs go first, then running ones, then stopped ones.
+fn status_rank(proc: &ProcHandle) -> u8 {
+  if proc.is_failed() {
+    0
+  } else if proc.is_up() {
+    1
+  } else {
+    2
+  }
+}
+
+/// Index of the item at `index` after moving an item from `from` to `to`.
+fn moved_index(index: usize, from: usize, to: usize) -> usize {
+  if index == from {
+    to
+  } else if from < index && index <= to {
+    index - 1
+  } else if to <= index && index < from {
+    index + 1
+  } else {
+    index
+  }
+}
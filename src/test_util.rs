@@ -0,0 +1,11 @@
+use std::path::PathBuf;
+
+/// Path in the temp dir unique for the test process, e.g.
+/// `temp_path("session.yaml")`.
+pub fn temp_path(name: &str) -> PathBuf {
+  std::env::temp_dir().join(format!(
+    "mprocs-test-{}-{}",
+    std::process::id(),
+    name
+  ))
+}
@@ -1,11 +1,68 @@
+use anyhow::Result;
 use tui::{
   style::{Color, Modifier, Style},
   widgets::{Block, BorderType, Borders},
 };
 
+use crate::yaml_val::Val;
+
+/// Colors assigned to processes in the interleaved view.
+const PROC_COLORS: [Color; 6] = [
+  Color::Cyan,
+  Color::Magenta,
+  Color::Yellow,
+  Color::Green,
+  Color::Blue,
+  Color::Red,
+];
+
 pub struct Theme {
   pub procs_item: Style,
   pub procs_item_active: Style,
+  pub border_style: BorderStyle,
+  pub focus_border_color: Color,
+}
+
+/// Lines used for the borders of the panes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BorderStyle {
+  /// Thick border around the focused pane, plain around the others.
+  #[default]
+  Default,
+  Plain,
+  Rounded,
+  Double,
+  Thick,
+  None,
+}
+
+impl BorderStyle {
+  pub fn from_val(val: &Val) -> Result<Self> {
+    match val.as_str()? {
+      "default" => Ok(BorderStyle::Default),
+      "plain" => Ok(BorderStyle::Plain),
+      "rounded" => Ok(BorderStyle::Rounded),
+      "double" => Ok(BorderStyle::Double),
+      "thick" => Ok(BorderStyle::Thick),
+      "none" => Ok(BorderStyle::None),
+      _ => Err(val.error_at(
+        "Expected \"default\", \"plain\", \"rounded\", \"double\", \"thick\" \
+         or \"none\"",
+      )),
+    }
+  }
+
+  fn border_type(self, active: bool) -> BorderType {
+    match self {
+      BorderStyle::Default if active => BorderType::Thick,
+      BorderStyle::Default | BorderStyle::Plain | BorderStyle::None => {
+        BorderType::Plain
+      }
+      BorderStyle::Rounded => BorderType::Rounded,
+      BorderStyle::Double => BorderType::Double,
+      BorderStyle::Thick => BorderType::Thick,
+    }
+  }
 }
 
 impl Theme {
@@ -19,15 +76,19 @@ impl Theme {
   }
 
   pub fn pane(&self, active: bool) -> Block {
-    let type_ = match active {
-      true => BorderType::Thick,
-      false => BorderType::Plain,
+    let borders = match self.border_style {
+      BorderStyle::None => Borders::NONE,
+      _ => Borders::ALL,
+    };
+    let color = match active {
+      true => self.focus_border_color,
+      false => Color::Reset,
     };
 
     Block::default()
-      .borders(Borders::ALL)
-      .border_type(type_)
-      .border_style(Style::default().fg(Color::Reset).bg(Color::Reset))
+      .borders(borders)
+      .border_type(self.border_style.border_type(active))
+      .border_style(Style::default().fg(color).bg(Color::Reset))
   }
 
   pub fn copy_mode_label(&self) -> Style {
@@ -45,6 +106,31 @@ impl Theme {
     }
   }
 
+  pub fn line_number(&self) -> Style {
+    Style::default().fg(Color::DarkGray)
+  }
+
+  pub fn unread(&self) -> Style {
+    Style::default().fg(Color::Yellow)
+  }
+
+  pub fn drop_indicator(&self) -> Style {
+    Style::default().fg(Color::Yellow)
+  }
+
+  pub fn scrollbar(&self) -> Style {
+    Style::default().fg(Color::Gray)
+  }
+
+  /// Color of the process with index `index` in the palette.
+  pub fn proc_color(&self, index: usize) -> Color {
+    PROC_COLORS[index % PROC_COLORS.len()]
+  }
+
+  pub fn flash(&self) -> Style {
+    Style::default().fg(Color::Black).bg(Color::Yellow)
+  }
+
   pub fn zoom_tip(&self) -> Style {
     Style::default().fg(Color::Black).bg(Color::Yellow)
   }
@@ -55,6 +141,8 @@ impl Default for Theme {
     Self {
       procs_item: Style::default().fg(Color::Reset),
       procs_item_active: Style::default().bg(Color::Indexed(240)),
+      border_style: BorderStyle::default(),
+      focus_border_color: Color::Reset,
     }
   }
 }
@@ -0,0 +1,89 @@
+use anyhow::{bail, Result};
+
+/// Template for process names in the process list. Placeholders: `{name}`,
+/// `{title}` and `{icon_name}` (the last two are set by the process using
+/// OSC sequences).
+#[derive(Clone, Debug, PartialEq)]
+pub struct TitleFormat(Vec<Part>);
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+  Text(String),
+  Name,
+  Title,
+  IconName,
+}
+
+impl Default for TitleFormat {
+  fn default() -> Self {
+    TitleFormat(vec![Part::Name])
+  }
+}
+
+impl TitleFormat {
+  pub fn parse(src: &str) -> Result<Self> {
+    let mut parts = Vec::new();
+    let mut rest = src;
+    while let Some(start) = rest.find('{') {
+      if start > 0 {
+        parts.push(Part::Text(rest[..start].to_string()));
+      }
+      let end = match rest[start..].find('}') {
+        Some(end) => start + end,
+        None => bail!("Unclosed \"{{\" in title format: \"{}\"", src),
+      };
+      let part = match &rest[start + 1..end] {
+        "name" => Part::Name,
+        "title" => Part::Title,
+        "icon_name" => Part::IconName,
+        other => bail!("Unknown placeholder in title format: \"{}\"", other),
+      };
+      parts.push(part);
+      rest = &rest[end + 1..];
+    }
+    if !rest.is_empty() {
+      parts.push(Part::Text(rest.to_string()));
+    }
+    Ok(TitleFormat(parts))
+  }
+
+  /// Formats the name. Until the process sets a title, only the name is
+  /// shown.
+  pub fn render(&self, name: &str, title: &str, icon_name: &str) -> String {
+    if title.is_empty() && icon_name.is_empty() {
+      return name.to_string();
+    }
+
+    let mut buf = String::new();
+    for part in &self.0 {
+      match part {
+        Part::Text(text) => buf.push_str(text),
+        Part::Name => buf.push_str(name),
+        Part::Title => buf.push_str(title),
+        Part::IconName => buf.push_str(icon_name),
+      }
+    }
+    buf
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn render() {
+    let format = TitleFormat::parse("{name}: {title}").unwrap();
+    assert_eq!(format.render("shell", "vim", ""), "shell: vim");
+    assert_eq!(format.render("shell", "", ""), "shell");
+
+    let format = TitleFormat::parse("[{icon_name}]").unwrap();
+    assert_eq!(format.render("shell", "vim", "v"), "[v]");
+  }
+
+  #[test]
+  fn parse_errors() {
+    assert!(TitleFormat::parse("{name").is_err());
+    assert!(TitleFormat::parse("{cmd}").is_err());
+  }
+}
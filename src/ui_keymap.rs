@@ -1,3 +1,4 @@
+use chrono::Local;
 use tui::{
   layout::{Margin, Rect},
   style::{Color, Style},
@@ -5,6 +6,7 @@ use tui::{
   widgets::{Clear, Paragraph},
   Frame,
 };
+use unicode_width::UnicodeWidthStr;
 
 use crate::{
   encode_term::print_key,
@@ -19,6 +21,8 @@ pub fn render_keymap(
   frame: &mut Frame,
   state: &mut State,
   keymap: &Keymap,
+  hints: Option<&[AppEvent]>,
+  clock_format: &str,
 ) {
   let theme = Theme::default();
 
@@ -28,9 +32,73 @@ pub fn render_keymap(
   frame.render_widget(Clear, area);
   frame.render_widget(block, area);
 
+  let mut area = area.inner(&Margin {
+    vertical: 1,
+    horizontal: 1,
+  });
+
+  if !clock_format.is_empty() {
+    let clock = Local::now().format(clock_format).to_string();
+    let width = (clock.width() as u16).min(area.width);
+    let clock_area = Rect {
+      x: area.x + area.width - width,
+      width,
+      ..area
+    };
+    frame.render_widget(Paragraph::new(clock), clock_area);
+    area.width = area.width.saturating_sub(width + 1);
+  }
+
+  if let Some(msg) = state.flash_msg() {
+    let msg = Span::styled(format!(" {} ", msg), theme.flash());
+    frame.render_widget(Paragraph::new(Line::from(msg)), area);
+    return;
+  }
+
   let group = state.get_keymap_group();
-  let items = match group {
-    KeymapGroup::Procs => vec![
+  if matches!(group, KeymapGroup::Copy) {
+    let size = state
+      .get_current_proc_mut()
+      .and_then(|p| p.selection_size());
+    if let Some(size) = size {
+      let info = format!(
+        " {} {}, {} {} ",
+        size.lines,
+        plural(size.lines, "line", "lines"),
+        size.chars,
+        plural(size.chars, "char", "chars"),
+      );
+      let width = (info.width() as u16).min(area.width);
+      let info_area = Rect {
+        x: area.x + area.width - width,
+        width,
+        ..area
+      };
+      let info = Span::styled(info, theme.copy_mode_label());
+      frame.render_widget(Paragraph::new(Line::from(info)), info_area);
+      area.width = area.width.saturating_sub(width + 1);
+    }
+  }
+
+  let line = Line::from(hint_spans(keymap, group, hints, area.width as usize));
+  let line = Text::from(vec![line]);
+
+  let p = Paragraph::new(line);
+  frame.render_widget(p, area);
+}
+
+/// Hints for commands (from the `keymap_hints` setting or the defaults) that
+/// are bound to keys in the keymap group. Hints that don't fit into `width`
+/// are replaced with "…".
+fn hint_spans(
+  keymap: &Keymap,
+  group: KeymapGroup,
+  hints: Option<&[AppEvent]>,
+  width: usize,
+) -> Vec<Span<'static>> {
+  let events = match (hints, group) {
+    (Some(hints), _) => hints.to_vec(),
+    (None, KeymapGroup::Procs) => vec![
       AppEvent::ToggleFocus,
       AppEvent::Quit,
       AppEvent::NextProc,
@@ -38,38 +106,67 @@ pub fn render_keymap(
       AppEvent::StartProc,
       AppEvent::TermProc,
       AppEvent::RestartProc,
+      AppEvent::ShowCommandsMenu,
+      AppEvent::ShowAddProc,
+      AppEvent::CopyModeEnter,
+      AppEvent::Zoom,
       AppEvent::ToggleKeymapWindow,
     ],
-    KeymapGroup::Term => vec![AppEvent::ToggleFocus],
-    KeymapGroup::Copy => vec![
+    (None, KeymapGroup::Term) => vec![
+      AppEvent::ToggleFocus,
+      AppEvent::Zoom,
+      AppEvent::CopyModeEnter,
+      AppEvent::SendLiteralNext,
+      AppEvent::Paste,
+    ],
+    (None, KeymapGroup::Copy) => vec![
       AppEvent::CopyModeEnd,
       AppEvent::CopyModeCopy,
       AppEvent::CopyModeLeave,
+      AppEvent::ScrollUp,
+      AppEvent::ScrollDown,
     ],
   };
-  let line = items
+
+  const MORE: &str = " …";
+  let hints = events
     .into_iter()
     .filter_map(|event| Some((keymap.resolve_key(group, &event)?, event)))
-    .flat_map(|(key, event)| {
-      vec![
-        Span::raw(" <"),
-        Span::styled(print_key(key), Style::default().fg(Color::Yellow)),
-        Span::raw(": "),
-        Span::raw(event.desc()),
-        Span::raw("> "),
-      ]
-    })
+    .map(|(key, event)| (print_key(key), event.desc()))
     .collect::<Vec<_>>();
+  let mut spans = Vec::new();
+  let mut left = width;
+  for (i, (key, desc)) in hints.iter().enumerate() {
+    let hint_width = key.width() + desc.width() + 6;
+    let is_last = i + 1 == hints.len();
+    // Keep room for the "…" unless this is the last hint.
+    let needed = if is_last {
+      hint_width
+    } else {
+      hint_width + MORE.width()
+    };
+    if needed > left {
+      if left >= MORE.width() {
+        spans.push(Span::raw(MORE));
+      }
+      break;
+    }
+    left -= hint_width;
+    spans.extend([
+      Span::raw(" <"),
+      Span::styled(key.clone(), Style::default().fg(Color::Yellow)),
+      Span::raw(": "),
+      Span::raw(desc.clone()),
+      Span::raw("> "),
+    ]);
+  }
+  spans
+}
 
-  let line = Line::from(line);
-  let line = Text::from(vec![line]);
-
-  let p = Paragraph::new(line);
-  frame.render_widget(
-    p,
-    area.inner(&Margin {
-      vertical: 1,
-      horizontal: 1,
-    }),
-  );
+fn plural<'a>(n: usize, one: &'a str, many: &'a str) -> &'a str {
+  if n == 1 {
+    one
+  } else {
+    many
+  }
 }
@@ -7,19 +7,29 @@ use tui::{
 };
 
 use crate::{
-  proc::handle::ProcHandle,
-  state::{Scope, State},
+  proc::{handle::ProcHandle, usage::format_bytes},
+  proc_format::{format_uptime, Field, ProcFormat, StatusGlyphs},
+  state::{ProcDrag, Scope, State},
   theme::Theme,
+  title_format::TitleFormat,
 };
 
-pub fn render_procs(area: Rect, frame: &mut Frame, state: &mut State) {
+/// Number of decode errors in the output before a proc is marked with "⚠".
+const DECODE_ERRORS_MIN: usize = 16;
+
+pub fn render_procs(
+  area: Rect,
+  frame: &mut Frame,
+  state: &mut State,
+  theme: &Theme,
+  title_format: &TitleFormat,
+  proc_format: Option<&ProcFormat>,
+  status_glyphs: &StatusGlyphs,
+) {
   if area.width <= 2 {
     return;
   }
 
-  let theme = Theme::default();
-  let theme = &theme;
-
   let active = state.scope == Scope::Procs;
 
   let mut list_state = ListState::default();
@@ -29,12 +39,28 @@ pub fn render_procs(area: Rect, frame: &mut Frame, state: &mut State) {
     .iter_mut()
     .enumerate()
     .map(|(i, proc)| {
-      create_proc_item(proc, i == state.selected, area.width - 2, theme)
+      let is_cur = i == state.selected;
+      create_proc_item(
+        proc,
+        is_cur,
+        area.width - 2,
+        title_format,
+        proc_format,
+        status_glyphs,
+        theme,
+      )
     })
     .collect::<Vec<_>>();
 
   let title = {
     let mut spans = vec![Span::styled("Processes", theme.pane_title(active))];
+    if let Some((by, desc)) = state.sort {
+      let arrow = if desc { "↓" } else { "↑" };
+      spans.push(Span::styled(
+        format!(" by {} {}", by, arrow),
+        theme.pane_title(false),
+      ));
+    }
     if state.quitting {
       spans.push(Span::from(" "));
       spans.push(Span::styled(
@@ -52,12 +78,52 @@ pub fn render_procs(area: Rect, frame: &mut Frame, state: &mut State) {
     .block(theme.pane(active).title(title))
     .style(Style::default().fg(Color::White));
   frame.render_stateful_widget(items, area, &mut list_state);
+
+  if let Some(drag) = state.drag.filter(|drag| drag.from != drag.to) {
+    render_drop_indicator(area, frame, state.selected, drag, theme);
+  }
+}
+
+/// Draws a line where the dragged proc is going to be inserted: under the
+/// row above the insertion point or over the top border.
+fn render_drop_indicator(
+  area: Rect,
+  frame: &mut Frame,
+  selected: usize,
+  drag: ProcDrag,
+  theme: &Theme,
+) {
+  let inner = area.inner(&Margin {
+    vertical: 1,
+    horizontal: 1,
+  });
+  // Same scrolling as in `procs_get_clicked_index`.
+  let scroll = (selected + 1).saturating_sub(inner.height as usize);
+  let above = if drag.to > drag.from {
+    Some(drag.to)
+  } else {
+    drag.to.checked_sub(1)
+  };
+  let (y, style) = match above {
+    Some(above) if above >= scroll => (
+      inner.y + (above - scroll) as u16,
+      theme.drop_indicator().add_modifier(Modifier::UNDERLINED),
+    ),
+    _ => (area.y, theme.drop_indicator()),
+  };
+  if y < inner.bottom() {
+    let line = Rect::new(inner.x, y, inner.width, 1);
+    frame.buffer_mut().set_style(line, style);
+  }
 }
 
 fn create_proc_item<'a>(
   proc_handle: &mut ProcHandle,
   is_cur: bool,
   width: u16,
+  title_format: &TitleFormat,
+  proc_format: Option<&ProcFormat>,
+  status_glyphs: &StatusGlyphs,
   theme: &Theme,
 ) -> ListItem<'a> {
   let status = if proc_handle.is_up() {
@@ -67,13 +133,29 @@ fn create_proc_item<'a>(
         .fg(Color::LightGreen)
         .add_modifier(Modifier::BOLD),
     )
+  } else if let Some(dep) = proc_handle.waiting_for().first() {
+    // The first dep the proc is waiting for and how many more there are.
+    let deps = match proc_handle.waiting_for().len() {
+      1 => dep.clone(),
+      n => format!("{} +{}", dep, n - 1),
+    };
+    Span::styled(
+      format!(" WAIT ({}) ", deps),
+      Style::default().fg(Color::Yellow),
+    )
+  } else if proc_handle.spawn_error().is_some() {
+    Span::styled(" FAILED ", Style::default().fg(Color::LightRed))
   } else {
-    match proc_handle.exit_code() {
-      Some(0) => {
+    match proc_handle.exit_status() {
+      Some(status) if status.signal.is_some() => Span::styled(
+        format!(" KILLED ({})", status.signal_name().unwrap_or_default()),
+        Style::default().fg(Color::LightRed),
+      ),
+      Some(status) if status.code == 0 => {
         Span::styled(" DOWN (0)", Style::default().fg(Color::LightBlue))
       }
-      Some(exit_code) => Span::styled(
-        format!(" DOWN ({})", exit_code),
+      Some(status) => Span::styled(
+        format!(" DOWN ({})", status.code),
         Style::default().fg(Color::LightRed),
       ),
       None => Span::styled(" DOWN ", Style::default().fg(Color::LightRed)),
@@ -86,35 +168,178 @@ fn create_proc_item<'a>(
     Span::raw(" ")
   };
 
-  let mut name = proc_handle.name().to_string();
-  let name_max = (width as usize)
-    .saturating_sub(mark.width())
-    .saturating_sub(status.width());
-  let name_len = name.chars().count();
-  if name_len > name_max {
-    name.truncate(
-      name
-        .char_indices()
-        .nth(name_max)
-        .map_or(name.len(), |(n, _)| n),
-    )
-  }
-  if name_len < name_max {
-    for _ in name_len..name_max {
-      name.push(' ');
-    }
-  }
+  // A few stray bytes are common, so the marker is shown only after a number
+  // of errors.
+  let decode_errors = if proc_handle.decode_errors() >= DECODE_ERRORS_MIN {
+    Span::styled(" ⚠", Style::default().fg(Color::Yellow))
+  } else {
+    Span::raw("")
+  };
 
-  let name_style = Style::default();
+  let critical = if proc_handle.critical() {
+    Span::styled(" 🔒", Style::default().fg(Color::Yellow))
+  } else {
+    Span::raw("")
+  };
+
+  let autorestart = if proc_handle.autorestart {
+    Span::styled(" ↻", Style::default().fg(Color::DarkGray))
+  } else {
+    Span::raw("")
+  };
+
+  let no_autostart = if proc_handle.autostart() {
+    Span::raw("")
+  } else {
+    Span::styled(" ⊘", Style::default().fg(Color::DarkGray))
+  };
+
+  let unread = match proc_handle.unread_lines() {
+    Some(0) => Span::styled(" ●", theme.unread()),
+    Some(lines) => Span::styled(format!(" ●{}", lines), theme.unread()),
+    None => Span::raw(""),
+  };
+
+  let usage = match proc_handle.usage() {
+    Some(usage) => Span::styled(
+      format!(" {:.0}% {}", usage.cpu, format_bytes(usage.rss)),
+      Style::default().fg(Color::DarkGray),
+    ),
+    None => Span::raw(""),
+  };
+
+  let trimmed = if proc_handle.scrollback_trimmed() > 0 {
+    Span::styled(" (trimmed)", Style::default().fg(Color::DarkGray))
+  } else {
+    Span::raw("")
+  };
+
+  let (title, icon_name) = proc_handle.term_title();
+  let mut name = title_format.render(proc_handle.name(), &title, &icon_name);
+  if let Some(cmd) = proc_handle.foreground_cmd() {
+    name.push_str(" → ");
+    name.push_str(cmd);
+  }
+  let name_style = match proc_handle.color() {
+    Some(color) => Style::default().fg(color),
+    None => Style::default(),
+  };
   let name_style = if proc_handle.changed() {
     name_style.add_modifier(Modifier::BOLD)
+  } else if proc_handle.is_idle() {
+    name_style.add_modifier(Modifier::DIM)
   } else {
     name_style
   };
-  let name = Span::styled(name, name_style);
 
-  ListItem::new(Line::from(vec![mark, name, status]))
-    .style(theme.get_procs_item(is_cur))
+  let entry_max = (width as usize)
+    .saturating_sub(mark.width())
+    .saturating_sub(critical.width())
+    .saturating_sub(autorestart.width())
+    .saturating_sub(no_autostart.width())
+    .saturating_sub(unread.width())
+    .saturating_sub(decode_errors.width())
+    .saturating_sub(trimmed.width())
+    .saturating_sub(usage.width());
+
+  let mut spans = vec![mark];
+  match proc_format {
+    Some(proc_format) => {
+      let pid = proc_handle.pid().map(|pid| pid.to_string());
+      let exit = match proc_handle.exit_status() {
+        Some(status) if !proc_handle.is_up() => Some(
+          status
+            .signal_name()
+            .unwrap_or_else(|| status.code.to_string()),
+        ),
+        _ => None,
+      };
+      let uptime = proc_handle.uptime().map(format_uptime);
+      let entry = proc_format.render(
+        |text| Span::raw(text.to_string()),
+        |field| match field {
+          Field::Status => {
+            format_status(proc_handle, status_glyphs, status.style)
+          }
+          Field::Name => Span::styled(name.clone(), name_style),
+          Field::Pid => Span::raw(pid.clone().unwrap_or_default()),
+          Field::Exit => {
+            Span::styled(exit.clone().unwrap_or_default(), status.style)
+          }
+          Field::Uptime => Span::raw(uptime.clone().unwrap_or_default()),
+        },
+      );
+      spans.extend(fit_spans(entry, entry_max));
+      spans.extend([
+        critical,
+        autorestart,
+        no_autostart,
+        unread,
+        decode_errors,
+        trimmed,
+        usage,
+      ]);
+    }
+    None => {
+      let name_max = entry_max.saturating_sub(status.width());
+      let name = Span::styled(name, name_style);
+      spans.extend(fit_spans(vec![name], name_max));
+      spans.extend([
+        critical,
+        autorestart,
+        no_autostart,
+        unread,
+        decode_errors,
+        trimmed,
+        usage,
+        status,
+      ]);
+    }
+  }
+
+  ListItem::new(Line::from(spans)).style(theme.get_procs_item(is_cur))
+}
+
+fn format_status<'a>(
+  proc_handle: &ProcHandle,
+  glyphs: &StatusGlyphs,
+  style: Style,
+) -> Span<'a> {
+  let glyph = if proc_handle.is_up() {
+    &glyphs.up
+  } else if proc_handle.is_failed() {
+    &glyphs.failed
+  } else {
+    &glyphs.down
+  };
+  Span::styled(glyph.clone(), style)
+}
+
+/// Cuts off or pads spans with spaces to make them exactly `width` chars
+/// long.
+fn fit_spans(spans: Vec<Span>, width: usize) -> Vec<Span> {
+  let mut left = width;
+  let mut fitted = Vec::with_capacity(spans.len() + 1);
+  for mut span in spans {
+    let len = span.content.chars().count();
+    if len > left {
+      let end = span
+        .content
+        .char_indices()
+        .nth(left)
+        .map_or(span.content.len(), |(n, _)| n);
+      span.content = span.content[..end].to_string().into();
+      fitted.push(span);
+      left = 0;
+      break;
+    }
+    left -= len;
+    fitted.push(span);
+  }
+  if left > 0 {
+    fitted.push(Span::raw(" ".repeat(left)));
+  }
+  fitted
 }
 
 pub fn procs_get_clicked_index(
@@ -138,6 +363,15 @@ pub fn procs_get_clicked_index(
   None
 }
 
+/// Whether the position is on the border between the process list and the
+/// terminal, which can be dragged to resize the list.
+pub fn procs_border_hit(area: Rect, x: u16, y: u16) -> bool {
+  area.width > 0
+    && (x + 1 == area.right() || x == area.right())
+    && area.y <= y
+    && y < area.bottom()
+}
+
 pub fn procs_check_hit(area: Rect, x: u16, y: u16) -> bool {
   area.x < x
     && area.x + area.width > x + 1
@@ -1,3 +1,4 @@
+use anyhow::Result;
 use termwiz::escape::csi::CursorStyle;
 use tui::{
   layout::{Margin, Rect},
@@ -6,63 +7,172 @@ use tui::{
   widgets::{Clear, Paragraph, Widget, Wrap},
   Frame,
 };
+use unicode_width::UnicodeWidthStr;
 
 use crate::{
-  proc::{handle::ProcViewFrame, CopyMode, Pos},
+  config::Config,
+  dedup::{count_runs, repeat_suffix},
+  highlight::{highlight_style, Highlight},
+  proc::{
+    handle::{ProcHandle, ProcViewFrame},
+    CopyMode, Pos,
+  },
   state::{Scope, State},
   theme::Theme,
+  yaml_val::Val,
 };
 
+/// When to show the scrollbar on the right border of the terminal pane.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ScrollbarMode {
+  /// Only while scrolled into the scrollback.
+  #[default]
+  Scrolled,
+  Always,
+  Never,
+}
+
+impl ScrollbarMode {
+  pub fn from_val(val: &Val) -> Result<Self> {
+    match val.as_str()? {
+      "scrolled" => Ok(ScrollbarMode::Scrolled),
+      "always" => Ok(ScrollbarMode::Always),
+      "never" => Ok(ScrollbarMode::Never),
+      _ => Err(val.error_at("Expected \"scrolled\", \"always\" or \"never\"")),
+    }
+  }
+
+  fn visible(self, screen: &vt100::Screen) -> bool {
+    match self {
+      ScrollbarMode::Scrolled => screen.scrollback() > 0,
+      ScrollbarMode::Always => true,
+      ScrollbarMode::Never => false,
+    }
+  }
+}
+
 pub fn render_term(
   area: Rect,
   frame: &mut Frame,
   state: &mut State,
+  theme: &Theme,
+  config: &Config,
   cursor_style: &mut CursorStyle,
 ) {
   if area.width < 3 || area.height < 3 {
     return;
   }
 
-  let theme = Theme::default();
-
   let active = match state.scope {
     Scope::Procs => false,
     Scope::Term | Scope::TermZoom => true,
   };
 
+  if state.interleave {
+    render_interleaved(area, frame, state, theme, active);
+    return;
+  }
+
   if let Some(proc) = state.get_current_proc() {
     let mut title = Vec::with_capacity(4);
     title.push(Span::styled("Terminal", theme.pane_title(active)));
+    if config.show_term_title {
+      // Leave room for the labels below.
+      let max_width = (area.width as usize).saturating_sub(2) / 2;
+      title.push(Span::raw(" "));
+      title.push(Span::styled(
+        proc_title(proc, max_width),
+        theme.pane_title(active),
+      ));
+    }
     match proc.copy_mode() {
       CopyMode::None(_) => (),
       CopyMode::Start(_, _) | CopyMode::Range(_, _, _) => {
+        let label = if proc.normal_screen_view() {
+          "NORMAL SCREEN"
+        } else {
+          "COPY MODE"
+        };
         title.push(Span::raw(" "));
-        title.push(Span::styled("COPY MODE", theme.copy_mode_label()));
+        title.push(Span::styled(label, theme.copy_mode_label()));
       }
     };
+    if state.literal_next {
+      title.push(Span::raw(" "));
+      title.push(Span::styled("SEND NEXT KEY", theme.copy_mode_label()));
+    }
+    if !proc.wrap() {
+      title.push(Span::raw(" "));
+      let label = match proc.h_offset() {
+        0 => "nowrap".to_string(),
+        h_offset => format!("nowrap +{}", h_offset),
+      };
+      title.push(Span::styled(label, theme.pane_title(active)));
+    }
+    if !state.mouse_capture {
+      title.push(Span::raw(" "));
+      title.push(Span::styled("mouse off", theme.pane_title(active)));
+    }
+    if proc.dedup_lines() {
+      title.push(Span::raw(" "));
+      title.push(Span::styled("dedup", theme.pane_title(active)));
+    }
+    if !proc.cfg().follow_output {
+      title.push(Span::raw(" "));
+      title.push(Span::styled("pinned", theme.pane_title(active)));
+    }
+    if proc.scrollback_len() != proc.cfg().scrollback_len {
+      title.push(Span::raw(" "));
+      title.push(Span::styled(
+        format!("scrollback: {}", proc.scrollback_len()),
+        theme.pane_title(active),
+      ));
+    }
 
     let block = theme.pane(active).title(Line::from(title));
     frame.render_widget(Clear, area);
     frame.render_widget(block, area);
 
+    if let Some(len) = proc.raw_len() {
+      let text = Text::styled(
+        format!("(raw capture {} bytes)", len),
+        Style::default().fg(Color::DarkGray),
+      );
+      frame.render_widget(
+        Paragraph::new(text),
+        area.inner(&Margin {
+          vertical: 1,
+          horizontal: 1,
+        }),
+      );
+      return;
+    }
+
     match &proc.lock_view() {
       ProcViewFrame::Empty => (),
       ProcViewFrame::Vt(vt) => {
+        // Copy mode positions are in screen rows, so it is always displayed
+        // wrapped and without collapsed lines.
+        let (wrap, dedup) = match proc.copy_mode() {
+          CopyMode::None(_) => (proc.wrap(), proc.dedup_lines()),
+          CopyMode::Start(_, _) | CopyMode::Range(_, _, _) => (true, false),
+        };
+        let gutter = proc.gutter();
         let (screen, cursor) = match proc.copy_mode() {
           CopyMode::None(_) => {
             let screen = vt.screen();
-            let cursor = if screen.hide_cursor() {
+            let cursor = if screen.hide_cursor() || !wrap {
               None
             } else {
               let cursor = screen.cursor_position();
-              Some((area.x + 1 + cursor.1, area.y + 1 + cursor.0))
+              Some((area.x + 1 + gutter + cursor.1, area.y + 1 + cursor.0))
             };
             (screen, cursor)
           }
           CopyMode::Start(screen, pos) | CopyMode::Range(screen, _, pos) => {
             let y = area.y as i32 + 1 + (pos.y + screen.scrollback() as i32);
             let cursor = if y >= 0 {
-              Some((area.x + 1 + pos.x as u16, y as u16))
+              Some((area.x + 1 + gutter + pos.x as u16, y as u16))
             } else {
               None
             };
@@ -70,7 +180,11 @@ pub fn render_term(
           }
         };
 
-        let term = UiTerm::new(screen, proc.copy_mode());
+        let h_offset = if wrap { 0 } else { proc.h_offset() };
+        let term = UiTerm::new(screen, proc.copy_mode(), wrap, h_offset)
+          .gutter(gutter, theme.line_number())
+          .dedup(dedup)
+          .highlights(&config.highlights);
         frame.render_widget(
           term,
           area.inner(&Margin {
@@ -79,6 +193,10 @@ pub fn render_term(
           }),
         );
 
+        if config.scrollbar.visible(screen) {
+          render_scrollbar(area, frame.buffer_mut(), screen, theme);
+        }
+
         if active {
           if let Some(cursor) = cursor {
             frame.set_cursor(cursor.0, cursor.1);
@@ -101,29 +219,300 @@ pub fn render_term(
   }
 }
 
+/// Last lines of output of all procs, each prefixed with the proc name.
+fn render_interleaved(
+  area: Rect,
+  frame: &mut Frame,
+  state: &State,
+  theme: &Theme,
+  active: bool,
+) {
+  let title = Span::styled("Interleaved", theme.pane_title(active));
+  frame.render_widget(Clear, area);
+  frame.render_widget(theme.pane(active).title(title), area);
+
+  let inner = area.inner(&Margin {
+    vertical: 1,
+    horizontal: 1,
+  });
+  let name_width = state
+    .procs
+    .iter()
+    .map(|p| p.name().width())
+    .max()
+    .unwrap_or(0);
+  let skip = state
+    .interleaved
+    .len()
+    .saturating_sub(inner.height as usize);
+  let lines = state
+    .interleaved
+    .iter()
+    .skip(skip)
+    .map(|(id, text)| {
+      let proc = state.get_proc(*id);
+      let name = proc.map_or("", |p| p.name());
+      let color = proc
+        .and_then(|p| p.color())
+        .unwrap_or_else(|| theme.proc_color(*id));
+      let pad = " ".repeat(name_width.saturating_sub(name.width()));
+      let prefix = format!("[{}]{} ", name, pad);
+      Line::from(vec![
+        Span::styled(prefix, Style::default().fg(color)),
+        Span::raw(text.as_str()),
+      ])
+    })
+    .collect::<Vec<_>>();
+  frame.render_widget(Paragraph::new(lines), inner);
+}
+
+/// Name and status of the process, cut off at `max_width` chars.
+fn proc_title(proc: &ProcHandle, max_width: usize) -> String {
+  let status = if proc.is_up() {
+    "up".to_string()
+  } else {
+    match proc.exit_code() {
+      Some(code) => format!("exit {}", code),
+      None => "down".to_string(),
+    }
+  };
+  format_title(proc.name(), &status, max_width)
+}
+
+fn format_title(name: &str, status: &str, max_width: usize) -> String {
+  let status = format!(" ({})", status);
+  let name_width = max_width.saturating_sub(status.chars().count());
+  let name = if name.chars().count() > name_width {
+    let mut name = name
+      .chars()
+      .take(name_width.saturating_sub(1))
+      .collect::<String>();
+    name.push('…');
+    name
+  } else {
+    name.to_string()
+  };
+  format!("{}{}", name, status)
+}
+
+/// Draws the scrollbar thumb over the right border of the pane `area`.
+fn render_scrollbar(
+  area: Rect,
+  buf: &mut tui::buffer::Buffer,
+  screen: &vt100::Screen,
+  theme: &Theme,
+) {
+  let height = area.height.saturating_sub(2);
+  let (top, size) = scrollbar_thumb(screen, height);
+  let x = area.x + area.width - 1;
+  for y in top..top + size {
+    buf
+      .get_mut(x, area.y + 1 + y)
+      .set_char('█')
+      .set_style(theme.scrollbar());
+  }
+}
+
+/// Position and size of the scrollbar thumb in a track of `height` cells.
+fn scrollbar_thumb(screen: &vt100::Screen, height: u16) -> (u16, u16) {
+  if height == 0 {
+    return (0, 0);
+  }
+  let height = height as usize;
+  let rows = screen.size().0 as usize;
+  let above = screen.scrollback_rows();
+  let size = (height * rows / (above + rows).max(1)).clamp(1, height);
+  let top = match above {
+    0 => 0,
+    above => (height - size) * (above - screen.scrollback()) / above,
+  };
+  (top as u16, size as u16)
+}
+
+/// If the pane position (`x`, `y`) is on the scrollbar, returns the current
+/// scrollback offset and the offset the click points to.
+pub fn scrollbar_hit(
+  area: Rect,
+  x: u16,
+  y: u16,
+  proc: &ProcHandle,
+  mode: ScrollbarMode,
+) -> Option<(usize, usize)> {
+  let height = area.height.saturating_sub(2);
+  if x + 1 != area.x + area.width || y <= area.y || y > area.y + height {
+    return None;
+  }
+  let offsets = |screen: &vt100::Screen| {
+    if !mode.visible(screen) {
+      return None;
+    }
+    let above = screen.scrollback_rows();
+    let (_, size) = scrollbar_thumb(screen, height);
+    let track = (height - size) as usize;
+    let pos = (y - area.y - 1).saturating_sub(size / 2) as usize;
+    let target = match track {
+      0 => screen.scrollback(),
+      track => above - above * pos.min(track) / track,
+    };
+    Some((screen.scrollback(), target))
+  };
+  match proc.copy_mode() {
+    CopyMode::None(_) => match proc.lock_view() {
+      ProcViewFrame::Vt(vt) => offsets(vt.screen()),
+      ProcViewFrame::Empty | ProcViewFrame::Err(_) => None,
+    },
+    CopyMode::Start(screen, _) | CopyMode::Range(screen, _, _) => {
+      offsets(screen)
+    }
+  }
+}
+
 pub struct UiTerm<'a> {
   screen: &'a vt100::Screen,
   copy_mode: &'a CopyMode,
+  wrap: bool,
+  h_offset: usize,
+  gutter: u16,
+  gutter_style: Style,
+  highlights: &'a [Highlight],
+  dedup: bool,
 }
 
 impl<'a> UiTerm<'a> {
-  pub fn new(screen: &'a vt100::Screen, copy_mode: &'a CopyMode) -> Self {
-    UiTerm { screen, copy_mode }
+  pub fn new(
+    screen: &'a vt100::Screen,
+    copy_mode: &'a CopyMode,
+    wrap: bool,
+    h_offset: usize,
+  ) -> Self {
+    UiTerm {
+      screen,
+      copy_mode,
+      wrap,
+      h_offset,
+      gutter: 0,
+      gutter_style: Style::default(),
+      highlights: &[],
+      dedup: false,
+    }
   }
-}
 
-impl Widget for UiTerm<'_> {
-  fn render(self, area: Rect, buf: &mut tui::buffer::Buffer) {
+  /// Collapse runs of identical lines into one line with a repeat count.
+  pub fn dedup(mut self, dedup: bool) -> Self {
+    self.dedup = dedup;
+    self
+  }
+
+  pub fn highlights(mut self, highlights: &'a [Highlight]) -> Self {
+    self.highlights = highlights;
+    self
+  }
+
+  /// Highlight style of the row.
+  fn row_style(&self, row: i32) -> Option<Style> {
+    if self.highlights.is_empty() {
+      return None;
+    }
+    let cols = self.screen.size().1;
+    let text = self.screen.row_contents_at(row, 0, cols)?;
+    highlight_style(self.highlights, &text)
+  }
+
+  /// Show line numbers in a column of `width` cells on the left.
+  pub fn gutter(mut self, width: u16, style: Style) -> Self {
+    self.gutter = width;
+    self.gutter_style = style;
+    self
+  }
+
+  /// Part of the area left for the terminal contents.
+  fn content_area(&self, area: Rect) -> Rect {
+    let gutter = self.gutter.min(area.width);
+    Rect {
+      x: area.x + gutter,
+      width: area.width - gutter,
+      ..area
+    }
+  }
+
+  /// Renders number of the screen `row` (counting from the start of the
+  /// output) at line `y` of the gutter.
+  fn render_line_number(
+    &self,
+    area: Rect,
+    buf: &mut tui::buffer::Buffer,
+    y: u16,
+    row: Option<i32>,
+  ) {
+    let width = self.gutter.min(area.width) as usize;
+    if width == 0 {
+      return;
+    }
+    let text = match row {
+      Some(row) => {
+        let n = self.screen.rows_above() as i64 + row as i64 + 1;
+        format!("{:>w$} ", n, w = width - 1)
+      }
+      None => " ".repeat(width),
+    };
+    buf.set_stringn(area.x, area.y + y, text, width, self.gutter_style);
+  }
+
+  fn render_wrapped(&self, area: Rect, buf: &mut tui::buffer::Buffer) {
     let screen = self.screen;
+    let full_area = area;
+    let area = self.content_area(area);
+    let cols = screen.size().1 as usize;
+
+    // Screen row displayed at each line with the repeat count to show after
+    // it.
+    let rows: Vec<(i32, Option<(usize, usize)>)> = if self.dedup {
+      let rows = self
+        .display_lines(area.height as usize)
+        .into_iter()
+        .flat_map(|(line, count, width)| {
+          let last = line.len() - 1;
+          let x = width.saturating_sub(last * cols);
+          line.into_iter().enumerate().map(move |(i, row)| {
+            let repeats = if i == last && count > 1 {
+              Some((x, count))
+            } else {
+              None
+            };
+            (row, repeats)
+          })
+        })
+        .collect::<Vec<_>>();
+      // The first line may only partly fit, keep the end of the output.
+      let skip = rows.len().saturating_sub(area.height as usize);
+      rows.into_iter().skip(skip).collect()
+    } else {
+      (0..area.height as i32).map(|row| (row, None)).collect()
+    };
 
-    for row in 0..area.height {
+    for y in 0..area.height {
+      let (row, repeats) = match rows.get(y as usize) {
+        Some(row) => *row,
+        None => {
+          self.render_line_number(full_area, buf, y, None);
+          for col in 0..area.width {
+            buf.get_mut(area.x + col, area.y + y).reset();
+          }
+          continue;
+        }
+      };
+      self.render_line_number(full_area, buf, y, Some(row));
+      let row_style = self.row_style(row);
       for col in 0..area.width {
-        let to_cell = buf.get_mut(area.x + col, area.y + row);
-        if let Some(cell) = screen.cell(row, col) {
+        let to_cell = buf.get_mut(area.x + col, area.y + y);
+        if let Some(cell) = screen.cell_at(row, col) {
           *to_cell = cell.to_tui();
           if !cell.has_contents() {
             to_cell.set_char(' ');
           }
+          if let Some(style) = row_style {
+            to_cell.set_style(style);
+          }
 
           let copy_mode = match self.copy_mode {
             CopyMode::None(_) => None,
@@ -135,7 +524,7 @@ impl Widget for UiTerm<'_> {
               start,
               end,
               &Pos {
-                y: (row as i32) - screen.scrollback() as i32,
+                y: row - screen.scrollback() as i32,
                 x: col as i32,
               },
             ) {
@@ -148,6 +537,147 @@ impl Widget for UiTerm<'_> {
           to_cell.set_char('?');
         }
       }
+      if let Some((x, count)) = repeats {
+        render_repeat_count(area, buf, y, x, count);
+      }
+    }
+  }
+
+  /// Rows from `top` (negative for scrollback rows above the visible area)
+  /// to the bottom of the screen grouped into lines joined by wrapping, with
+  /// the text of each line.
+  fn lines(&self, top: i32) -> Vec<(Vec<i32>, String)> {
+    let screen = self.screen;
+    let cols = screen.size().1;
+    let mut lines: Vec<(Vec<i32>, String)> = Vec::new();
+    let mut continued = false;
+    for row in top..screen.size().0 as i32 {
+      let text = match screen.row_contents_at(row, 0, cols) {
+        Some(text) => text,
+        None => continue,
+      };
+      match lines.last_mut() {
+        Some((line, line_text)) if continued => {
+          line.push(row);
+          line_text.push_str(&text);
+        }
+        _ => lines.push((vec![row], text)),
+      }
+      continued = screen.row_wrapped_at(row);
+    }
+    lines
+  }
+
+  /// Lines to display with the number of repeats and the width of the text
+  /// of each. Runs of identical lines are collapsed if dedup is enabled, and
+  /// then lines from the scrollback are added on top to fill `height` rows.
+  fn display_lines(&self, height: usize) -> Vec<(Vec<i32>, usize, usize)> {
+    if !self.dedup {
+      return self
+        .lines(0)
+        .into_iter()
+        .map(|(line, _)| (line, 1, 0))
+        .collect();
+    }
+    let mut top = 0;
+    loop {
+      let lines = self.lines(top);
+      let texts = lines.iter().map(|(_, text)| text).collect::<Vec<_>>();
+      let runs = count_runs(&texts);
+      let shown = if self.wrap {
+        runs.iter().map(|(i, _)| lines[*i].0.len()).sum()
+      } else {
+        runs.len()
+      };
+      let all = self.screen.row_contents_at(top - 1, 0, 0).is_none();
+      if shown >= height || all {
+        let mut lines = lines;
+        let mut display = runs
+          .into_iter()
+          .map(|(i, count)| {
+            let line = std::mem::take(&mut lines[i].0);
+            (line, count, lines[i].1.width())
+          })
+          .collect::<Vec<_>>();
+        // Drop lines that were pulled from the scrollback but don't fit.
+        let mut shown = shown;
+        while let Some((line, _, _)) = display.first() {
+          let rows = if self.wrap { line.len() } else { 1 };
+          if line[0] >= 0 || shown - rows < height {
+            break;
+          }
+          shown -= rows;
+          display.remove(0);
+        }
+        return display;
+      }
+      top = (top * 2).min(-(height as i32));
+    }
+  }
+
+  /// Renders rows joined by wrapping as a single line, cutting off the part
+  /// that doesn't fit. Lines start at `h_offset` column.
+  fn render_unwrapped(&self, area: Rect, buf: &mut tui::buffer::Buffer) {
+    let screen = self.screen;
+    let full_area = area;
+    let area = self.content_area(area);
+    let cols = screen.size().1;
+    if cols == 0 {
+      return;
+    }
+    let lines = self.display_lines(area.height as usize);
+
+    for (y, (line, count, width)) in
+      lines.iter().take(area.height as usize).enumerate()
+    {
+      self.render_line_number(full_area, buf, y as u16, line.first().copied());
+      let row_styles = line
+        .iter()
+        .map(|row| self.row_style(*row))
+        .collect::<Vec<_>>();
+      for x in 0..area.width {
+        let to_cell = buf.get_mut(area.x + x, area.y + y as u16);
+        let col = self.h_offset + x as usize;
+        let row = line.get(col / cols as usize);
+        let row_style = row_styles.get(col / cols as usize).copied().flatten();
+        let col = (col % cols as usize) as u16;
+        match row.and_then(|row| screen.cell_at(*row, col)) {
+          Some(cell) => {
+            *to_cell = cell.to_tui();
+            if !cell.has_contents() {
+              to_cell.set_char(' ');
+            }
+            if let Some(style) = row_style {
+              to_cell.set_style(style);
+            }
+          }
+          None => {
+            to_cell.reset();
+          }
+        }
+      }
+      if *count > 1 {
+        let x = width.saturating_sub(self.h_offset);
+        render_repeat_count(area, buf, y as u16, x, *count);
+      }
+    }
+    for y in lines.len() as u16..area.height {
+      self.render_line_number(full_area, buf, y, None);
+      for x in 0..area.width {
+        buf.get_mut(area.x + x, area.y + y).reset();
+      }
+    }
+  }
+}
+
+impl Widget for UiTerm<'_> {
+  fn render(self, area: Rect, buf: &mut tui::buffer::Buffer) {
+    let screen = self.screen;
+
+    if self.wrap {
+      self.render_wrapped(area, buf);
+    } else {
+      self.render_unwrapped(area, buf);
     }
 
     let scrollback = screen.scrollback();
@@ -167,9 +697,57 @@ impl Widget for UiTerm<'_> {
   }
 }
 
+/// Draws the number of repeats of a collapsed line after its text, which ends
+/// at column `x`.
+fn render_repeat_count(
+  area: Rect,
+  buf: &mut tui::buffer::Buffer,
+  y: u16,
+  x: usize,
+  count: usize,
+) {
+  let text = repeat_suffix(count);
+  let x = x.min((area.width as usize).saturating_sub(text.width())) as u16;
+  buf.set_stringn(
+    area.x + x,
+    area.y + y,
+    text,
+    (area.width - x) as usize,
+    Style::default().fg(Color::DarkGray),
+  );
+}
+
 pub fn term_check_hit(area: Rect, x: u16, y: u16) -> bool {
   area.x <= x
     && area.x + area.width >= x + 1
     && area.y <= y
     && area.y + area.height >= y + 1
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn title_cut_off() {
+    assert_eq!(format_title("server", "up", 20), "server (up)");
+    assert_eq!(
+      format_title("long-server-name", "exit 1", 16),
+      "long-s… (exit 1)"
+    );
+  }
+
+  #[test]
+  fn dedup_fills_from_scrollback() {
+    let mut vt = vt100::Parser::new(3, 10, 100);
+    vt.process(b"a\r\nb\r\nx\r\nx\r\nx\r\nx\r\ny");
+    let copy_mode = CopyMode::None(None);
+    let term = UiTerm::new(vt.screen(), &copy_mode, true, 0).dedup(true);
+    let lines = term
+      .display_lines(3)
+      .into_iter()
+      .map(|(line, count, _)| (line, count))
+      .collect::<Vec<_>>();
+    assert_eq!(lines, vec![(vec![-3], 1), (vec![-2], 4), (vec![2], 1)]);
+  }
+}
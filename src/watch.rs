@@ -0,0 +1,228 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use regex::Regex;
+use serde_yaml::Value;
+
+use crate::yaml_val::Val;
+
+/// Matches of the same pattern within this time are reported once.
+const DEBOUNCE: Duration = Duration::from_secs(1);
+/// Longer lines are cut off before matching.
+const LINE_MAX_LEN: usize = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WatchAction {
+  /// Desktop notification sent by the terminal (OSC 9).
+  Notify,
+  Bell,
+  /// Select the proc that printed the line.
+  Focus,
+  /// The proc is ready (`ready_when`). Reported in headless mode.
+  Ready,
+}
+
+/// Rule from the `watch_patterns` setting.
+#[derive(Clone, Debug)]
+pub struct WatchPattern {
+  /// Name of the watched proc. All procs are watched if not set.
+  pub proc: Option<String>,
+  pub regex: Regex,
+  pub action: WatchAction,
+}
+
+impl WatchPattern {
+  pub fn from_val(val: &Val) -> Result<Self> {
+    let map = val.as_object()?;
+
+    let proc = match map.get(&Value::from("proc")) {
+      Some(proc) => Some(proc.as_str()?.to_string()),
+      None => None,
+    };
+
+    let regex = match map.get(&Value::from("pattern")) {
+      Some(pattern) => Regex::new(pattern.as_str()?)
+        .map_err(|err| pattern.error_at(err.to_string()))?,
+      None => bail!(val.error_at("Expected \"pattern\" field")),
+    };
+
+    let action = match map.get(&Value::from("action")) {
+      Some(action) => match action.as_str()? {
+        "notify" => WatchAction::Notify,
+        "bell" => WatchAction::Bell,
+        "focus" => WatchAction::Focus,
+        _ => {
+          bail!(action.error_at("Expected \"notify\", \"bell\" or \"focus\""))
+        }
+      },
+      None => WatchAction::Notify,
+    };
+
+    Ok(WatchPattern {
+      proc,
+      regex,
+      action,
+    })
+  }
+}
+
+/// Patterns that apply to the proc named `name`.
+pub fn patterns_for(
+  patterns: &[WatchPattern],
+  name: &str,
+) -> Vec<WatchPattern> {
+  patterns
+    .iter()
+    .filter(|p| p.proc.as_ref().map_or(true, |proc| proc == name))
+    .cloned()
+    .collect()
+}
+
+#[derive(Clone, Copy, Default)]
+enum EscState {
+  #[default]
+  Text,
+  Esc,
+  /// Escape sequence with one more byte (e.g. charset selection).
+  EscArg,
+  Csi,
+  Osc,
+  OscEsc,
+}
+
+/// Splits raw output into lines and strips escape sequences.
+#[derive(Default)]
+pub struct LineSplitter {
+  line: Vec<u8>,
+  state: EscState,
+}
+
+impl LineSplitter {
+  /// Returns the lines completed by `bytes`.
+  pub fn feed(&mut self, bytes: &[u8]) -> Vec<String> {
+    let mut lines = Vec::new();
+    for &b in bytes {
+      self.state = match self.state {
+        EscState::Text => match b {
+          0x1b => EscState::Esc,
+          b'\n' => {
+            lines.push(String::from_utf8_lossy(&self.line).into_owned());
+            self.line.clear();
+            EscState::Text
+          }
+          b if b < 0x20 && b != b'\t' => EscState::Text,
+          b => {
+            if self.line.len() < LINE_MAX_LEN {
+              self.line.push(b);
+            }
+            EscState::Text
+          }
+        },
+        EscState::Esc => match b {
+          b'[' => EscState::Csi,
+          b']' => EscState::Osc,
+          b'(' | b')' | b'*' | b'+' | b'#' | b'%' => EscState::EscArg,
+          _ => EscState::Text,
+        },
+        EscState::EscArg => EscState::Text,
+        EscState::Csi => match b {
+          0x40..=0x7e => EscState::Text,
+          _ => EscState::Csi,
+        },
+        EscState::Osc => match b {
+          0x07 => EscState::Text,
+          0x1b => EscState::OscEsc,
+          _ => EscState::Osc,
+        },
+        EscState::OscEsc => EscState::Text,
+      };
+    }
+    lines
+  }
+
+  /// Returns the last line if it wasn't completed by a newline. Called when
+  /// there is no more output.
+  pub fn finish(&mut self) -> Option<String> {
+    self.state = EscState::Text;
+    if self.line.is_empty() {
+      return None;
+    }
+    let line = String::from_utf8_lossy(&self.line).into_owned();
+    self.line.clear();
+    Some(line)
+  }
+}
+
+/// Matches lines of output against patterns.
+pub struct Watcher {
+  patterns: Vec<WatchPattern>,
+  last_match: Vec<Option<Instant>>,
+  lines: LineSplitter,
+}
+
+impl Watcher {
+  pub fn new(patterns: Vec<WatchPattern>) -> Self {
+    Watcher {
+      last_match: vec![None; patterns.len()],
+      patterns,
+      lines: LineSplitter::default(),
+    }
+  }
+
+  /// Returns actions of patterns matching the lines completed by `bytes`
+  /// together with the matched line.
+  pub fn feed(&mut self, bytes: &[u8]) -> Vec<(WatchAction, String)> {
+    let mut matches = Vec::new();
+    let now = Instant::now();
+    for line in self.lines.feed(bytes) {
+      for (pattern, last) in self.patterns.iter().zip(&mut self.last_match) {
+        if !pattern.regex.is_match(&line) {
+          continue;
+        }
+        if last.map_or(true, |last| now.duration_since(last) >= DEBOUNCE) {
+          *last = Some(now);
+          matches.push((pattern.action, line.trim_end().to_string()));
+        }
+      }
+    }
+    matches
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn watcher(pattern: &str) -> Watcher {
+    Watcher::new(vec![WatchPattern {
+      proc: None,
+      regex: Regex::new(pattern).unwrap(),
+      action: WatchAction::Bell,
+    }])
+  }
+
+  #[test]
+  fn strips_escapes() {
+    let mut watcher = watcher("^error: x$");
+    let matches = watcher.feed(b"\x1b[31merror\x1b[m: \x1b]0;t\x07x\r\n");
+    assert_eq!(matches, vec![(WatchAction::Bell, "error: x".to_string())]);
+  }
+
+  #[test]
+  fn waits_for_line_end() {
+    let mut watcher = watcher("panic");
+    assert!(watcher.feed(b"thread main pan").is_empty());
+    assert_eq!(watcher.feed(b"icked\n").len(), 1);
+    // Debounced.
+    assert!(watcher.feed(b"panic\n").is_empty());
+  }
+
+  #[test]
+  fn finish_partial_line() {
+    let mut lines = LineSplitter::default();
+    assert_eq!(lines.feed(b"one\ntw"), vec!["one".to_string()]);
+    assert!(lines.feed(b"o\x1b[1m").is_empty());
+    assert_eq!(lines.finish(), Some("two".to_string()));
+    assert_eq!(lines.finish(), None);
+  }
+}
@@ -1,4 +1,9 @@
-use std::{env::consts::OS, rc::Rc};
+use std::{
+  env::consts::OS,
+  fmt,
+  path::{Path, PathBuf},
+  rc::Rc,
+};
 
 use anyhow::bail;
 use indexmap::IndexMap;
@@ -16,24 +21,64 @@ impl Trace {
     Trace(Some(Rc::new(Box::new((seg.to_string(), self.clone())))))
   }
 
-  pub fn to_string(&self) -> String {
-    let mut str = String::new();
-    fn add(buf: &mut String, trace: &Trace) {
-      match &trace.0 {
-        Some(part) => {
-          add(buf, &part.1);
-          buf.push('.');
-          buf.push_str(&part.0);
-        }
-        None => buf.push_str("<config>"),
+  fn segments(&self) -> Vec<String> {
+    let mut segments = Vec::new();
+    let mut trace = self;
+    while let Some(part) = &trace.0 {
+      segments.push(part.0.clone());
+      trace = &part.1;
+    }
+    segments.reverse();
+    segments
+  }
+}
+
+/// Error about a config value. Keeps the path to the value to find it in the
+/// config file.
+#[derive(Debug)]
+pub struct ValError {
+  msg: String,
+  path: Vec<String>,
+  file: Option<PathBuf>,
+}
+
+impl ValError {
+  /// Keys and array indexes leading to the value.
+  pub fn path(&self) -> &[String] {
+    &self.path
+  }
+
+  /// File with the value, if it's known to be other than the config file
+  /// (e.g. an included file).
+  pub fn file(&self) -> Option<&Path> {
+    self.file.as_deref()
+  }
+
+  /// Marks a `ValError` as coming from `file`. Other errors are returned as
+  /// is.
+  pub fn in_file(err: anyhow::Error, file: &Path) -> anyhow::Error {
+    match err.downcast::<ValError>() {
+      Ok(mut err) => {
+        err.file.get_or_insert_with(|| file.to_path_buf());
+        err.into()
       }
+      Err(err) => err,
     }
-    add(&mut str, self);
+  }
+}
 
-    str
+impl fmt::Display for ValError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} at <config>", self.msg)?;
+    for seg in &self.path {
+      write!(f, ".{}", seg)?;
+    }
+    Ok(())
   }
 }
 
+impl std::error::Error for ValError {}
+
 pub struct Val<'a>(&'a Value, Trace);
 
 impl<'a> Val<'a> {
@@ -75,48 +120,46 @@ impl<'a> Val<'a> {
         return Ok((v, trace.add("$else")));
       }
 
-      anyhow::bail!(
-        "No matching condition found at {}. Use \"$else\" for default value.",
-        trace.to_string(),
-      )
+      Err(error_at(
+        "No matching condition found. Use \"$else\" for default value",
+        &trace,
+      ))
     } else {
-      anyhow::bail!("Expected \"os\" at {}", trace.add("$select").to_string())
+      Err(error_at("Expected \"os\"", &trace.add("$select")))
     }
   }
 
   pub fn error_at<T: AsRef<str>>(&self, msg: T) -> anyhow::Error {
-    anyhow::format_err!("{} at {}", msg.as_ref(), self.1.to_string())
+    error_at(msg, &self.1)
   }
 
   pub fn as_bool(&self) -> anyhow::Result<bool> {
-    self.0.as_bool().ok_or_else(|| {
-      anyhow::format_err!("Expected bool at {}", self.1.to_string())
-    })
+    self
+      .0
+      .as_bool()
+      .ok_or_else(|| self.error_at("Expected bool"))
   }
 
   pub fn as_usize(&self) -> anyhow::Result<usize> {
     self
       .0
       .as_u64()
-      .ok_or_else(|| {
-        anyhow::format_err!("Expected int at {}", self.1.to_string())
-      })
+      .ok_or_else(|| self.error_at("Expected int"))
       .map(|x| x as usize)
   }
 
   pub fn as_str(&self) -> anyhow::Result<&str> {
-    self.0.as_str().ok_or_else(|| {
-      anyhow::format_err!("Expected string at {}", self.1.to_string())
-    })
+    self
+      .0
+      .as_str()
+      .ok_or_else(|| self.error_at("Expected string"))
   }
 
   pub fn as_array(&self) -> anyhow::Result<Vec<Val>> {
     self
       .0
       .as_sequence()
-      .ok_or_else(|| {
-        anyhow::format_err!("Expected array at {}", self.1.to_string())
-      })?
+      .ok_or_else(|| self.error_at("Expected array"))?
       .iter()
       .enumerate()
       .map(|(i, item)| Val::create(item, self.1.add(i)))
@@ -127,9 +170,7 @@ impl<'a> Val<'a> {
     self
       .0
       .as_mapping()
-      .ok_or_else(|| {
-        anyhow::format_err!("Expected object at {}", self.1.to_string())
-      })?
+      .ok_or_else(|| self.error_at("Expected object"))?
       .iter()
       .map(|(k, item)| {
         #[inline]
@@ -146,6 +187,15 @@ impl<'a> Val<'a> {
   }
 }
 
+fn error_at<T: AsRef<str>>(msg: T, trace: &Trace) -> anyhow::Error {
+  ValError {
+    msg: msg.as_ref().to_string(),
+    path: trace.segments(),
+    file: None,
+  }
+  .into()
+}
+
 pub fn value_to_string(value: &Value) -> anyhow::Result<String> {
   match value {
     Value::Null => Ok("null".to_string()),
@@ -161,3 +211,187 @@ pub fn value_to_string(value: &Value) -> anyhow::Result<String> {
     Value::Tagged(_) => anyhow::bail!("Yaml tags are not supported"),
   }
 }
+
+/// Value in the config source: line, column of the key or the `-` of an array
+/// item and whether it's an array item.
+#[derive(Clone, Copy)]
+struct Node {
+  line: usize,
+  col: usize,
+  item: bool,
+}
+
+/// Finds the line and column (1-based) of the value at `path` in yaml or json
+/// source. Only indentation is looked at, so flow collections aren't entered.
+/// Returns `None` unless the full path is found.
+pub fn find_position(source: &str, path: &[String]) -> Option<(usize, usize)> {
+  let lines = source.lines().collect::<Vec<_>>();
+  let mut node = None;
+  for seg in path {
+    let next = seg
+      .parse::<usize>()
+      .ok()
+      .and_then(|index| find_item(&lines, node, index))
+      .or_else(|| find_key(&lines, node, seg))?;
+    node = Some(next);
+  }
+  node.map(|node| (node.line + 1, node.col + 1))
+}
+
+fn find_key(lines: &[&str], node: Option<Node>, key: &str) -> Option<Node> {
+  let children = children(lines, node);
+  let level = children.first()?.1;
+  children
+    .into_iter()
+    .filter(|(_, col)| *col == level)
+    .find(|(line, col)| is_key(&lines[*line][*col..], key))
+    .map(|(line, col)| Node {
+      line,
+      col,
+      item: false,
+    })
+}
+
+fn find_item(lines: &[&str], node: Option<Node>, index: usize) -> Option<Node> {
+  let children = children(lines, node);
+  let level = children.first()?.1;
+  children
+    .into_iter()
+    .filter(|(line, col)| *col == level && is_item(&lines[*line][*col..]))
+    .nth(index)
+    .map(|(line, col)| Node {
+      line,
+      col,
+      item: true,
+    })
+}
+
+/// Lines (with their indentation) nested in the node. Content after `- ` of
+/// an array item counts as a line of its own.
+fn children(lines: &[&str], node: Option<Node>) -> Vec<(usize, usize)> {
+  let mut children = Vec::new();
+  let start = match node {
+    Some(node) => {
+      if node.item {
+        let rest = &lines[node.line][node.col + 1..];
+        let trimmed = rest.trim_start();
+        if !trimmed.is_empty() && !trimmed.starts_with('#') {
+          let col = node.col + 1 + rest.len() - trimmed.len();
+          children.push((node.line, col));
+        }
+      }
+      node.line + 1
+    }
+    None => 0,
+  };
+  for (i, line) in lines.iter().enumerate().skip(start) {
+    let trimmed = line.trim_start_matches(' ');
+    // Skip lines with only brackets of json.
+    let content =
+      trimmed.trim_matches(|c: char| c.is_whitespace() || "{}[],".contains(c));
+    if content.is_empty() || trimmed.starts_with('#') {
+      continue;
+    }
+    let indent = line.len() - trimmed.len();
+    if let Some(node) = node {
+      // Arrays in mappings may be on the same level as the key.
+      let nested = indent > node.col
+        || (!node.item && indent == node.col && is_item(trimmed));
+      if !nested {
+        break;
+      }
+    }
+    children.push((i, indent));
+  }
+  children
+}
+
+fn is_key(text: &str, key: &str) -> bool {
+  let quoted = [format!("\"{}\"", key), format!("'{}'", key)];
+  if let Some(rest) = quoted.iter().find_map(|q| text.strip_prefix(q.as_str()))
+  {
+    return rest.trim_start().starts_with(':');
+  }
+  match text
+    .strip_prefix(key)
+    .and_then(|rest| rest.strip_prefix(':'))
+  {
+    Some(rest) => rest.is_empty() || rest.starts_with(char::is_whitespace),
+    None => false,
+  }
+}
+
+fn is_item(text: &str) -> bool {
+  text == "-" || text.starts_with("- ")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn position(source: &str, path: &[&str]) -> Option<(usize, usize)> {
+    let path = path.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    find_position(source, &path)
+  }
+
+  #[test]
+  fn yaml() {
+    let source = "\
+procs:
+  # comment
+  web:
+    cmd: [\"npm\", \"start\"]
+    stop: SIGFOO
+  api:
+    cmd:
+    - cargo
+    -   run
+    env:
+      - name: a
+        stop: x
+";
+    assert_eq!(position(source, &["procs", "web", "stop"]), Some((5, 5)));
+    assert_eq!(
+      position(source, &["procs", "api", "cmd", "1"]),
+      Some((9, 5))
+    );
+    assert_eq!(
+      position(source, &["procs", "api", "env", "0", "stop"]),
+      Some((12, 9))
+    );
+    assert_eq!(
+      position(source, &["procs", "web", "cmd", "1"]),
+      Some((4, 5))
+    );
+    assert_eq!(position(source, &["procs", "db", "stop"]), None);
+    assert_eq!(position(source, &["procs", "web", "env"]), None);
+    assert_eq!(position(source, &["server"]), None);
+  }
+
+  #[test]
+  fn json() {
+    let source = "\
+{
+  \"procs\": {
+    \"web\": {
+      \"stop\": 1
+    }
+  }
+}
+";
+    assert_eq!(position(source, &["procs", "web", "stop"]), Some((4, 7)));
+  }
+
+  #[test]
+  fn error_path() {
+    let value: Value =
+      serde_yaml::from_str("procs:\n  web: {stop: 1}").unwrap();
+    let val = Val::new(&value).unwrap();
+    let procs = val.as_object().unwrap();
+    let web = procs[&Value::from("procs")].as_object().unwrap();
+    let err = web[&Value::from("web")].as_str().unwrap_err();
+    assert_eq!(err.to_string(), "Expected string at <config>.procs.web");
+    let err = err.downcast::<ValError>().unwrap();
+    assert_eq!(err.path(), ["procs", "web"]);
+  }
+}